@@ -0,0 +1,263 @@
+// bibliography/bibtex.rs
+//
+// BibTeX import and export. The biblatex crate does the parsing, including string
+// macros and crossrefs; this turns its entries into references. Writing goes the
+// other way, with plain BibTeX entry types and fields, for the LaTeX export. Imported
+// entries are written as they were read, so no braces in them are lost.
+use biblatex::{Bibliography, ChunksExt, Entry, Person};
+
+use super::{Name, Reference};
+use crate::document::{Error, tex};
+
+pub fn parse(source: &str) -> Result<Vec<Reference>, Error> {
+    let bibliography =
+        Bibliography::parse(source).map_err(|error| Error::Bibliography(error.to_string()))?;
+    Ok(bibliography.iter().map(reference).collect())
+}
+
+fn reference(entry: &Entry) -> Reference {
+    let container = field(entry, "journal")
+        .or_else(|| field(entry, "journaltitle"))
+        .or_else(|| field(entry, "booktitle"))
+        .unwrap_or_default();
+    let publisher = field(entry, "publisher")
+        .or_else(|| field(entry, "school"))
+        .or_else(|| field(entry, "institution"))
+        .or_else(|| field(entry, "organization"))
+        .unwrap_or_default();
+
+    Reference {
+        key: entry.key.clone(),
+        kind: entry.entry_type.to_string(),
+        authors: names(entry, "author"),
+        editors: names(entry, "editor"),
+        title: field(entry, "title").unwrap_or_default(),
+        container,
+        year: year(entry),
+        volume: field(entry, "volume").unwrap_or_default(),
+        number: field(entry, "number")
+            .or_else(|| field(entry, "issue"))
+            .unwrap_or_default(),
+        pages: field(entry, "pages")
+            .map(|pages| pages.replace("--", "–"))
+            .unwrap_or_default(),
+        edition: field(entry, "edition").unwrap_or_default(),
+        publisher,
+        place: field(entry, "address")
+            .or_else(|| field(entry, "location"))
+            .unwrap_or_default(),
+        doi: field(entry, "doi").unwrap_or_default(),
+        url: field(entry, "url").unwrap_or_default(),
+        bibtex: source(entry),
+    }
+}
+
+// The entry in BibTeX, with its string macros and crossrefs already resolved, so it
+// needs no other entry. The crate keeps braced groups in field values and writes
+// them back braced. An entry it cannot write is generated from the reference.
+fn source(entry: &Entry) -> String {
+    let mut entry = entry.clone();
+    entry.remove("crossref");
+    entry.remove("xdata");
+    entry.to_bibtex_string().unwrap_or_default()
+}
+
+fn field(entry: &Entry, name: &str) -> Option<String> {
+    let value = entry.get(name)?.format_verbatim();
+    let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!value.is_empty()).then_some(value)
+}
+
+fn names(entry: &Entry, name: &str) -> Vec<Name> {
+    entry
+        .get_as::<Vec<Person>>(name)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|person| {
+            let family = [person.prefix.as_str(), person.name.as_str()]
+                .into_iter()
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let given = [person.given_name.as_str(), person.suffix.as_str()]
+                .into_iter()
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+                .join(", ");
+            Name { family, given }
+        })
+        .collect()
+}
+
+// BibTeX has a year field, BibLaTeX a date that starts with the year.
+fn year(entry: &Entry) -> String {
+    let value = field(entry, "year")
+        .or_else(|| field(entry, "date"))
+        .unwrap_or_default();
+    let digits: String = value.chars().take_while(char::is_ascii_digit).collect();
+    if digits.is_empty() { value } else { digits }
+}
+
+// The entry types of plain BibTeX. BibLaTeX types are mapped to the nearest one.
+const ENTRY_TYPES: [&str; 14] = [
+    "article",
+    "book",
+    "booklet",
+    "conference",
+    "inbook",
+    "incollection",
+    "inproceedings",
+    "manual",
+    "mastersthesis",
+    "misc",
+    "phdthesis",
+    "proceedings",
+    "techreport",
+    "unpublished",
+];
+
+fn entry_type(kind: &str) -> &str {
+    match kind {
+        kind if ENTRY_TYPES.contains(&kind) => kind,
+        "thesis" => "phdthesis",
+        "report" => "techreport",
+        "collection" | "mvcollection" => "book",
+        _ => "misc",
+    }
+}
+
+// A name as BibTeX reads it: "von Last, First", or "von Last, Jr, First" with a suffix,
+// which is kept after the given names. A name with no given names, such as that of an
+// organization, is braced so that BibTeX takes all of it as the family name.
+fn written_name(name: &Name) -> String {
+    let family = tex::escape(&name.family);
+    match name.given.split_once(", ") {
+        Some((given, suffix)) => format!(
+            "{}, {}, {}",
+            family,
+            tex::escape(suffix),
+            tex::escape(given)
+        ),
+        None if name.given.is_empty() => format!("{{{}}}", family),
+        None => format!("{}, {}", family, tex::escape(&name.given)),
+    }
+}
+
+fn name_list(names: &[Name]) -> String {
+    names
+        .iter()
+        .map(written_name)
+        .collect::<Vec<_>>()
+        .join(" and ")
+}
+
+// Write references as BibTeX entries, in the given order.
+pub fn write(references: &[&Reference]) -> String {
+    let mut source = String::new();
+    for reference in references {
+        if !source.is_empty() {
+            source.push('\n');
+        }
+        if !reference.bibtex.is_empty() {
+            source.push_str(&reference.bibtex);
+            source.push('\n');
+            continue;
+        }
+        let kind = entry_type(&reference.kind);
+        let container = match kind {
+            "article" => "journal",
+            _ => "booktitle",
+        };
+        let publisher = match kind {
+            "phdthesis" | "mastersthesis" => "school",
+            "techreport" => "institution",
+            _ => "publisher",
+        };
+        let fields = [
+            ("author", name_list(&reference.authors)),
+            ("editor", name_list(&reference.editors)),
+            ("title", tex::escape(&reference.title)),
+            (container, tex::escape(&reference.container)),
+            ("year", tex::escape(&reference.year)),
+            ("volume", tex::escape(&reference.volume)),
+            ("number", tex::escape(&reference.number)),
+            ("pages", tex::escape(&reference.pages.replace('–', "--"))),
+            ("edition", tex::escape(&reference.edition)),
+            (publisher, tex::escape(&reference.publisher)),
+            ("address", tex::escape(&reference.place)),
+            // Identifiers are not text, but they cannot be allowed to unbalance braces.
+            ("doi", reference.doi.replace(['{', '}'], "")),
+            ("url", reference.url.replace(['{', '}'], "")),
+        ];
+        source.push_str(&format!("@{}{{{},\n", kind, reference.key));
+        for (field, value) in fields.iter().filter(|(_, value)| !value.is_empty()) {
+            source.push_str(&format!("  {} = {{{}}},\n", field, value));
+        }
+        source.push_str("}\n");
+    }
+    source
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imported_entries_keep_their_braces() {
+        let references = parse(
+            "@string{who = {World Health Organization}}
+            @article{report,
+              author = {{World Health Organization} and Smith, Jane},
+              title = {Sequencing {DNA} in {R}wanda},
+              journal = who,
+              year = 2020,
+            }",
+        )
+        .unwrap();
+        let written = write(&references.iter().collect::<Vec<_>>());
+
+        assert!(written.starts_with("@article{report,\n"));
+        assert!(written.contains("author = {{World Health Organization} and Smith, Jane},"));
+        assert!(written.contains("title = {Sequencing {DNA} in {R}wanda},"));
+        assert!(written.contains("journal = {World Health Organization},"));
+    }
+
+    #[test]
+    fn imported_entries_need_no_other_entry() {
+        let references = parse(
+            "@inproceedings{paper, crossref = {proceedings}, title = {Paper}}
+            @proceedings{proceedings, title = {Proceedings}, year = 2021}",
+        )
+        .unwrap();
+        let paper = references.iter().find(|r| r.key == "paper").unwrap();
+        let written = write(&[paper]);
+
+        assert!(!written.contains("crossref"));
+        assert!(written.contains("booktitle = {Proceedings},"));
+        assert!(written.contains("year = {2021},"));
+    }
+
+    #[test]
+    fn generated_entries_brace_names_without_given_names() {
+        let reference = Reference {
+            key: "report".into(),
+            kind: "techreport".into(),
+            authors: vec![
+                Name {
+                    family: "World Health Organization".into(),
+                    given: String::new(),
+                },
+                Name {
+                    family: "Smith".into(),
+                    given: "Jane, Jr".into(),
+                },
+            ],
+            title: "Annual report".into(),
+            ..Default::default()
+        };
+        let written = write(&[&reference]);
+
+        assert!(written.starts_with("@techreport{report,\n"));
+        assert!(written.contains("author = {{World Health Organization} and Smith, Jr, Jane},"));
+    }
+}
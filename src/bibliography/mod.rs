@@ -0,0 +1,280 @@
+// bibliography/mod.rs
+//
+// The references of a document. They are imported from BibTeX files, saved with the
+// document, cited in the text with tokens like `[@key]` and listed in a bibliography
+// section at the end of the document.
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::document::Error;
+use crate::widgets::paragraph::ParagraphStyle;
+use crate::widgets::textarea::{Line, TextStyle};
+
+pub mod bibtex;
+pub mod style;
+
+pub use style::CitationStyle;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Name {
+    // Includes particles like "van", so that "van Gogh" sorts and prints as one.
+    pub family: String,
+    #[serde(default)]
+    pub given: String,
+}
+
+// One work that can be cited. Missing fields are empty.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Reference {
+    pub key: String,
+    // The BibTeX entry type in lowercase, such as "article" or "book".
+    pub kind: String,
+    pub authors: Vec<Name>,
+    pub editors: Vec<Name>,
+    pub title: String,
+    // The journal of an article or the book a chapter appeared in.
+    pub container: String,
+    pub year: String,
+    pub volume: String,
+    pub number: String,
+    pub pages: String,
+    pub edition: String,
+    pub publisher: String,
+    pub place: String,
+    pub doi: String,
+    pub url: String,
+    // The entry as it was imported, in BibTeX, which the LaTeX export writes out
+    // as it is so that braces protecting capitals and whole names are kept. Empty
+    // for references saved before it was kept.
+    pub bibtex: String,
+}
+
+impl Reference {
+    // The text the citation picker matches a query against.
+    fn search_text(&self) -> String {
+        let authors = self
+            .authors
+            .iter()
+            .map(|name| name.family.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!("{} {} {} {}", self.key, authors, self.year, self.title)
+    }
+
+    // A short description for the citation picker, like "Smith 2020: Title".
+    pub fn summary(&self) -> String {
+        let author = match self.authors.as_slice() {
+            [] => String::new(),
+            [only] => only.family.clone(),
+            [first, second] => format!("{} & {}", first.family, second.family),
+            [first, ..] => format!("{} et al.", first.family),
+        };
+        let lead = [author.as_str(), self.year.as_str()]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        match lead.is_empty() {
+            true => self.title.clone(),
+            false => format!("{}: {}", lead, self.title),
+        }
+    }
+}
+
+// The reference store of a document, by citation key.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct References {
+    entries: BTreeMap<String, Reference>,
+}
+
+impl References {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Reference> {
+        self.entries.get(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Reference> {
+        self.entries.values()
+    }
+
+    // Add references, replacing those with the same key. Returns how many were added.
+    pub fn merge(&mut self, references: Vec<Reference>) -> usize {
+        let count = references.len();
+        for reference in references {
+            self.entries.insert(reference.key.clone(), reference);
+        }
+        count
+    }
+
+    // The references that best match a query, best first. An empty query matches
+    // everything in key order.
+    pub fn search(&self, query: &str) -> Vec<&Reference> {
+        let query = query.trim();
+        if query.is_empty() {
+            return self.iter().collect();
+        }
+        let matcher = SkimMatcherV2::default().ignore_case();
+        let mut scored: Vec<(i64, &Reference)> = self
+            .iter()
+            .filter_map(|reference| {
+                let score = matcher.fuzzy_match(&reference.search_text(), query)?;
+                Some((score, reference))
+            })
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().map(|(_, reference)| reference).collect()
+    }
+}
+
+impl FromIterator<Reference> for References {
+    fn from_iter<I: IntoIterator<Item = Reference>>(iter: I) -> Self {
+        let mut references = References::default();
+        references.merge(iter.into_iter().collect());
+        references
+    }
+}
+
+// The token that cites a reference in the text.
+pub fn citation(key: &str) -> String {
+    format!("[@{}]", key)
+}
+
+fn citation_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    // A group of citations like [@smith2020] or [@smith2020; @jones2019, p. 4].
+    PATTERN.get_or_init(|| Regex::new(r"\[(@[^\[\]]*)\]").expect("valid citation pattern"))
+}
+
+fn key_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"@([^\s;,@\]]+)").expect("valid key pattern"))
+}
+
+// A group of citations in a piece of text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Citation {
+    // In bytes.
+    pub range: Range<usize>,
+    // Every key cited, with the locator that follows it, like "p. 4". It is empty for
+    // most keys.
+    pub keys: Vec<(String, String)>,
+}
+
+// The citation groups in a piece of text, in order.
+pub fn citations_in(text: &str) -> Vec<Citation> {
+    citation_pattern()
+        .captures_iter(text)
+        .map(|group| {
+            let inner = &group[1];
+            let found: Vec<_> = key_pattern().captures_iter(inner).collect();
+            let keys = found
+                .iter()
+                .enumerate()
+                .map(|(i, key)| {
+                    let whole = key.get(0).expect("a match has a whole");
+                    let next = found.get(i + 1).map_or(inner.len(), |next| {
+                        next.get(0).expect("a match has a whole").start()
+                    });
+                    // Up to the next key, or the semicolon before it.
+                    let after = inner[whole.end()..next]
+                        .split(';')
+                        .next()
+                        .unwrap_or_default();
+                    let locator = after.trim_matches([',', ' ']);
+                    (key[1].to_string(), locator.to_string())
+                })
+                .collect();
+            Citation {
+                range: group.get(0).expect("a match has a whole").range(),
+                keys,
+            }
+        })
+        .collect()
+}
+
+// The keys cited in a piece of text, in order and with repeats.
+pub fn keys_in(text: &str) -> Vec<String> {
+    citations_in(text)
+        .into_iter()
+        .flat_map(|citation| citation.keys.into_iter().map(|(key, _)| key))
+        .collect()
+}
+
+// Every key cited in the lines, once each, in the order of their first citation.
+pub fn cited_keys(lines: &[Line]) -> Vec<String> {
+    let mut keys: Vec<String> = Vec::new();
+    for line in lines {
+        let text: String = line.content.iter().collect();
+        for key in keys_in(&text) {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+    }
+    keys
+}
+
+// Where an earlier bibliography section starts, so it can be replaced.
+pub fn section_start(lines: &[Line]) -> Option<usize> {
+    lines.iter().rposition(|line| {
+        let text: String = line.content.iter().collect();
+        line.paragraph() == ParagraphStyle::Heading1
+            && CitationStyle::ALL
+                .iter()
+                .any(|style| style.heading() == text.trim())
+    })
+}
+
+// The bibliography section for the cited references: a heading followed by one
+// paragraph per reference, in the order the style sorts them.
+pub fn section(references: &[&Reference], style: CitationStyle, base: TextStyle) -> Vec<Line> {
+    let mut heading = Line::new();
+    heading.set_paragraph(ParagraphStyle::Heading1);
+    heading.push_styled(style.heading().chars(), base);
+
+    let mut sorted = references.to_vec();
+    sorted.sort_by_cached_key(|reference| style::sort_key(reference));
+
+    let mut lines = vec![heading];
+    for reference in sorted {
+        let mut line = Line::new();
+        for piece in style.format(reference) {
+            let mut run = base;
+            if piece.italic {
+                run.font.style = iced::font::Style::Italic;
+            }
+            line.push_styled(piece.text.chars(), run);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Ask the user for a BibTeX file and read the references in it.
+pub async fn import_file() -> Result<Vec<Reference>, Error> {
+    let handle = rfd::AsyncFileDialog::new()
+        .set_title("Import bibliography")
+        .add_filter("BibTeX", &["bib"])
+        .add_filter("All files", &["*"])
+        .pick_file()
+        .await
+        .ok_or(Error::DialogClosed)?;
+
+    let bytes = std::fs::read(handle.path())?;
+    let text = std::str::from_utf8(&bytes).map_err(|_| Error::InvalidEncoding)?;
+    bibtex::parse(text)
+}
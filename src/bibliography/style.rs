@@ -0,0 +1,527 @@
+// bibliography/style.rs
+//
+// Bibliography entries in APA (7th edition), MLA (9th edition) and Chicago
+// (notes and bibliography) style. Every entry is a list of pieces, so titles and
+// journals can be set in italics.
+use std::fmt;
+
+use super::{Name, Reference};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CitationStyle {
+    #[default]
+    Apa,
+    Mla,
+    Chicago,
+}
+
+impl fmt::Display for CitationStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CitationStyle::Apa => "APA",
+            CitationStyle::Mla => "MLA",
+            CitationStyle::Chicago => "Chicago",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl CitationStyle {
+    pub const ALL: [CitationStyle; 3] = [
+        CitationStyle::Apa,
+        CitationStyle::Mla,
+        CitationStyle::Chicago,
+    ];
+
+    // The title of the bibliography section.
+    pub fn heading(self) -> &'static str {
+        match self {
+            CitationStyle::Apa => "References",
+            CitationStyle::Mla => "Works Cited",
+            CitationStyle::Chicago => "Bibliography",
+        }
+    }
+
+    pub fn format(self, reference: &Reference) -> Vec<Piece> {
+        let mut entry = Entry::default();
+        match self {
+            CitationStyle::Apa => apa(&mut entry, reference),
+            CitationStyle::Mla => mla(&mut entry, reference),
+            CitationStyle::Chicago => chicago(&mut entry, reference),
+        }
+        entry.finish()
+    }
+}
+
+// All three styles list entries alphabetically by the first author, or by title
+// when there is none.
+pub fn sort_key(reference: &Reference) -> (String, String, String) {
+    let lead = match reference.authors.first() {
+        Some(name) => format!("{} {}", name.family, name.given),
+        None => reference.title.clone(),
+    };
+    (
+        lead.to_lowercase(),
+        reference.year.clone(),
+        reference.title.to_lowercase(),
+    )
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Piece {
+    pub text: String,
+    pub italic: bool,
+}
+
+// How a work is cited, which is mostly decided by what it appeared in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Article,
+    Book,
+    Chapter,
+    Other,
+}
+
+impl Shape {
+    fn of(reference: &Reference) -> Self {
+        match reference.kind.as_str() {
+            "article" => Shape::Article,
+            "book" | "mvbook" | "collection" | "proceedings" | "booklet" => Shape::Book,
+            "incollection" | "inbook" | "inproceedings" | "bookinbook" | "inreference" => {
+                Shape::Chapter
+            }
+            _ => Shape::Other,
+        }
+    }
+
+    fn quoted_title(self) -> bool {
+        matches!(self, Shape::Article | Shape::Chapter)
+    }
+}
+
+#[derive(Default)]
+struct Entry {
+    pieces: Vec<Piece>,
+}
+
+impl Entry {
+    fn push(&mut self, text: &str, italic: bool) {
+        if text.is_empty() {
+            return;
+        }
+        match self.pieces.last_mut() {
+            Some(last) if last.italic == italic => last.text.push_str(text),
+            _ => self.pieces.push(Piece {
+                text: text.to_string(),
+                italic,
+            }),
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        self.push(text, false);
+    }
+
+    fn italic(&mut self, text: &str) {
+        self.push(text, true);
+    }
+
+    // End a sentence with a period, unless it already ends in punctuation.
+    fn stop(&mut self) {
+        let Some(last) = self.pieces.last() else {
+            return;
+        };
+        let trimmed = last.text.trim_end();
+        if !trimmed.ends_with(['.', '?', '!']) {
+            self.text(".");
+        }
+        self.text(" ");
+    }
+
+    fn finish(mut self) -> Vec<Piece> {
+        if let Some(last) = self.pieces.last_mut() {
+            last.text.truncate(last.text.trim_end().len());
+        }
+        self.pieces.retain(|piece| !piece.text.is_empty());
+        self.pieces
+    }
+}
+
+fn year(reference: &Reference) -> &str {
+    if reference.year.is_empty() {
+        "n.d."
+    } else {
+        &reference.year
+    }
+}
+
+fn link(reference: &Reference) -> String {
+    if !reference.doi.is_empty() {
+        format!("https://doi.org/{}", reference.doi)
+    } else {
+        reference.url.clone()
+    }
+}
+
+// A title with a period inside the closing quote, as the American styles want it.
+fn quoted(title: &str) -> String {
+    if title.ends_with(['.', '?', '!']) {
+        format!("\u{201c}{}\u{201d}", title)
+    } else {
+        format!("\u{201c}{}.\u{201d}", title)
+    }
+}
+
+// "2" becomes "2nd"; anything that is not a number is kept as it is.
+fn edition(reference: &Reference) -> Option<String> {
+    let edition = reference.edition.trim();
+    if edition.is_empty() {
+        return None;
+    }
+    let Ok(number) = edition.parse::<u32>() else {
+        return Some(edition.to_string());
+    };
+    let suffix = match (number % 10, number % 100) {
+        (1, 11) | (2, 12) | (3, 13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    Some(format!("{}{}", number, suffix))
+}
+
+fn initials(given: &str) -> String {
+    given
+        .split_whitespace()
+        .map(|part| {
+            part.split('-')
+                .filter_map(|piece| piece.chars().next())
+                .map(|c| format!("{}.", c))
+                .collect::<Vec<_>>()
+                .join("-")
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn family_first(name: &Name) -> String {
+    if name.given.is_empty() {
+        name.family.clone()
+    } else {
+        format!("{}, {}", name.family, name.given)
+    }
+}
+
+fn given_first(name: &Name) -> String {
+    if name.given.is_empty() {
+        name.family.clone()
+    } else {
+        format!("{} {}", name.given, name.family)
+    }
+}
+
+// Join names like "A, B, and C", or "A and B" for two.
+fn series(names: Vec<String>, conjunction: &str) -> String {
+    match names.as_slice() {
+        [] => String::new(),
+        [only] => only.clone(),
+        [first, second] => format!("{} {} {}", first, conjunction, second),
+        [rest @ .., last] => format!("{}, {} {}", rest.join(", "), conjunction, last),
+    }
+}
+
+// === APA ===
+
+fn apa_name(name: &Name) -> String {
+    if name.given.is_empty() {
+        name.family.clone()
+    } else {
+        format!("{}, {}", name.family, initials(&name.given))
+    }
+}
+
+fn apa_names(names: &[Name]) -> String {
+    match names {
+        [] => String::new(),
+        [only] => apa_name(only),
+        // Up to twenty authors are listed; beyond that the last follows an ellipsis.
+        _ if names.len() > 20 => {
+            let first: Vec<String> = names[..19].iter().map(apa_name).collect();
+            format!(
+                "{}, . . . {}",
+                first.join(", "),
+                apa_name(&names[names.len() - 1])
+            )
+        }
+        [rest @ .., last] => {
+            let rest: Vec<String> = rest.iter().map(apa_name).collect();
+            format!("{}, & {}", rest.join(", "), apa_name(last))
+        }
+    }
+}
+
+fn editor_label(editors: &[Name]) -> &'static str {
+    if editors.len() == 1 { "Ed." } else { "Eds." }
+}
+
+fn apa_editors(names: &[Name]) -> String {
+    let names = names
+        .iter()
+        .map(|name| {
+            format!("{} {}", initials(&name.given), name.family)
+                .trim()
+                .to_string()
+        })
+        .collect();
+    series(names, "&")
+}
+
+fn apa(entry: &mut Entry, reference: &Reference) {
+    let shape = Shape::of(reference);
+    let mut lead = apa_names(&reference.authors);
+    if lead.is_empty() && shape == Shape::Book && !reference.editors.is_empty() {
+        lead = format!(
+            "{} ({})",
+            apa_names(&reference.editors),
+            editor_label(&reference.editors)
+        );
+    }
+
+    let title = |entry: &mut Entry| {
+        if shape.quoted_title() {
+            entry.text(&reference.title);
+        } else {
+            entry.italic(&reference.title);
+            if let Some(edition) = edition(reference) {
+                entry.text(&format!(" ({} ed.)", edition));
+            }
+        }
+        entry.stop();
+    };
+
+    // Without authors the title moves to the front.
+    if lead.is_empty() {
+        title(entry);
+        entry.text(&format!("({})", year(reference)));
+        entry.stop();
+    } else {
+        entry.text(&format!("{} ({})", lead, year(reference)));
+        entry.stop();
+        title(entry);
+    }
+
+    match shape {
+        Shape::Article => {
+            entry.italic(&reference.container);
+            if !reference.volume.is_empty() {
+                entry.text(", ");
+                entry.italic(&reference.volume);
+            }
+            if !reference.number.is_empty() {
+                entry.text(&format!("({})", reference.number));
+            }
+            if !reference.pages.is_empty() {
+                entry.text(&format!(", {}", reference.pages));
+            }
+            entry.stop();
+        }
+        Shape::Chapter => {
+            entry.text("In ");
+            if !reference.editors.is_empty() {
+                entry.text(&format!(
+                    "{} ({}), ",
+                    apa_editors(&reference.editors),
+                    editor_label(&reference.editors)
+                ));
+            }
+            entry.italic(&reference.container);
+            if !reference.pages.is_empty() {
+                entry.text(&format!(" (pp. {})", reference.pages));
+            }
+            entry.stop();
+            entry.text(&reference.publisher);
+            entry.stop();
+        }
+        Shape::Book | Shape::Other => {
+            if shape == Shape::Other && !reference.container.is_empty() {
+                entry.italic(&reference.container);
+                entry.stop();
+            }
+            entry.text(&reference.publisher);
+            entry.stop();
+        }
+    }
+    entry.text(&link(reference));
+}
+
+// === MLA ===
+
+fn mla_names(names: &[Name]) -> String {
+    match names {
+        [] => String::new(),
+        [only] => family_first(only),
+        [first, second] => format!("{}, and {}", family_first(first), given_first(second)),
+        [first, ..] => format!("{}, et al", family_first(first)),
+    }
+}
+
+fn mla(entry: &mut Entry, reference: &Reference) {
+    let shape = Shape::of(reference);
+    let authors = mla_names(&reference.authors);
+    if !authors.is_empty() {
+        entry.text(&authors);
+        entry.stop();
+    }
+    if shape.quoted_title() {
+        entry.text(&quoted(&reference.title));
+        entry.text(" ");
+    } else {
+        entry.italic(&reference.title);
+        entry.stop();
+    }
+
+    // The container and everything after it form one list, separated by commas.
+    let mut details: Vec<String> = Vec::new();
+    match shape {
+        Shape::Article => {
+            if !reference.volume.is_empty() {
+                details.push(format!("vol. {}", reference.volume));
+            }
+            if !reference.number.is_empty() {
+                details.push(format!("no. {}", reference.number));
+            }
+            details.push(reference.year.clone());
+            if !reference.pages.is_empty() {
+                details.push(format!("pp. {}", reference.pages));
+            }
+        }
+        Shape::Chapter => {
+            if !reference.editors.is_empty() {
+                let editors = reference.editors.iter().map(given_first).collect();
+                details.push(format!("edited by {}", series(editors, "and")));
+            }
+            details.push(reference.publisher.clone());
+            details.push(reference.year.clone());
+            if !reference.pages.is_empty() {
+                details.push(format!("pp. {}", reference.pages));
+            }
+        }
+        Shape::Book | Shape::Other => {
+            if let Some(edition) = edition(reference) {
+                details.push(format!("{} ed.", edition));
+            }
+            details.push(reference.publisher.clone());
+            details.push(reference.year.clone());
+        }
+    }
+    details.retain(|detail| !detail.is_empty());
+
+    let container = match shape {
+        Shape::Book => "",
+        _ => reference.container.as_str(),
+    };
+    entry.italic(container);
+    if !details.is_empty() {
+        if !container.is_empty() {
+            entry.text(", ");
+        }
+        entry.text(&details.join(", "));
+    }
+    if !container.is_empty() || !details.is_empty() {
+        entry.stop();
+    }
+    entry.text(&link(reference));
+}
+
+// === Chicago ===
+
+fn chicago_names(names: &[Name]) -> String {
+    let Some((first, rest)) = names.split_first() else {
+        return String::new();
+    };
+    // More than ten authors are cut down to the first seven.
+    if names.len() > 10 {
+        let mut listed = vec![family_first(first)];
+        listed.extend(rest[..6].iter().map(given_first));
+        return format!("{}, et al", listed.join(", "));
+    }
+    let mut listed = vec![family_first(first)];
+    listed.extend(rest.iter().map(given_first));
+    if listed.len() == 2 {
+        return format!("{}, and {}", listed[0], listed[1]);
+    }
+    series(listed, "and")
+}
+
+fn publication(reference: &Reference) -> String {
+    let publisher = match (reference.place.as_str(), reference.publisher.as_str()) {
+        ("", publisher) => publisher.to_string(),
+        (place, "") => place.to_string(),
+        (place, publisher) => format!("{}: {}", place, publisher),
+    };
+    match publisher.is_empty() {
+        true => year(reference).to_string(),
+        false => format!("{}, {}", publisher, year(reference)),
+    }
+}
+
+fn chicago(entry: &mut Entry, reference: &Reference) {
+    let shape = Shape::of(reference);
+    let authors = chicago_names(&reference.authors);
+    if !authors.is_empty() {
+        entry.text(&authors);
+        entry.stop();
+    }
+    if shape.quoted_title() {
+        entry.text(&quoted(&reference.title));
+        entry.text(" ");
+    } else {
+        entry.italic(&reference.title);
+        entry.stop();
+    }
+
+    match shape {
+        Shape::Article => {
+            entry.italic(&reference.container);
+            if !reference.volume.is_empty() {
+                entry.text(&format!(" {}", reference.volume));
+            }
+            if !reference.number.is_empty() {
+                entry.text(&format!(", no. {}", reference.number));
+            }
+            entry.text(&format!(" ({})", year(reference)));
+            if !reference.pages.is_empty() {
+                entry.text(&format!(": {}", reference.pages));
+            }
+            entry.stop();
+        }
+        Shape::Chapter => {
+            entry.text("In ");
+            entry.italic(&reference.container);
+            if !reference.editors.is_empty() {
+                let editors = reference.editors.iter().map(given_first).collect();
+                entry.text(&format!(", edited by {}", series(editors, "and")));
+            }
+            if !reference.pages.is_empty() {
+                entry.text(&format!(", {}", reference.pages));
+            }
+            entry.stop();
+            entry.text(&publication(reference));
+            entry.stop();
+        }
+        Shape::Book | Shape::Other => {
+            if let Some(edition) = edition(reference) {
+                entry.text(&format!("{} ed", edition));
+                entry.stop();
+            }
+            if shape == Shape::Other && !reference.container.is_empty() {
+                entry.italic(&reference.container);
+                entry.stop();
+            }
+            entry.text(&publication(reference));
+            entry.stop();
+        }
+    }
+    entry.text(&link(reference));
+}
@@ -0,0 +1,124 @@
+// document/dictionary.rs
+//
+// Spelling dictionaries on disk. A Hunspell dictionary is a pair of files like
+// en_US.aff and en_US.dic, looked up in the usual system locations or picked by hand.
+// Words added to the dictionary go to a personal word list with one word per line.
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::Error;
+use crate::widgets::hunspell::Dictionary;
+use crate::widgets::spelling::Speller;
+
+// Used when the locale names no language with an installed dictionary.
+const FALLBACK_LANGUAGE: &str = "en_US";
+
+// Where dictionaries are installed, most specific first.
+fn directories() -> Vec<PathBuf> {
+    let mut directories = Vec::new();
+    if let Some(data) = dirs::data_dir() {
+        directories.push(data.join("blackscript").join("dictionaries"));
+        directories.push(data.join("hunspell"));
+    }
+    if let Some(home) = dirs::home_dir() {
+        directories.push(home.join("Library").join("Spelling"));
+    }
+    for system in [
+        "/usr/share/hunspell",
+        "/usr/local/share/hunspell",
+        "/usr/share/myspell",
+        "/usr/share/myspell/dicts",
+        "/Library/Spelling",
+    ] {
+        directories.push(PathBuf::from(system));
+    }
+    directories
+}
+
+// The personal word list, e.g. ~/.local/share/blackscript/personal.dic on Linux.
+fn personal_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("blackscript").join("personal.dic"))
+}
+
+// The language of the locale, like "en_GB" for LANG=en_GB.UTF-8.
+fn locale_language() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .map(|value| {
+            value
+                .split(['.', '@'])
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        })
+        .find(|language| !language.is_empty() && language != "C" && language != "POSIX")
+}
+
+fn find(language: &str) -> Option<PathBuf> {
+    directories()
+        .into_iter()
+        .map(|dir| dir.join(language).with_extension("dic"))
+        .find(|path| path.is_file() && path.with_extension("aff").is_file())
+}
+
+fn read(dic: &Path) -> Result<Speller, Error> {
+    let aff = std::fs::read(dic.with_extension("aff"))?;
+    let words = std::fs::read(dic)?;
+    let dictionary = Dictionary::from_bytes(&aff, &words)
+        .map_err(|error| Error::Dictionary(error.to_string()))?;
+    let name = dic
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(Speller::new(name, dictionary, personal_words()))
+}
+
+fn personal_words() -> Vec<String> {
+    personal_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|text| {
+            text.lines()
+                .map(str::trim)
+                .filter(|word| !word.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Load the dictionary for the language of the locale, or the fallback language.
+pub async fn load_default() -> Result<Speller, Error> {
+    let path = locale_language()
+        .and_then(|language| find(&language))
+        .or_else(|| find(FALLBACK_LANGUAGE))
+        .ok_or(Error::NoDictionary)?;
+    read(&path)
+}
+
+/// Ask the user for a .dic file and load it with the .aff file next to it.
+pub async fn open() -> Result<Speller, Error> {
+    let handle = rfd::AsyncFileDialog::new()
+        .set_title("Load dictionary")
+        .add_filter("Hunspell dictionary", &["dic"])
+        .pick_file()
+        .await
+        .ok_or(Error::DialogClosed)?;
+    read(handle.path())
+}
+
+/// Add a word to the personal word list.
+pub async fn remember(word: String) -> Result<(), Error> {
+    let Some(path) = personal_path() else {
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", word.replace('’', "'"))?;
+    Ok(())
+}
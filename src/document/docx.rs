@@ -0,0 +1,1258 @@
+// document/docx.rs
+//
+// Word documents (.docx). A DOCX file is a zip archive of WordprocessingML parts.
+// Every line becomes a paragraph and every style run a text run with its font, weight,
+// slant, size and underline, written as they look in the editor. Headings use Word's
+// own heading styles, which is what its navigation pane lists. Notes become real
+// footnotes and endnotes.
+//
+// Reading goes the other way and keeps what the editor can show: the text, the font,
+// weight, slant, size and underline of every run, the paragraph styles it knows and
+// the notes. Images, table layouts, tracked changes and comments are dropped, and
+// counted so the user can be told.
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+
+use iced::Font;
+use iced::font::{Family, Style, Weight};
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use super::{Document, Error, native};
+use crate::widgets::notes::{self, NoteKind, Notes, Numbering};
+use crate::widgets::paragraph::ParagraphStyle;
+use crate::widgets::textarea::{Line, TextStyle};
+
+pub const EXTENSION: &str = "docx";
+
+// Font sizes in the editor are in pixels at 96 per inch, Word counts half points.
+const HALF_POINTS_PER_PIXEL: f32 = 1.5;
+
+const NAMESPACES: &str = concat!(
+    r#"xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main" "#,
+    r#"xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships""#,
+);
+
+// The id and the name of the Word style for a paragraph style. Word recognizes its
+// built-in styles by these names.
+fn word_style(paragraph: ParagraphStyle) -> (&'static str, &'static str) {
+    match paragraph {
+        ParagraphStyle::Title => ("Title", "Title"),
+        ParagraphStyle::Heading1 => ("Heading1", "heading 1"),
+        ParagraphStyle::Heading2 => ("Heading2", "heading 2"),
+        ParagraphStyle::Heading3 => ("Heading3", "heading 3"),
+        ParagraphStyle::Body => ("Normal", "Normal"),
+        ParagraphStyle::BlockQuote => ("Quote", "Quote"),
+        ParagraphStyle::Caption => ("Caption", "caption"),
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            // XML has no way to write most control characters.
+            c if c.is_control() && c != '\t' && c != '\n' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// The font name Word should use, if the font names one.
+fn font_name(family: Family) -> Option<&'static str> {
+    match family {
+        Family::Name(name) => Some(name),
+        Family::Serif => Some("Times New Roman"),
+        Family::SansSerif => Some("Arial"),
+        Family::Monospace => Some("Courier New"),
+        Family::Cursive | Family::Fantasy => None,
+    }
+}
+
+fn run_properties(style: &TextStyle) -> String {
+    let mut properties = String::from("<w:rPr>");
+    if let Some(name) = font_name(style.font.family) {
+        let name = escape(name);
+        properties.push_str(&format!(
+            r#"<w:rFonts w:ascii="{0}" w:hAnsi="{0}" w:cs="{0}"/>"#,
+            name
+        ));
+    }
+    let bold = matches!(
+        style.font.weight,
+        Weight::Semibold | Weight::Bold | Weight::ExtraBold | Weight::Black
+    );
+    if bold {
+        properties.push_str("<w:b/>");
+    }
+    if style.font.style != Style::Normal {
+        properties.push_str("<w:i/>");
+    }
+    if style.underline {
+        properties.push_str(r#"<w:u w:val="single"/>"#);
+    }
+    let size = (style.size * HALF_POINTS_PER_PIXEL).round() as u32;
+    properties.push_str(&format!(
+        r#"<w:sz w:val="{0}"/><w:szCs w:val="{0}"/>"#,
+        size
+    ));
+    properties.push_str("</w:rPr>");
+    properties
+}
+
+// A run of text. Tabs are elements of their own in WordprocessingML.
+fn text_run(text: &str, properties: &str) -> String {
+    let mut run = format!("<w:r>{}", properties);
+    for (i, part) in text.split('\t').enumerate() {
+        if i > 0 {
+            run.push_str("<w:tab/>");
+        }
+        if !part.is_empty() {
+            run.push_str(&format!(
+                r#"<w:t xml:space="preserve">{}</w:t>"#,
+                escape(part)
+            ));
+        }
+    }
+    run.push_str("</w:r>");
+    run
+}
+
+// How the notes of the document are numbered in Word, which counts footnotes and
+// endnotes separately from 1 up, like the editor does.
+struct NoteIds {
+    numbering: Numbering,
+    ids: HashMap<u32, usize>,
+    footnotes: Vec<u32>,
+    endnotes: Vec<u32>,
+}
+
+impl NoteIds {
+    fn of(document: &Document) -> Self {
+        let numbering = Numbering::of(&document.lines, &document.notes);
+        let mut note_ids = Self {
+            numbering: Numbering::default(),
+            ids: HashMap::new(),
+            footnotes: Vec::new(),
+            endnotes: Vec::new(),
+        };
+        for id in numbering.order() {
+            let list = match document.notes.kind(*id) {
+                NoteKind::Footnote => &mut note_ids.footnotes,
+                NoteKind::Endnote => &mut note_ids.endnotes,
+            };
+            list.push(*id);
+            note_ids.ids.insert(*id, list.len());
+        }
+        note_ids.numbering = numbering;
+        note_ids
+    }
+}
+
+fn paragraph(
+    line: &Line,
+    document: &Document,
+    note_ids: &NoteIds,
+    referenced: &mut Vec<u32>,
+) -> String {
+    let paragraph = line.paragraph();
+    let mut xml = format!(
+        r#"<w:p><w:pPr><w:pStyle w:val="{}"/></w:pPr>"#,
+        word_style(paragraph).0
+    );
+    for (range, style) in line.runs(0..line.content.len()) {
+        let style = paragraph.apply(style);
+        let properties = run_properties(&style);
+        let mut text = String::new();
+        for c in &line.content[range] {
+            let Some(id) = notes::anchor_id(*c) else {
+                text.push(*c);
+                continue;
+            };
+            if !text.is_empty() {
+                xml.push_str(&text_run(&text, &properties));
+                text.clear();
+            }
+            let element = match document.notes.kind(id) {
+                NoteKind::Footnote => "footnoteReference",
+                NoteKind::Endnote => "endnoteReference",
+            };
+            // A copied anchor shows the number of the note it was copied from; Word
+            // wants a note of its own for every reference, so copies stay plain text.
+            match note_ids.ids.get(&id) {
+                Some(number) if !referenced.contains(&id) => {
+                    referenced.push(id);
+                    xml.push_str(&format!(
+                        r#"<w:r><w:rPr><w:vertAlign w:val="superscript"/></w:rPr><w:{} w:id="{}"/></w:r>"#,
+                        element, number
+                    ));
+                }
+                _ => {
+                    xml.push_str(&format!(
+                        r#"<w:r><w:rPr><w:vertAlign w:val="superscript"/></w:rPr><w:t>{}</w:t></w:r>"#,
+                        escape(note_ids.numbering.label(id))
+                    ));
+                }
+            }
+        }
+        if !text.is_empty() {
+            xml.push_str(&text_run(&text, &properties));
+        }
+    }
+    xml.push_str("</w:p>");
+    xml
+}
+
+fn document_part(document: &Document, note_ids: &NoteIds) -> String {
+    let mut referenced = Vec::new();
+    let mut xml = format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><w:document {}><w:body>"#,
+        NAMESPACES
+    );
+    for line in &document.lines {
+        xml.push_str(&paragraph(line, document, note_ids, &mut referenced));
+    }
+    // Endnotes are numbered in lowercase roman numerals, as in the editor.
+    xml.push_str(
+        r#"<w:sectPr><w:endnotePr><w:numFmt w:val="lowerRoman"/></w:endnotePr></w:sectPr>"#,
+    );
+    xml.push_str("</w:body></w:document>");
+    xml
+}
+
+// footnotes.xml or endnotes.xml. Word expects the separator lines above the notes to
+// be notes themselves, with the ids -1 and 0.
+fn notes_part(document: &Document, ids: &[u32], kind: NoteKind) -> String {
+    let (root, element, reference) = match kind {
+        NoteKind::Footnote => ("footnotes", "footnote", "footnoteRef"),
+        NoteKind::Endnote => ("endnotes", "endnote", "endnoteRef"),
+    };
+    let mut xml = format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><w:{} {}>"#,
+        root, NAMESPACES
+    );
+    xml.push_str(&format!(
+        r#"<w:{0} w:type="separator" w:id="-1"><w:p><w:r><w:separator/></w:r></w:p></w:{0}><w:{0} w:type="continuationSeparator" w:id="0"><w:p><w:r><w:continuationSeparator/></w:r></w:p></w:{0}>"#,
+        element
+    ));
+    for (number, id) in ids.iter().enumerate() {
+        let body = document
+            .notes
+            .get(*id)
+            .map_or("", |note| note.body.as_str());
+        xml.push_str(&format!(
+            r#"<w:{0} w:id="{1}"><w:p><w:pPr><w:pStyle w:val="NoteText"/></w:pPr><w:r><w:rPr><w:vertAlign w:val="superscript"/></w:rPr><w:{2}/></w:r>{3}</w:p></w:{0}>"#,
+            element,
+            number + 1,
+            reference,
+            text_run(&format!(" {}", body), "")
+        ));
+    }
+    xml.push_str(&format!("</w:{}>", root));
+    xml
+}
+
+// The paragraph styles. Headings carry an outline level, which is what puts them in
+// Word's navigation pane.
+fn styles_part() -> String {
+    let mut xml = format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><w:styles {}>"#,
+        NAMESPACES
+    );
+    xml.push_str(r#"<w:style w:type="paragraph" w:default="1" w:styleId="Normal"><w:name w:val="Normal"/><w:qFormat/></w:style>"#);
+    for paragraph in ParagraphStyle::ALL {
+        if paragraph == ParagraphStyle::Body {
+            continue;
+        }
+        let format = paragraph.format();
+        let (id, name) = word_style(paragraph);
+        let mut properties = format!(
+            r#"<w:spacing w:before="{}" w:after="{}"/>"#,
+            twips(format.space_before),
+            twips(format.space_after)
+        );
+        if format.indent_left > 0.0 || format.indent_right > 0.0 {
+            properties.push_str(&format!(
+                r#"<w:ind w:left="{}" w:right="{}"/>"#,
+                twips(format.indent_left),
+                twips(format.indent_right)
+            ));
+        }
+        if let Some(level) = paragraph.heading_level().filter(|level| *level > 0) {
+            properties.push_str(&format!(
+                r#"<w:keepNext/><w:outlineLvl w:val="{}"/>"#,
+                level - 1
+            ));
+        }
+        xml.push_str(&format!(
+            r#"<w:style w:type="paragraph" w:styleId="{}"><w:name w:val="{}"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr>{}</w:pPr></w:style>"#,
+            id, name, properties
+        ));
+    }
+    xml.push_str(r#"<w:style w:type="paragraph" w:styleId="NoteText"><w:name w:val="footnote text"/><w:basedOn w:val="Normal"/></w:style>"#);
+    xml.push_str("</w:styles>");
+    xml
+}
+
+// Paragraph spacing is in multiples of the editor's 16 pixel base size, which is 12
+// points or 240 twentieths of a point.
+fn twips(multiple: f32) -> u32 {
+    (multiple * 240.0).round() as u32
+}
+
+fn content_types(footnotes: bool, endnotes: bool) -> String {
+    let mut xml = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+        r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">"#,
+        r#"<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>"#,
+        r#"<Default Extension="xml" ContentType="application/xml"/>"#,
+        r#"<Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/>"#,
+        r#"<Override PartName="/word/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml"/>"#,
+    ));
+    if footnotes {
+        xml.push_str(r#"<Override PartName="/word/footnotes.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.footnotes+xml"/>"#);
+    }
+    if endnotes {
+        xml.push_str(r#"<Override PartName="/word/endnotes.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.endnotes+xml"/>"#);
+    }
+    xml.push_str("</Types>");
+    xml
+}
+
+const PACKAGE_RELATIONSHIPS: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+    r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+    r#"<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/>"#,
+    r#"</Relationships>"#,
+);
+
+fn document_relationships(footnotes: bool, endnotes: bool) -> String {
+    let mut xml = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+        r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+        r#"<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/>"#,
+    ));
+    if footnotes {
+        xml.push_str(r#"<Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/footnotes" Target="footnotes.xml"/>"#);
+    }
+    if endnotes {
+        xml.push_str(r#"<Relationship Id="rId3" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/endnotes" Target="endnotes.xml"/>"#);
+    }
+    xml.push_str("</Relationships>");
+    xml
+}
+
+pub fn encode(document: &Document) -> Result<Vec<u8>, Error> {
+    let note_ids = NoteIds::of(document);
+    let footnotes = !note_ids.footnotes.is_empty();
+    let endnotes = !note_ids.endnotes.is_empty();
+
+    let mut parts = vec![
+        ("[Content_Types].xml", content_types(footnotes, endnotes)),
+        ("_rels/.rels", PACKAGE_RELATIONSHIPS.to_string()),
+        (
+            "word/_rels/document.xml.rels",
+            document_relationships(footnotes, endnotes),
+        ),
+        ("word/document.xml", document_part(document, &note_ids)),
+        ("word/styles.xml", styles_part()),
+    ];
+    if footnotes {
+        parts.push((
+            "word/footnotes.xml",
+            notes_part(document, &note_ids.footnotes, NoteKind::Footnote),
+        ));
+    }
+    if endnotes {
+        parts.push((
+            "word/endnotes.xml",
+            notes_part(document, &note_ids.endnotes, NoteKind::Endnote),
+        ));
+    }
+
+    let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, xml) in parts {
+        archive
+            .start_file(name, options)
+            .map_err(|e| Error::Malformed(e.to_string()))?;
+        archive.write_all(xml.as_bytes())?;
+    }
+    let cursor = archive
+        .finish()
+        .map_err(|e| Error::Malformed(e.to_string()))?;
+    Ok(cursor.into_inner())
+}
+
+// === Reading ===
+
+// Word's size for text that no style gives a size, in half points.
+const DEFAULT_HALF_POINTS: f32 = 20.0;
+
+// How many basedOn links are followed before a style chain is taken to loop.
+const MAX_STYLE_DEPTH: usize = 16;
+
+// Run properties as Word stores them. Whatever a run leaves unset comes from its
+// character style, then its paragraph style, then the document defaults.
+#[derive(Debug, Clone, Copy, Default)]
+struct RunFormat {
+    font: Option<&'static str>,
+    bold: Option<bool>,
+    italic: Option<bool>,
+    underline: Option<bool>,
+    // In half points.
+    size: Option<f32>,
+}
+
+impl RunFormat {
+    fn or(self, fallback: RunFormat) -> RunFormat {
+        RunFormat {
+            font: self.font.or(fallback.font),
+            bold: self.bold.or(fallback.bold),
+            italic: self.italic.or(fallback.italic),
+            underline: self.underline.or(fallback.underline),
+            size: self.size.or(fallback.size),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct WordStyleDefinition {
+    id: String,
+    name: String,
+    based_on: Option<String>,
+    run: RunFormat,
+    outline_level: Option<usize>,
+}
+
+// The theme fonts that runs may refer to instead of naming a font.
+#[derive(Debug, Clone, Copy, Default)]
+struct Theme {
+    major: Option<&'static str>,
+    minor: Option<&'static str>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Styles {
+    theme: Theme,
+    defaults: RunFormat,
+    definitions: Vec<WordStyleDefinition>,
+    default_paragraph: Option<String>,
+}
+
+impl Styles {
+    fn find(&self, id: &str) -> Option<&WordStyleDefinition> {
+        self.definitions.iter().find(|style| style.id == id)
+    }
+
+    // A style and the styles it is based on, nearest first.
+    fn chain(&self, id: Option<&str>) -> Vec<&WordStyleDefinition> {
+        let mut chain = Vec::new();
+        let mut next = id.and_then(|id| self.find(id));
+        while let Some(style) = next {
+            if chain.len() == MAX_STYLE_DEPTH {
+                break;
+            }
+            chain.push(style);
+            next = style.based_on.as_deref().and_then(|id| self.find(id));
+        }
+        chain
+    }
+
+    fn run_format(&self, id: Option<&str>) -> RunFormat {
+        self.chain(id)
+            .iter()
+            .fold(RunFormat::default(), |format, style| format.or(style.run))
+    }
+
+    fn paragraph_id<'a>(&'a self, id: Option<&'a str>) -> Option<&'a str> {
+        id.or(self.default_paragraph.as_deref())
+    }
+
+    // The editor's paragraph style for a Word paragraph style, from the name of the
+    // style or of one it is based on, or else from its outline level.
+    fn paragraph_style(&self, id: Option<&str>) -> ParagraphStyle {
+        let chain = self.chain(self.paragraph_id(id));
+        chain
+            .iter()
+            .find_map(|style| paragraph_style_named(&style.name))
+            .or_else(|| {
+                chain
+                    .iter()
+                    .find_map(|style| style.outline_level)
+                    .and_then(outline_style)
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn paragraph_style_named(name: &str) -> Option<ParagraphStyle> {
+    let name = name.to_lowercase();
+    match name.as_str() {
+        "title" => Some(ParagraphStyle::Title),
+        "heading 1" => Some(ParagraphStyle::Heading1),
+        "heading 2" => Some(ParagraphStyle::Heading2),
+        "quote" | "intense quote" => Some(ParagraphStyle::BlockQuote),
+        "caption" => Some(ParagraphStyle::Caption),
+        _ => name
+            .strip_prefix("heading ")
+            .and_then(|level| level.parse::<usize>().ok())
+            .filter(|level| *level >= 3)
+            .map(|_| ParagraphStyle::Heading3),
+    }
+}
+
+// Word counts outline levels from 0; level 9 is body text. The editor has three
+// heading levels, so deeper ones become the third.
+fn outline_style(level: usize) -> Option<ParagraphStyle> {
+    match level {
+        0 => Some(ParagraphStyle::Heading1),
+        1 => Some(ParagraphStyle::Heading2),
+        2..=8 => Some(ParagraphStyle::Heading3),
+        _ => None,
+    }
+}
+
+fn malformed(error: impl std::fmt::Display) -> Error {
+    Error::Malformed(error.to_string())
+}
+
+// The value of an attribute, whatever namespace prefix the document gives it.
+fn attribute(element: &BytesStart, name: &[u8]) -> Option<String> {
+    element
+        .attributes()
+        .flatten()
+        .find(|attribute| attribute.key.local_name().as_ref() == name)
+        .and_then(|attribute| attribute.unescape_value().ok())
+        .map(|value| value.into_owned())
+}
+
+// Whether a toggle property like <w:b/> is on. It is unless its value says otherwise.
+fn toggle(element: &BytesStart) -> bool {
+    !matches!(
+        attribute(element, b"val").as_deref(),
+        Some("0" | "false" | "off" | "none")
+    )
+}
+
+// Read one child of a <w:rPr> element into a run format.
+fn run_property(format: &mut RunFormat, element: &BytesStart, theme: &Theme) {
+    match element.local_name().as_ref() {
+        b"b" => format.bold = Some(toggle(element)),
+        b"i" => format.italic = Some(toggle(element)),
+        b"u" => format.underline = Some(toggle(element)),
+        b"sz" => {
+            format.size = attribute(element, b"val").and_then(|value| value.parse().ok());
+        }
+        b"rFonts" => {
+            let theme_font = attribute(element, b"asciiTheme").and_then(|value| {
+                match value.starts_with("major") {
+                    true => theme.major,
+                    false => theme.minor,
+                }
+            });
+            format.font = attribute(element, b"ascii")
+                .map(native::intern)
+                .or(theme_font)
+                .or(format.font);
+        }
+        _ => {}
+    }
+}
+
+fn read_theme(xml: &str) -> Result<Theme, Error> {
+    let mut theme = Theme::default();
+    let mut reader = Reader::from_str(xml);
+    let mut major = false;
+    loop {
+        match reader.read_event().map_err(malformed)? {
+            Event::Start(element) => match element.local_name().as_ref() {
+                b"majorFont" => major = true,
+                b"minorFont" => major = false,
+                _ => {}
+            },
+            Event::Empty(element) if element.local_name().as_ref() == b"latin" => {
+                let font = match major {
+                    true => &mut theme.major,
+                    false => &mut theme.minor,
+                };
+                if font.is_none() {
+                    *font = attribute(&element, b"typeface")
+                        .filter(|name| !name.is_empty())
+                        .map(native::intern);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(theme)
+}
+
+fn read_styles(xml: &str, theme: Theme) -> Result<Styles, Error> {
+    let mut styles = Styles {
+        theme,
+        ..Styles::default()
+    };
+    let mut reader = Reader::from_str(xml);
+    let mut style: Option<WordStyleDefinition> = None;
+    let mut in_defaults = false;
+    let mut in_run = false;
+    loop {
+        match reader.read_event().map_err(malformed)? {
+            Event::Start(element) => match element.local_name().as_ref() {
+                b"rPrDefault" => in_defaults = true,
+                b"rPr" => in_run = true,
+                b"style" => {
+                    let id = attribute(&element, b"styleId").unwrap_or_default();
+                    if attribute(&element, b"type").as_deref() == Some("paragraph")
+                        && attribute(&element, b"default").is_some_and(|value| value == "1")
+                    {
+                        styles.default_paragraph = Some(id.clone());
+                    }
+                    style = Some(WordStyleDefinition {
+                        id,
+                        ..WordStyleDefinition::default()
+                    });
+                }
+                _ => {}
+            },
+            Event::Empty(element) => {
+                if in_run {
+                    let format = match (&mut style, in_defaults) {
+                        (_, true) => &mut styles.defaults,
+                        (Some(style), false) => &mut style.run,
+                        (None, false) => continue,
+                    };
+                    run_property(format, &element, &theme);
+                    continue;
+                }
+                let Some(style) = &mut style else {
+                    continue;
+                };
+                match element.local_name().as_ref() {
+                    b"name" => style.name = attribute(&element, b"val").unwrap_or_default(),
+                    b"basedOn" => style.based_on = attribute(&element, b"val"),
+                    b"outlineLvl" => {
+                        style.outline_level =
+                            attribute(&element, b"val").and_then(|value| value.parse().ok());
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(element) => match element.local_name().as_ref() {
+                b"rPrDefault" => in_defaults = false,
+                b"rPr" => in_run = false,
+                b"style" => styles.definitions.extend(style.take()),
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(styles)
+}
+
+// What the editor has no place for, counted so the user can be told.
+#[derive(Debug, Default)]
+struct Dropped {
+    images: usize,
+    tables: usize,
+    changes: usize,
+    comments: usize,
+}
+
+impl Dropped {
+    // Count an element that is dropped or simplified. Returns whether everything in
+    // it is dropped as well.
+    fn element(&mut self, name: &[u8]) -> bool {
+        match name {
+            b"drawing" | b"pict" | b"object" => {
+                self.images += 1;
+                true
+            }
+            // The cells of a table are kept as paragraphs of their own.
+            b"tbl" => {
+                self.tables += 1;
+                false
+            }
+            // Tracked changes are accepted: insertions stay, deletions go, and earlier
+            // formatting is forgotten.
+            b"ins" | b"moveTo" => {
+                self.changes += 1;
+                false
+            }
+            b"del" | b"moveFrom" => {
+                self.changes += 1;
+                true
+            }
+            name if name.ends_with(b"PrChange") => {
+                self.changes += 1;
+                true
+            }
+            b"commentReference" => {
+                self.comments += 1;
+                false
+            }
+            // Alternate content repeats what comes before it for older readers.
+            b"Fallback" => true,
+            _ => false,
+        }
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let mut add = |count: usize, one: &str, many: &str| {
+            if count > 0 {
+                warnings.push(format!("{} {}", count, if count == 1 { one } else { many }));
+            }
+        };
+        add(self.images, "image", "images");
+        add(self.tables, "table layout", "table layouts");
+        add(self.changes, "tracked change", "tracked changes");
+        add(self.comments, "comment", "comments");
+        warnings
+    }
+}
+
+// The text of every footnote or endnote by its id. Notes are plain text in the
+// editor, so the paragraphs of a note are joined by spaces.
+fn read_notes(xml: &str, dropped: &mut Dropped) -> Result<HashMap<String, String>, Error> {
+    let mut bodies = HashMap::new();
+    let mut reader = Reader::from_str(xml);
+    let mut note: Option<(String, String)> = None;
+    let mut skip = 0;
+    let mut in_text = false;
+    loop {
+        let event = reader.read_event().map_err(malformed)?;
+        if skip > 0 {
+            match event {
+                Event::Start(_) => skip += 1,
+                Event::End(_) => skip -= 1,
+                _ => {}
+            }
+            continue;
+        }
+        match event {
+            Event::Start(element) => match element.local_name().as_ref() {
+                // The separator lines are notes too, with a type of their own.
+                b"footnote" | b"endnote" => {
+                    note = attribute(&element, b"id")
+                        .filter(|_| attribute(&element, b"type").is_none_or(|t| t == "normal"))
+                        .map(|id| (id, String::new()));
+                }
+                b"p" => {
+                    if let Some((_, body)) = &mut note
+                        && !body.is_empty()
+                    {
+                        body.push(' ');
+                    }
+                }
+                b"t" => in_text = true,
+                name => {
+                    if dropped.element(name) {
+                        skip = 1;
+                    }
+                }
+            },
+            Event::Empty(element) => {
+                if element.local_name().as_ref() == b"tab"
+                    && let Some((_, body)) = &mut note
+                {
+                    body.push(' ');
+                }
+            }
+            Event::Text(text) if in_text => {
+                if let Some((_, body)) = &mut note {
+                    body.push_str(&text.unescape().map_err(malformed)?);
+                }
+            }
+            Event::End(element) => match element.local_name().as_ref() {
+                b"t" => in_text = false,
+                b"footnote" | b"endnote" => {
+                    if let Some((id, body)) = note.take() {
+                        bodies.insert(id, body.trim().to_string());
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(bodies)
+}
+
+// The size of a character in the editor, before its paragraph style scales it. Word
+// rounds sizes to half points, so a whole pixel size that rounds to the same is
+// taken to be what was meant.
+fn unscaled_size(half_points: f32, scale: f32) -> f32 {
+    let exact = half_points / HALF_POINTS_PER_PIXEL / scale;
+    let whole = exact.round();
+    match (whole * scale * HALF_POINTS_PER_PIXEL).round() == half_points.round() {
+        true => whole,
+        false => (exact * 10.0).round() / 10.0,
+    }
+}
+
+// The paragraph being read. A line break inside it starts another line in the editor,
+// with the same paragraph style.
+struct OpenParagraph {
+    style_id: Option<String>,
+    outline_level: Option<usize>,
+    style: ParagraphStyle,
+    lines: Vec<Line>,
+}
+
+// The run being read, with its character style and direct formatting.
+#[derive(Default)]
+struct OpenRun {
+    style_id: Option<String>,
+    format: RunFormat,
+}
+
+impl OpenParagraph {
+    // How the text of a run looks in the editor. Paragraph styles already make their
+    // text bold or italic and scale it, so that is taken out again.
+    fn text_style(&self, run: &OpenRun, styles: &Styles) -> TextStyle {
+        let format = run
+            .format
+            .or(styles.run_format(run.style_id.as_deref()))
+            .or(styles.run_format(styles.paragraph_id(self.style_id.as_deref())))
+            .or(styles.defaults);
+        let look = self.style.format();
+        let half_points = format.size.unwrap_or(DEFAULT_HALF_POINTS);
+        TextStyle {
+            font: Font {
+                family: format.font.map_or(Family::Serif, Family::Name),
+                weight: match format.bold.unwrap_or(false) && !look.bold {
+                    true => Weight::Bold,
+                    false => Weight::Normal,
+                },
+                style: match format.italic.unwrap_or(false) && !look.italic {
+                    true => Style::Italic,
+                    false => Style::Normal,
+                },
+                ..Font::default()
+            },
+            size: unscaled_size(half_points, look.scale),
+            underline: format.underline.unwrap_or(false),
+        }
+    }
+
+    // A note anchor looks like the text before it; Word shrinks and raises note
+    // references, which the editor does by itself.
+    fn push_anchor(&mut self, anchor: char, run: &OpenRun, styles: &Styles) {
+        let Some(line) = self.lines.last_mut() else {
+            return;
+        };
+        match line.spans().last().map(|span| span.style()) {
+            Some(style) => line.push_styled([anchor], style),
+            None => self.push(&anchor.to_string(), run, styles),
+        }
+    }
+
+    fn push(&mut self, text: &str, run: &OpenRun, styles: &Styles) {
+        let style = self.text_style(run, styles);
+        if let Some(line) = self.lines.last_mut() {
+            line.push_styled(text.chars(), style);
+        }
+    }
+}
+
+fn read_body(
+    xml: &str,
+    styles: &Styles,
+    footnotes: &HashMap<String, String>,
+    endnotes: &HashMap<String, String>,
+    notes: &mut Notes,
+    dropped: &mut Dropped,
+) -> Result<Vec<Line>, Error> {
+    let mut lines = Vec::new();
+    let mut reader = Reader::from_str(xml);
+    let mut paragraph: Option<OpenParagraph> = None;
+    let mut run = OpenRun::default();
+    let mut skip = 0;
+    let (mut in_properties, mut in_run_properties, mut in_text) = (false, false, false);
+    loop {
+        let event = reader.read_event().map_err(malformed)?;
+        if skip > 0 {
+            match event {
+                Event::Start(_) => skip += 1,
+                Event::End(_) => skip -= 1,
+                _ => {}
+            }
+            continue;
+        }
+        match event {
+            Event::Start(element) => match element.local_name().as_ref() {
+                b"p" => {
+                    paragraph = Some(OpenParagraph {
+                        style_id: None,
+                        outline_level: None,
+                        style: styles.paragraph_style(None),
+                        lines: vec![Line::new()],
+                    });
+                }
+                b"pPr" => in_properties = true,
+                // The properties of the paragraph mark are not those of any text.
+                b"rPr" => in_run_properties = !in_properties,
+                b"r" => run = OpenRun::default(),
+                b"t" => in_text = true,
+                name => {
+                    if dropped.element(name) {
+                        skip = 1;
+                    }
+                }
+            },
+            Event::Empty(element) => {
+                let name = element.local_name();
+                dropped.element(name.as_ref());
+                let Some(open) = &mut paragraph else {
+                    continue;
+                };
+                if in_properties {
+                    match name.as_ref() {
+                        b"pStyle" => open.style_id = attribute(&element, b"val"),
+                        b"outlineLvl" => {
+                            open.outline_level =
+                                attribute(&element, b"val").and_then(|value| value.parse().ok());
+                        }
+                        _ => {}
+                    }
+                } else if in_run_properties {
+                    match name.as_ref() {
+                        b"rStyle" => run.style_id = attribute(&element, b"val"),
+                        _ => run_property(&mut run.format, &element, &styles.theme),
+                    }
+                } else {
+                    match name.as_ref() {
+                        b"tab" => open.push("\t", &run, styles),
+                        b"noBreakHyphen" => open.push("-", &run, styles),
+                        // Page and column breaks have no place in the editor.
+                        b"br"
+                            if attribute(&element, b"type")
+                                .is_none_or(|kind| kind == "textWrapping") =>
+                        {
+                            open.lines.push(Line::new());
+                        }
+                        b"cr" => open.lines.push(Line::new()),
+                        reference @ (b"footnoteReference" | b"endnoteReference") => {
+                            let (kind, bodies) = match reference {
+                                b"footnoteReference" => (NoteKind::Footnote, footnotes),
+                                _ => (NoteKind::Endnote, endnotes),
+                            };
+                            let Some(id) = notes.add(kind) else {
+                                continue;
+                            };
+                            let body = attribute(&element, b"id")
+                                .and_then(|id| bodies.get(&id).cloned())
+                                .unwrap_or_default();
+                            notes.set_body(id, body);
+                            open.push_anchor(notes::anchor(id), &run, styles);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Event::Text(text) if in_text => {
+                if let Some(open) = &mut paragraph {
+                    open.push(&text.unescape().map_err(malformed)?, &run, styles);
+                }
+            }
+            Event::End(element) => match element.local_name().as_ref() {
+                b"pPr" => {
+                    in_properties = false;
+                    if let Some(open) = &mut paragraph {
+                        open.style = match open.outline_level.and_then(outline_style) {
+                            Some(style) => style,
+                            None => styles.paragraph_style(open.style_id.as_deref()),
+                        };
+                    }
+                }
+                b"rPr" => in_run_properties = false,
+                b"t" => in_text = false,
+                b"p" => {
+                    if let Some(open) = paragraph.take() {
+                        lines.extend(open.lines.into_iter().map(|mut line| {
+                            line.set_paragraph(open.style);
+                            line
+                        }));
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if lines.is_empty() {
+        lines.push(Line::new());
+    }
+    Ok(lines)
+}
+
+// Read a Word document. Returns the document together with a description of what in
+// it could not be kept, if anything.
+pub fn decode(bytes: &[u8]) -> Result<(Document, Vec<String>), Error> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(malformed)?;
+    let mut part = |name: &str| -> Result<Option<String>, Error> {
+        match archive.by_name(name) {
+            Ok(mut file) => {
+                let mut xml = String::new();
+                file.read_to_string(&mut xml)?;
+                Ok(Some(xml))
+            }
+            Err(ZipError::FileNotFound) => Ok(None),
+            Err(error) => Err(malformed(error)),
+        }
+    };
+
+    let theme = match part("word/theme/theme1.xml")? {
+        Some(xml) => read_theme(&xml)?,
+        None => Theme::default(),
+    };
+    let styles = match part("word/styles.xml")? {
+        Some(xml) => read_styles(&xml, theme)?,
+        None => Styles {
+            theme,
+            ..Styles::default()
+        },
+    };
+    let mut dropped = Dropped::default();
+    let footnotes = match part("word/footnotes.xml")? {
+        Some(xml) => read_notes(&xml, &mut dropped)?,
+        None => HashMap::new(),
+    };
+    let endnotes = match part("word/endnotes.xml")? {
+        Some(xml) => read_notes(&xml, &mut dropped)?,
+        None => HashMap::new(),
+    };
+    let body = part("word/document.xml")?
+        .ok_or_else(|| Error::Malformed(String::from("no word/document.xml")))?;
+
+    let mut notes = Notes::default();
+    let lines = read_body(
+        &body,
+        &styles,
+        &footnotes,
+        &endnotes,
+        &mut notes,
+        &mut dropped,
+    )?;
+    let document = Document {
+        lines,
+        notes,
+        ..Document::default()
+    };
+    Ok((document, dropped.warnings()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::widgets::notes::Note;
+
+    const GEORGIA: Family = Family::Name("Georgia");
+
+    fn style(weight: Weight, style: Style, size: f32, underline: bool) -> TextStyle {
+        TextStyle {
+            font: Font {
+                family: GEORGIA,
+                weight,
+                style,
+                ..Font::default()
+            },
+            size,
+            underline,
+        }
+    }
+
+    fn line(paragraph: ParagraphStyle, runs: &[(&str, TextStyle)]) -> Line {
+        let mut line = Line::new();
+        line.set_paragraph(paragraph);
+        for (text, style) in runs {
+            line.push_styled(text.chars(), *style);
+        }
+        line
+    }
+
+    fn round_trip(document: &Document) -> Document {
+        let (decoded, dropped) = decode(&encode(document).unwrap()).unwrap();
+        assert!(dropped.is_empty(), "{:?}", dropped);
+        decoded
+    }
+
+    #[test]
+    fn runs_survive_a_round_trip() {
+        let plain = style(Weight::Normal, Style::Normal, 16.0, false);
+        let monospace = TextStyle {
+            font: Font::MONOSPACE,
+            ..plain
+        };
+        let runs = [
+            ("Plain, ", plain),
+            ("bold, ", style(Weight::Bold, Style::Normal, 16.0, false)),
+            (
+                "italic, ",
+                style(Weight::Normal, Style::Italic, 16.0, false),
+            ),
+            (
+                "underlined,",
+                style(Weight::Normal, Style::Normal, 16.0, true),
+            ),
+            ("\tlarge", style(Weight::Bold, Style::Italic, 24.0, true)),
+            (
+                " & <small>",
+                style(Weight::Normal, Style::Normal, 11.0, false),
+            ),
+            (" code", monospace),
+        ];
+        let document = Document {
+            lines: vec![line(ParagraphStyle::Body, &runs)],
+            ..Document::default()
+        };
+
+        let decoded = round_trip(&document);
+        assert_eq!(decoded.lines.len(), 1);
+        let (line, expected) = (&decoded.lines[0], &document.lines[0]);
+        assert_eq!(line.content, expected.content);
+        // Monospace text is written in a font Word has, which is what comes back.
+        let courier = TextStyle {
+            font: Font {
+                family: Family::Name("Courier New"),
+                ..Font::default()
+            },
+            ..plain
+        };
+        let mut spans: Vec<TextStyle> = expected.spans().iter().map(|s| s.style()).collect();
+        *spans.last_mut().unwrap() = courier;
+        let decoded_spans: Vec<TextStyle> = line.spans().iter().map(|s| s.style()).collect();
+        assert_eq!(decoded_spans, spans);
+        let lengths = |line: &Line| line.spans().iter().map(|s| s.len).collect::<Vec<_>>();
+        assert_eq!(lengths(line), lengths(expected));
+    }
+
+    #[test]
+    fn paragraph_styles_survive_a_round_trip() {
+        let plain = style(Weight::Normal, Style::Normal, 16.0, false);
+        let document = Document {
+            lines: ParagraphStyle::ALL
+                .iter()
+                .map(|paragraph| line(*paragraph, &[(paragraph.key(), plain)]))
+                .collect(),
+            ..Document::default()
+        };
+
+        let decoded = round_trip(&document);
+        assert_eq!(decoded.lines.len(), ParagraphStyle::ALL.len());
+        for (line, paragraph) in decoded.lines.iter().zip(ParagraphStyle::ALL) {
+            assert_eq!(line.paragraph(), paragraph);
+            assert_eq!(line.content, paragraph.key().chars().collect::<Vec<_>>());
+            assert_eq!(line.style_at(0), Some(plain), "{:?}", paragraph);
+        }
+    }
+
+    #[test]
+    fn notes_survive_a_round_trip() {
+        let plain = style(Weight::Normal, Style::Normal, 16.0, false);
+        let text = format!(
+            "One{} two{} three{}.",
+            notes::anchor(4),
+            notes::anchor(9),
+            notes::anchor(2)
+        );
+        let mut document = Document {
+            lines: vec![line(ParagraphStyle::Body, &[(&text, plain)])],
+            ..Document::default()
+        };
+        let notes = [
+            (4, NoteKind::Footnote, "First footnote."),
+            (9, NoteKind::Endnote, "An endnote & more."),
+            (2, NoteKind::Footnote, "Second footnote."),
+        ];
+        for (id, kind, body) in notes {
+            let body = String::from(body);
+            document.notes.insert(id, Note { kind, body });
+        }
+
+        let decoded = round_trip(&document);
+        // Notes get new ids in the order of their anchors.
+        let expected = format!(
+            "One{} two{} three{}.",
+            notes::anchor(0),
+            notes::anchor(1),
+            notes::anchor(2)
+        );
+        let content: String = decoded.lines[0].content.iter().collect();
+        assert_eq!(content, expected);
+        let decoded_notes: Vec<(u32, NoteKind, &str)> = decoded
+            .notes
+            .iter()
+            .map(|(id, note)| (id, note.kind, note.body.as_str()))
+            .collect();
+        assert_eq!(
+            decoded_notes,
+            [
+                (0, NoteKind::Footnote, "First footnote."),
+                (1, NoteKind::Endnote, "An endnote & more."),
+                (2, NoteKind::Footnote, "Second footnote."),
+            ]
+        );
+    }
+
+    // A package holding only the given document.xml.
+    fn package(body: &str) -> Vec<u8> {
+        let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+        archive
+            .start_file("word/document.xml", SimpleFileOptions::default())
+            .unwrap();
+        let xml = format!(
+            r#"<?xml version="1.0"?><w:document {}><w:body>{}</w:body></w:document>"#,
+            NAMESPACES, body
+        );
+        archive.write_all(xml.as_bytes()).unwrap();
+        archive.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn what_cannot_be_kept_is_counted() {
+        let bytes = package(concat!(
+            r#"<w:p><w:r><w:t>Before</w:t></w:r>"#,
+            r#"<w:r><w:drawing><w:t>alt text</w:t></w:drawing></w:r></w:p>"#,
+            r#"<w:tbl><w:tr><w:tc><w:p><w:r><w:t>Cell one</w:t></w:r></w:p></w:tc>"#,
+            r#"<w:tc><w:p><w:r><w:t>Cell two</w:t></w:r></w:p></w:tc></w:tr></w:tbl>"#,
+            r#"<w:p><w:r><w:t xml:space="preserve">Kept </w:t></w:r>"#,
+            r#"<w:ins w:id="1"><w:r><w:t>inserted</w:t></w:r></w:ins>"#,
+            r#"<w:del w:id="2"><w:r><w:delText>deleted</w:delText></w:r></w:del></w:p>"#,
+        ));
+
+        let (document, dropped) = decode(&bytes).unwrap();
+        let text: Vec<String> = document
+            .lines
+            .iter()
+            .map(|line| line.content.iter().collect())
+            .collect();
+        assert_eq!(text, ["Before", "Cell one", "Cell two", "Kept inserted"]);
+        assert_eq!(dropped, ["1 image", "1 table layout", "2 tracked changes"]);
+    }
+
+    #[test]
+    fn documents_without_a_body_are_malformed() {
+        let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+        archive
+            .start_file("word/styles.xml", SimpleFileOptions::default())
+            .unwrap();
+        let bytes = archive.finish().unwrap().into_inner();
+        assert!(matches!(decode(&bytes), Err(Error::Malformed(_))));
+        assert!(matches!(decode(b"not a zip"), Err(Error::Malformed(_))));
+    }
+}
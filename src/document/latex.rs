@@ -0,0 +1,525 @@
+// document/latex.rs
+//
+// LaTeX export. Every line becomes a paragraph of an article: headings become
+// sections, the title is set with \maketitle and block quotes go in a quote
+// environment. Bold, italic, underlined and monospace runs are marked up as such;
+// fonts and sizes are left to the document class. Notes become \footnote and
+// \endnote, and citation tokens become \cite commands, with the cited references
+// written to a BibTeX file next to the LaTeX source. The source compiles with
+// pdfLaTeX as well as XeLaTeX and LuaLaTeX.
+use std::path::{Path, PathBuf};
+
+use iced::font::{Family, Style, Weight};
+
+use super::tex::escape;
+use super::{Document, Error};
+use crate::bibliography::{self, Reference, bibtex};
+use crate::widgets::notes::{self, NoteKind, Numbering};
+use crate::widgets::paragraph::ParagraphStyle;
+use crate::widgets::textarea::{Line, TextStyle};
+
+pub const EXTENSION: &str = "tex";
+
+const PREAMBLE: &str = r"\documentclass{article}
+\usepackage{iftex}
+\ifPDFTeX
+  \usepackage[T1]{fontenc}
+  \usepackage[utf8]{inputenc}
+  \usepackage{lmodern}
+  \usepackage{textcomp}
+\else
+  \usepackage{fontspec}
+\fi
+\usepackage[normalem]{ulem}
+";
+
+// What the BibTeX files written here start with, so that exporting again replaces
+// them but never a file that was written by hand.
+const BIBLIOGRAPHY_HEADER: &str =
+    "% The references cited in a document exported from Blackscript.\n\n";
+// How many numbered names are tried for the BibTeX file before giving up.
+const BIBLIOGRAPHY_NAMES: usize = 100;
+
+// Endnotes are numbered in lowercase roman numerals, as in the editor.
+const ENDNOTES: &str = r"\usepackage{endnotes}
+\renewcommand{\theendnote}{\roman{endnote}}
+";
+
+// The LaTeX source of a document, and the BibTeX entries of what it cites.
+pub struct Export {
+    pub source: String,
+    pub bibliography: Option<String>,
+}
+
+/// Ask where to save the LaTeX source, then write it there. The references it cites
+/// go in a BibTeX file next to it.
+pub async fn export(document: Document, title: String) -> Result<PathBuf, Error> {
+    let path = rfd::AsyncFileDialog::new()
+        .set_title("Export to LaTeX")
+        .set_file_name(format!("{}.{}", title, EXTENSION))
+        .add_filter("LaTeX document", &[EXTENSION])
+        .save_file()
+        .await
+        .map(|handle| handle.path().to_path_buf())
+        .ok_or(Error::DialogClosed)?;
+
+    let name = bibliography_name(&path)?;
+    let export = render(&document, &name);
+    if let Some(bibliography) = export.bibliography {
+        std::fs::write(path.with_file_name(format!("{}.bib", name)), bibliography)?;
+    }
+    std::fs::write(&path, export.source)?;
+    Ok(path)
+}
+
+// The name of the BibTeX file for a LaTeX source, like paper-references for
+// paper.tex. A file of that name that an export did not write, or that cannot be
+// read, is left alone for one with a number added. BibTeX cannot find files with
+// spaces or special characters in their names, so those are replaced.
+fn bibliography_name(tex: &Path) -> Result<String, Error> {
+    let stem = tex
+        .file_stem()
+        .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+    let stem: String = stem
+        .chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() || c == '-' || c == '_' => c,
+            _ => '-',
+        })
+        .collect();
+    let free = |name: &String| match std::fs::read(tex.with_file_name(format!("{}.bib", name))) {
+        Ok(bytes) => bytes.starts_with(BIBLIOGRAPHY_HEADER.as_bytes()),
+        Err(error) => error.kind() == std::io::ErrorKind::NotFound,
+    };
+    (1..=BIBLIOGRAPHY_NAMES)
+        .map(|number| match number {
+            1 => format!("{}-references", stem),
+            number => format!("{}-references-{}", stem, number),
+        })
+        .find(free)
+        .ok_or_else(|| {
+            Error::Export(format!(
+                "no name for the BibTeX file of {} is free",
+                tex.display()
+            ))
+        })
+}
+
+struct Writer<'a> {
+    document: &'a Document,
+    numbering: Numbering,
+    // The notes written so far. A copied anchor shows the number of the note it was
+    // copied from, so only the first one becomes a note.
+    written: Vec<u32>,
+    // Every key cited, once each, in the order of their first citation.
+    cited: Vec<String>,
+}
+
+impl Writer<'_> {
+    // Plain text, with its citation tokens as \cite commands.
+    fn text(&mut self, text: &str) -> String {
+        let mut latex = String::new();
+        let mut end = 0;
+        for citation in bibliography::citations_in(text) {
+            latex.push_str(&escape(&text[end..citation.range.start]));
+            latex.push_str(&self.cite(&citation.keys));
+            end = citation.range.end;
+        }
+        latex.push_str(&escape(&text[end..]));
+        latex
+    }
+
+    // A group of citations is a single \cite, unless some have a locator, which
+    // \cite takes only one of.
+    fn cite(&mut self, keys: &[(String, String)]) -> String {
+        for (key, _) in keys {
+            if !self.cited.contains(key) {
+                self.cited.push(key.clone());
+            }
+        }
+        if keys.iter().all(|(_, locator)| locator.is_empty()) {
+            let keys: Vec<&str> = keys.iter().map(|(key, _)| key.as_str()).collect();
+            return format!(r"\cite{{{}}}", keys.join(","));
+        }
+        keys.iter()
+            .map(|(key, locator)| match locator.is_empty() {
+                true => format!(r"\cite{{{}}}", key),
+                false => format!(r"\cite[{}]{{{}}}", escape(locator), key),
+            })
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    // A note at its anchor. In headings, the command has to survive being moved to
+    // the table of contents.
+    fn note(&mut self, id: u32, heading: bool) -> String {
+        if self.written.contains(&id) || self.document.notes.get(id).is_none() {
+            return format!(r"\textsuperscript{{{}}}", self.numbering.label(id));
+        }
+        self.written.push(id);
+        let body = self
+            .document
+            .notes
+            .get(id)
+            .map_or(String::new(), |note| note.body.clone());
+        let command = match self.document.notes.kind(id) {
+            NoteKind::Footnote => "footnote",
+            NoteKind::Endnote => "endnote",
+        };
+        let protect = if heading { r"\protect" } else { "" };
+        format!(r"{}\{}{{{}}}", protect, command, self.text(&body))
+    }
+
+    // The text of a line with its runs marked up. Notes and citations go outside the
+    // markup, which not every command can take them in. Citations are found in the
+    // whole line, so a token stays one citation even if part of it is styled
+    // differently.
+    fn line(&mut self, line: &Line) -> String {
+        let heading = line.paragraph().is_heading();
+        let text: String = line.content.iter().collect();
+        let mut citations = bibliography::citations_in(&text).into_iter().peekable();
+        let mut offset = 0;
+        let mut skip_to = 0;
+
+        let mut latex = String::new();
+        for (range, style) in line.runs(0..line.content.len()) {
+            let mut plain = String::new();
+            for &c in &line.content[range] {
+                let start = offset;
+                offset += c.len_utf8();
+                if start < skip_to {
+                    continue;
+                }
+                if let Some(citation) = citations.next_if(|citation| citation.range.start == start)
+                {
+                    latex.push_str(&marked_up(&std::mem::take(&mut plain), &style));
+                    latex.push_str(&self.cite(&citation.keys));
+                    skip_to = citation.range.end;
+                } else if let Some(id) = notes::anchor_id(c) {
+                    latex.push_str(&marked_up(&std::mem::take(&mut plain), &style));
+                    latex.push_str(&self.note(id, heading));
+                } else {
+                    plain.push(c);
+                }
+            }
+            latex.push_str(&marked_up(&plain, &style));
+        }
+        latex.trim().to_string()
+    }
+}
+
+fn marked_up(text: &str, style: &TextStyle) -> String {
+    let mut latex = escape(text);
+    if text.trim().is_empty() {
+        return latex;
+    }
+    if style.font.family == Family::Monospace {
+        latex = format!(r"\texttt{{{}}}", latex);
+    }
+    if style.font.style != Style::Normal {
+        latex = format!(r"\emph{{{}}}", latex);
+    }
+    if matches!(
+        style.font.weight,
+        Weight::Semibold | Weight::Bold | Weight::ExtraBold | Weight::Black
+    ) {
+        latex = format!(r"\textbf{{{}}}", latex);
+    }
+    if style.underline {
+        latex = format!(r"\uline{{{}}}", latex);
+    }
+    latex
+}
+
+// The LaTeX source of a document. A bibliography section the editor generated is
+// left out, since BibTeX generates its own from the entries in `bibliography`, the
+// name of the BibTeX file without its extension.
+pub fn render(document: &Document, bibliography: &str) -> Export {
+    let mut lines = document.lines.as_slice();
+    if let Some(start) = bibliography::section_start(lines)
+        && !bibliography::cited_keys(&lines[..start]).is_empty()
+    {
+        lines = &lines[..start];
+    }
+    let numbering = Numbering::of(lines, &document.notes);
+    let mut writer = Writer {
+        document,
+        numbering,
+        written: Vec::new(),
+        cited: Vec::new(),
+    };
+
+    let mut body = String::new();
+    let mut quote = false;
+    for line in lines {
+        let text = writer.line(line);
+        if text.is_empty() {
+            continue;
+        }
+        let paragraph = line.paragraph();
+        if quote != (paragraph == ParagraphStyle::BlockQuote) {
+            quote = !quote;
+            body.push_str(match quote {
+                true => "\\begin{quote}\n",
+                false => "\\end{quote}\n\n",
+            });
+        }
+        let paragraph = match paragraph {
+            ParagraphStyle::Title => {
+                format!("\\title{{{}}}\n\\author{{}}\n\\date{{}}\n\\maketitle", text)
+            }
+            ParagraphStyle::Heading1 => format!(r"\section{{{}}}", text),
+            ParagraphStyle::Heading2 => format!(r"\subsection{{{}}}", text),
+            ParagraphStyle::Heading3 => format!(r"\subsubsection{{{}}}", text),
+            ParagraphStyle::Caption => {
+                format!("\\begin{{center}}\n\\small {}\n\\end{{center}}", text)
+            }
+            ParagraphStyle::Body | ParagraphStyle::BlockQuote => text,
+        };
+        body.push_str(&paragraph);
+        body.push_str("\n\n");
+    }
+    if quote {
+        body.push_str("\\end{quote}\n\n");
+    }
+
+    let endnotes = writer
+        .written
+        .iter()
+        .any(|id| document.notes.kind(*id) == NoteKind::Endnote);
+    // Only references the document has can be listed; other keys are left for LaTeX
+    // to report as undefined.
+    let references: Vec<&Reference> = writer
+        .cited
+        .iter()
+        .filter_map(|key| document.references.get(key))
+        .collect();
+
+    let mut source = String::from(PREAMBLE);
+    if endnotes {
+        source.push_str(ENDNOTES);
+    }
+    source.push_str("\n\\begin{document}\n\n");
+    source.push_str(&body);
+    if endnotes {
+        source.push_str("\\theendnotes\n\n");
+    }
+    if !references.is_empty() {
+        source.push_str(&format!(
+            "\\bibliographystyle{{plain}}\n\\bibliography{{{}}}\n\n",
+            bibliography
+        ));
+    }
+    source.push_str("\\end{document}\n");
+
+    Export {
+        source,
+        bibliography: (!references.is_empty())
+            .then(|| format!("{}{}", BIBLIOGRAPHY_HEADER, bibtex::write(&references))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use iced::Font;
+
+    use super::*;
+    use crate::bibliography::CitationStyle;
+    use crate::widgets::notes::Note;
+
+    const BASE: TextStyle = TextStyle {
+        font: Font::DEFAULT,
+        size: 16.0,
+        underline: false,
+    };
+
+    fn line(paragraph: ParagraphStyle, text: &str) -> Line {
+        let mut line = Line::new();
+        line.set_paragraph(paragraph);
+        line.push_styled(text.chars(), BASE);
+        line
+    }
+
+    fn body(lines: Vec<Line>) -> Document {
+        Document {
+            lines,
+            ..Document::default()
+        }
+    }
+
+    // The source between \begin{document} and \end{document}.
+    fn rendered(document: &Document) -> String {
+        let source = render(document, "paper-references").source;
+        let start = source.find("\\begin{document}\n\n").unwrap() + 18;
+        let end = source.find("\\end{document}").unwrap();
+        source[start..end].to_string()
+    }
+
+    fn reference(key: &str) -> Reference {
+        Reference {
+            key: key.to_string(),
+            kind: String::from("book"),
+            title: format!("About {}", key),
+            ..Reference::default()
+        }
+    }
+
+    #[test]
+    fn runs_are_marked_up_around_citations() {
+        let mut styled = Line::new();
+        let mut bold = BASE;
+        bold.font.weight = Weight::Bold;
+        let mut code = BASE;
+        code.font.family = Family::Monospace;
+        code.underline = true;
+        styled.push_styled("Bold [@smi".chars(), bold);
+        styled.push_styled("th2020] ".chars(), BASE);
+        styled.push_styled("code".chars(), code);
+
+        assert_eq!(
+            rendered(&body(vec![styled])),
+            "\\textbf{Bold }\\cite{smith2020} \\uline{\\texttt{code}}\n\n"
+        );
+    }
+
+    #[test]
+    fn citations_with_locators_are_cited_one_by_one() {
+        let mut document = body(vec![line(
+            ParagraphStyle::Body,
+            "See [@smith2020, p. 4; @jones2019] and [@smith2020; @jones2019] or [@doe].",
+        )]);
+        document
+            .references
+            .merge(vec![reference("smith2020"), reference("doe")]);
+
+        let export = render(&document, "paper-references");
+        assert!(export.source.contains(
+            "See \\cite[p. 4]{smith2020}; \\cite{jones2019} and \\cite{smith2020,jones2019} \
+             or \\cite{doe}.\n\n"
+        ));
+        assert!(
+            export
+                .source
+                .contains("\\bibliographystyle{plain}\n\\bibliography{paper-references}\n")
+        );
+        // Only references the document has are written, in the order of citation.
+        let bibliography = export.bibliography.unwrap();
+        assert!(bibliography.starts_with(BIBLIOGRAPHY_HEADER));
+        let smith = bibliography.find("@book{smith2020,").unwrap();
+        let doe = bibliography.find("@book{doe,").unwrap();
+        assert!(smith < doe);
+        assert!(!bibliography.contains("jones2019"));
+    }
+
+    #[test]
+    fn copied_anchors_show_the_number_of_their_note() {
+        let anchor = notes::anchor(0);
+        let mut document = body(vec![line(
+            ParagraphStyle::Body,
+            &format!("One{} two{}", anchor, anchor),
+        )]);
+        document.notes.insert(
+            0,
+            Note {
+                kind: NoteKind::Footnote,
+                body: String::from("A note & [@smith2020]."),
+            },
+        );
+
+        assert_eq!(
+            rendered(&document),
+            "One\\footnote{A note \\& \\cite{smith2020}.} two\\textsuperscript{1}\n\n"
+        );
+    }
+
+    #[test]
+    fn notes_in_headings_are_protected() {
+        let anchor = notes::anchor(0);
+        let mut document = body(vec![
+            line(ParagraphStyle::Heading1, &format!("Intro{}", anchor)),
+            line(ParagraphStyle::Body, &format!("Text{}", notes::anchor(1))),
+        ]);
+        for (id, kind) in [(0, NoteKind::Endnote), (1, NoteKind::Footnote)] {
+            let body = String::from("Note.");
+            document.notes.insert(id, Note { kind, body });
+        }
+
+        let source = render(&document, "paper-references").source;
+        assert!(
+            source
+                .contains("\\section{Intro\\protect\\endnote{Note.}}\n\nText\\footnote{Note.}\n\n")
+        );
+        assert!(source.contains(ENDNOTES));
+        assert!(source.contains("\\theendnotes\n\n\\end{document}"));
+    }
+
+    #[test]
+    fn block_quotes_are_opened_and_closed() {
+        let document = body(vec![
+            line(ParagraphStyle::Title, "Title"),
+            line(ParagraphStyle::BlockQuote, "First"),
+            line(ParagraphStyle::BlockQuote, ""),
+            line(ParagraphStyle::BlockQuote, "Second"),
+            line(ParagraphStyle::Body, "Between"),
+            line(ParagraphStyle::BlockQuote, "Last"),
+        ]);
+
+        assert_eq!(
+            rendered(&document),
+            "\\title{Title}\n\\author{}\n\\date{}\n\\maketitle\n\n\
+             \\begin{quote}\nFirst\n\nSecond\n\n\\end{quote}\n\n\
+             Between\n\n\
+             \\begin{quote}\nLast\n\n\\end{quote}\n\n"
+        );
+    }
+
+    #[test]
+    fn generated_bibliography_sections_are_left_to_bibtex() {
+        let style = CitationStyle::default();
+        let smith = reference("smith2020");
+        let mut lines = vec![line(ParagraphStyle::Body, "Cited [@smith2020].")];
+        lines.extend(bibliography::section(&[&smith], style, BASE));
+        let mut document = body(lines);
+        document.references.merge(vec![smith]);
+
+        let source = rendered(&document);
+        assert!(!source.contains(style.heading()));
+        assert!(source.ends_with("\\bibliography{paper-references}\n\n"));
+
+        // A heading that only looks like one, with nothing cited before it, stays.
+        let document = body(vec![
+            line(ParagraphStyle::Heading1, style.heading()),
+            line(ParagraphStyle::Body, "Text."),
+        ]);
+        assert_eq!(
+            rendered(&document),
+            format!("\\section{{{}}}\n\nText.\n\n", style.heading())
+        );
+    }
+
+    #[test]
+    fn bibliography_files_never_replace_hand_written_ones() {
+        let directory =
+            std::env::temp_dir().join(format!("blackscript-latex-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let tex = directory.join("my paper.tex");
+        let bib = |name: &str| directory.join(format!("{}.bib", name));
+
+        assert_eq!(bibliography_name(&tex).unwrap(), "my-paper-references");
+        std::fs::write(
+            bib("my-paper-references"),
+            format!("{}@misc{{a,}}", BIBLIOGRAPHY_HEADER),
+        )
+        .unwrap();
+        assert_eq!(bibliography_name(&tex).unwrap(), "my-paper-references");
+        std::fs::write(bib("my-paper-references"), "@misc{a,}").unwrap();
+        assert_eq!(bibliography_name(&tex).unwrap(), "my-paper-references-2");
+
+        for number in 2..=BIBLIOGRAPHY_NAMES {
+            std::fs::write(bib(&format!("my-paper-references-{}", number)), "").unwrap();
+        }
+        let result = bibliography_name(&tex);
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert!(matches!(result, Err(Error::Export(_))));
+    }
+}
@@ -0,0 +1,936 @@
+// document/markdown.rs
+//
+// Markdown (.md). Headings, emphasis, strong text, block quotes and footnotes map to
+// the editor's styles; a heading underlined with = signs is the title, and underlined
+// text is written as <u> tags. List items become paragraphs that start with their
+// marker. What the editor cannot show, like links or code blocks, stays as its
+// Markdown source, code in a monospace font.
+//
+// Reading remembers the source of every paragraph, keyed by how the line it became
+// looks. Lines that still look the same when the document is saved are written back
+// exactly as they were read, hard wrapping and all; only edited ones are written anew.
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+
+use iced::font::{Family, Style, Weight};
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+
+use crate::widgets::notes::{self, NoteKind, Notes, Numbering};
+use crate::widgets::paragraph::ParagraphStyle;
+use crate::widgets::textarea::{Line, TextStyle};
+
+use super::Document;
+
+pub const EXTENSIONS: [&str; 2] = ["md", "markdown"];
+
+// The Markdown a document was read from.
+#[derive(Debug, Clone, Default)]
+pub struct Source {
+    // The source of every line by its fingerprint, in document order.
+    blocks: HashMap<u64, Vec<Block>>,
+    // The footnote definitions by note id.
+    definitions: HashMap<u32, Definition>,
+    // What follows the last line.
+    tail: Vec<Trailer>,
+}
+
+// Link reference definitions and the like after the last line are kept as they are,
+// footnote definitions there are written from the notes in their place.
+#[derive(Debug, Clone)]
+enum Trailer {
+    Text(String),
+    Note(u32),
+}
+
+#[derive(Debug, Clone)]
+struct Block {
+    // Starts with whatever separated it from the block before, blank lines included.
+    source: String,
+    // For the lines of a block that is kept as its source, like a code block: whether
+    // the block goes on after this line.
+    literal: Option<bool>,
+}
+
+#[derive(Debug, Clone)]
+struct Definition {
+    label: String,
+    body: String,
+    source: String,
+}
+
+// A hash of everything about a line that Markdown can express or that the user
+// could change.
+fn fingerprint(line: &Line) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    line.paragraph().hash(&mut hasher);
+    line.content.hash(&mut hasher);
+    for span in line.spans() {
+        (span.len, span.font, span.size.to_bits(), span.underline).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+// === Reading ===
+
+struct List {
+    // The column of its first item.
+    column: Option<usize>,
+    // The indentation of paragraphs after the first in the current item.
+    continuation: String,
+}
+
+struct OpenDefinition {
+    id: u32,
+    label: String,
+    body: String,
+    start: usize,
+}
+
+struct Reader<'a> {
+    source: &'a str,
+    base: TextStyle,
+    lines: Vec<Line>,
+    blocks: HashMap<u64, Vec<Block>>,
+    notes: Notes,
+    // Note ids by lowercase label, which is how Markdown matches them.
+    labels: HashMap<String, u32>,
+    definitions: HashMap<u32, Definition>,
+    definition: Option<OpenDefinition>,
+    definition_ranges: Vec<(u32, Range<usize>)>,
+    line: Option<Line>,
+    // Where the source of the next line starts: the end of the block before it.
+    block_start: usize,
+    last_line_end: usize,
+    quotes: usize,
+    lists: Vec<List>,
+    strong: usize,
+    emphasis: usize,
+    underline: usize,
+    // How deep the reader is inside an element that is kept as its source.
+    skip: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(source: &'a str, base: TextStyle) -> Self {
+        Self {
+            source,
+            base,
+            lines: Vec::new(),
+            blocks: HashMap::new(),
+            notes: Notes::default(),
+            labels: HashMap::new(),
+            definitions: HashMap::new(),
+            definition: None,
+            definition_ranges: Vec::new(),
+            line: None,
+            block_start: 0,
+            last_line_end: 0,
+            quotes: 0,
+            lists: Vec::new(),
+            strong: 0,
+            emphasis: 0,
+            underline: 0,
+            skip: 0,
+        }
+    }
+
+    fn style(&self) -> TextStyle {
+        let mut style = self.base;
+        if self.strong > 0 {
+            style.font.weight = Weight::Bold;
+        }
+        if self.emphasis > 0 {
+            style.font.style = Style::Italic;
+        }
+        style.underline = self.underline > 0;
+        style
+    }
+
+    fn code_style(&self) -> TextStyle {
+        let mut style = self.style();
+        style.font.family = Family::Monospace;
+        style
+    }
+
+    // The column of a position in its line of the source.
+    fn column(&self, position: usize) -> usize {
+        let start = self.source[..position].rfind('\n').map_or(0, |i| i + 1);
+        self.source[start..position].chars().count()
+    }
+
+    fn open(&mut self, paragraph: ParagraphStyle) {
+        let mut line = Line::new();
+        line.set_paragraph(match (paragraph, self.quotes) {
+            (ParagraphStyle::Body, 1..) => ParagraphStyle::BlockQuote,
+            (paragraph, _) => paragraph,
+        });
+        self.line = Some(line);
+    }
+
+    fn push(&mut self, text: &str, style: TextStyle) {
+        if let Some(definition) = &mut self.definition {
+            definition.body.push_str(text);
+            return;
+        }
+        if self.line.is_none() {
+            self.open(ParagraphStyle::Body);
+        }
+        if let Some(line) = &mut self.line {
+            line.push_styled(text.chars(), style);
+        }
+    }
+
+    // Finish the open line, whose source ends at the given position, less the
+    // whitespace before it.
+    fn finish(&mut self, end: usize) {
+        let end = self.source[..end].trim_end().len().max(self.block_start);
+        self.finish_at(end, None);
+    }
+
+    fn finish_at(&mut self, end: usize, literal: Option<bool>) {
+        let Some(line) = self.line.take() else {
+            return;
+        };
+        let block = Block {
+            source: self.source[self.block_start..end].to_string(),
+            literal,
+        };
+        self.blocks
+            .entry(fingerprint(&line))
+            .or_default()
+            .push(block);
+        self.lines.push(line);
+        self.block_start = end;
+        self.last_line_end = end;
+    }
+
+    // Keep a block as its source, a line of the editor for every line of it.
+    fn literal(&mut self, range: Range<usize>) {
+        self.finish(range.start);
+        let style = self.code_style();
+        let text = self.source[range.clone()].trim_end();
+        let mut start = range.start;
+        let mut pieces = text.split('\n').peekable();
+        while let Some(piece) = pieces.next() {
+            let mut line = Line::new();
+            line.push_styled(piece.chars(), style);
+            self.line = Some(line);
+            self.finish_at(start + piece.len(), Some(pieces.peek().is_some()));
+            start += piece.len() + 1;
+        }
+    }
+
+    fn note(&mut self, label: &str) -> Option<u32> {
+        let key = label.to_lowercase();
+        if let Some(id) = self.labels.get(&key) {
+            return Some(*id);
+        }
+        let id = self.notes.add(NoteKind::Footnote)?;
+        self.labels.insert(key, id);
+        Some(id)
+    }
+
+    fn read(&mut self, event: Event, range: Range<usize>) {
+        if self.skip > 0 {
+            match event {
+                Event::Start(_) => self.skip += 1,
+                Event::End(_) => self.skip -= 1,
+                _ => {}
+            }
+            return;
+        }
+        match event {
+            Event::Start(tag) => self.start(tag, range),
+            Event::End(tag) => self.end(tag, range),
+            Event::Text(text) => self.push(&text, self.style()),
+            Event::Code(code) => self.push(&code, self.code_style()),
+            Event::InlineHtml(html) => match html.trim().to_lowercase().as_str() {
+                "<u>" => self.underline += 1,
+                "</u>" => self.underline = self.underline.saturating_sub(1),
+                "<em>" | "<i>" => self.emphasis += 1,
+                "</em>" | "</i>" => self.emphasis = self.emphasis.saturating_sub(1),
+                "<strong>" | "<b>" => self.strong += 1,
+                "</strong>" | "</b>" => self.strong = self.strong.saturating_sub(1),
+                _ => self.push(&html, self.style()),
+            },
+            Event::SoftBreak | Event::HardBreak => self.push(" ", self.style()),
+            Event::FootnoteReference(label) if self.definition.is_none() => {
+                if let Some(id) = self.note(&label) {
+                    self.push(&notes::anchor(id).to_string(), self.style());
+                }
+            }
+            Event::Rule => self.literal(range),
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag, range: Range<usize>) {
+        match tag {
+            Tag::Link { .. } | Tag::Image { .. } => {
+                let source = self.source;
+                self.push(&source[range], self.style());
+                self.skip = 1;
+            }
+            Tag::Paragraph => match &mut self.definition {
+                Some(definition) if !definition.body.is_empty() => definition.body.push(' '),
+                Some(_) => {}
+                // The first paragraph of a list item goes on the line with its marker.
+                None if self.line.is_some() => {}
+                None => {
+                    self.open(ParagraphStyle::Body);
+                    if let Some(list) = self.lists.last() {
+                        let indent = list.continuation.clone();
+                        self.push(&indent, self.base);
+                    }
+                }
+            },
+            _ if self.definition.is_some() => {}
+            Tag::Heading { level, .. } => {
+                self.finish(range.start);
+                let setext = !self.source[range.start..].starts_with('#');
+                self.open(match level {
+                    HeadingLevel::H1 if setext => ParagraphStyle::Title,
+                    HeadingLevel::H1 => ParagraphStyle::Heading1,
+                    HeadingLevel::H2 => ParagraphStyle::Heading2,
+                    _ => ParagraphStyle::Heading3,
+                });
+            }
+            Tag::BlockQuote(_) => self.quotes += 1,
+            Tag::List(_) => {
+                // The text of a tight item ends where a nested list starts.
+                self.finish(range.start);
+                self.lists.push(List {
+                    column: None,
+                    continuation: String::new(),
+                });
+            }
+            Tag::Item => {
+                self.finish(range.start);
+                let column = self.column(range.start);
+                let outer = self.lists.first().and_then(|list| list.column);
+                let indent = " ".repeat(column.saturating_sub(outer.unwrap_or(column)));
+                let marker: String = self.source[range.start..]
+                    .chars()
+                    .take_while(|c| !c.is_whitespace())
+                    .collect();
+                if let Some(list) = self.lists.last_mut() {
+                    list.column.get_or_insert(column);
+                    list.continuation = " ".repeat(indent.len() + marker.chars().count() + 1);
+                }
+                self.open(ParagraphStyle::Body);
+                self.push(&format!("{}{} ", indent, marker), self.base);
+            }
+            Tag::Emphasis => self.emphasis += 1,
+            Tag::Strong => self.strong += 1,
+            Tag::FootnoteDefinition(label) => {
+                self.finish(range.start);
+                if let Some(id) = self.note(&label) {
+                    self.definition = Some(OpenDefinition {
+                        id,
+                        label: label.to_string(),
+                        body: String::new(),
+                        start: range.start,
+                    });
+                }
+            }
+            Tag::CodeBlock(_) | Tag::HtmlBlock | Tag::Table(_) | Tag::MetadataBlock(_) => {
+                self.literal(range);
+                self.skip = 1;
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd, range: Range<usize>) {
+        match tag {
+            TagEnd::FootnoteDefinition => {
+                let Some(definition) = self.definition.take() else {
+                    return;
+                };
+                let end = self.source[..range.end].trim_end().len();
+                let body = definition.body.trim().to_string();
+                self.notes.set_body(definition.id, body.clone());
+                self.definitions.insert(
+                    definition.id,
+                    Definition {
+                        label: definition.label,
+                        body,
+                        source: self.source[definition.start..end].to_string(),
+                    },
+                );
+                self.definition_ranges
+                    .push((definition.id, definition.start..end));
+                self.block_start = self.block_start.max(end);
+            }
+            _ if self.definition.is_some() => {}
+            TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item => self.finish(range.end),
+            TagEnd::BlockQuote(_) => self.quotes = self.quotes.saturating_sub(1),
+            TagEnd::List(_) => {
+                self.lists.pop();
+            }
+            TagEnd::Emphasis => self.emphasis = self.emphasis.saturating_sub(1),
+            TagEnd::Strong => self.strong = self.strong.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    fn document(mut self) -> Document {
+        self.finish(self.source.len());
+
+        let mut tail = Vec::new();
+        let mut position = self.last_line_end;
+        for (id, range) in &self.definition_ranges {
+            if range.start >= position {
+                tail.push(Trailer::Text(
+                    self.source[position..range.start].to_string(),
+                ));
+                tail.push(Trailer::Note(*id));
+                position = range.end;
+            }
+        }
+        tail.push(Trailer::Text(self.source[position..].to_string()));
+
+        if self.lines.is_empty() {
+            self.lines.push(Line::new());
+        }
+        Document {
+            lines: self.lines,
+            notes: self.notes,
+            markdown: Source {
+                blocks: self.blocks,
+                definitions: self.definitions,
+                tail,
+            },
+            ..Document::default()
+        }
+    }
+}
+
+// Read Markdown, giving its text the given style.
+pub fn decode(text: &str, base: TextStyle) -> Document {
+    let mut reader = Reader::new(text, base);
+    for (event, range) in Parser::new_ext(text, Options::ENABLE_FOOTNOTES).into_offset_iter() {
+        reader.read(event, range);
+    }
+    reader.document()
+}
+
+// === Writing ===
+
+// The length of the list marker a line starts with, after its indentation.
+fn list_marker(text: &[char]) -> Option<usize> {
+    match text {
+        ['-' | '+' | '*', ' ', ..] => Some(1),
+        _ => {
+            let digits = text.iter().take_while(|c| c.is_ascii_digit()).count();
+            matches!(text.get(digits..digits + 2), Some(['.' | ')', ' ']))
+                .then_some(digits + 1)
+                .filter(|_| (1..=9).contains(&digits))
+        }
+    }
+}
+
+fn is_item(line: &Line) -> bool {
+    let indent = line.content.iter().take_while(|c| **c == ' ').count();
+    matches!(
+        line.paragraph(),
+        ParagraphStyle::Body | ParagraphStyle::BlockQuote
+    ) && list_marker(&line.content[indent..]).is_some()
+}
+
+// List items follow each other directly; everything else is separated by a blank
+// line.
+fn separator(previous: Option<&Line>, line: &Line) -> &'static str {
+    match previous {
+        Some(previous) if is_item(previous) && is_item(line) => "\n",
+        _ => "\n\n",
+    }
+}
+
+// Whether an ampersand starts something that reads as an entity, like &amp;.
+fn is_entity(rest: &[char]) -> bool {
+    let rest = rest.strip_prefix(&['#']).unwrap_or(rest);
+    let name = rest
+        .iter()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .count();
+    name > 0 && rest.get(name) == Some(&';')
+}
+
+// Write a character of plain text, escaped where Markdown would read it as markup.
+fn push_escaped(text: &mut String, content: &[char], i: usize, title: bool) {
+    let c = content[i];
+    let previous = i.checked_sub(1).map(|j| content[j]);
+    let next = content.get(i + 1).copied();
+    let space = |c: Option<char>| c.is_none_or(char::is_whitespace);
+    let word = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+    let indent = content.iter().take_while(|c| **c == ' ').count();
+    let at_start = i == indent && indent < 4;
+
+    let escape = match c {
+        '\\' => next.is_some_and(|c| c.is_ascii_punctuation()),
+        '`' => true,
+        '*' => !(space(previous) && space(next)) || (title && at_start),
+        '_' => !((word(previous) && word(next)) || (space(previous) && space(next))),
+        '&' => is_entity(&content[i + 1..]),
+        '[' => next == Some('^'),
+        '#' | '>' if at_start => true,
+        '~' => at_start && content[i..].starts_with(&['~', '~', '~']),
+        // A line of dashes would be a horizontal rule. The title is a setext heading,
+        // whose text cannot be a list item either.
+        '-' if at_start => {
+            let rest = &content[i..];
+            (rest.iter().all(|c| *c == '-' || *c == ' ')
+                && rest.iter().filter(|c| **c == '-').count() >= 3)
+                || (title && next == Some(' '))
+        }
+        '+' if title && at_start => next == Some(' '),
+        '.' | ')' if title => list_marker(&content[indent..]) == Some(i - indent + 1),
+        _ => false,
+    };
+    if escape {
+        text.push('\\');
+    }
+    text.push(c);
+}
+
+// The kinds of markup a run of text can be in, outermost first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Markup {
+    Underline,
+    Strong,
+    Emphasis,
+    Code,
+}
+
+impl Markup {
+    fn of(style: &TextStyle) -> Vec<Markup> {
+        let mut markup = Vec::new();
+        if style.underline {
+            markup.push(Markup::Underline);
+        }
+        if matches!(
+            style.font.weight,
+            Weight::Semibold | Weight::Bold | Weight::ExtraBold | Weight::Black
+        ) {
+            markup.push(Markup::Strong);
+        }
+        if style.font.style != Style::Normal {
+            markup.push(Markup::Emphasis);
+        }
+        if style.font.family == Family::Monospace {
+            markup.push(Markup::Code);
+        }
+        markup
+    }
+}
+
+// Write the text of a line with its character styles as markup. Markup that lasts
+// longer opens first, so that it goes around shorter markup, and markup opens after
+// and closes before the spaces around what it marks up, as Markdown requires.
+fn write_text(line: &Line, labels: &HashMap<u32, String>) -> String {
+    let content = &line.content;
+    let title = line.paragraph() == ParagraphStyle::Title;
+    let runs = line.runs(0..content.len());
+    let blank = |range: &Range<usize>| content[range.clone()].iter().all(|c| c.is_whitespace());
+    let trailing = |range: &Range<usize>| {
+        content[range.clone()]
+            .iter()
+            .rev()
+            .take_while(|c| c.is_whitespace())
+            .count()
+    };
+    let markup: Vec<Vec<Markup>> = runs
+        .iter()
+        .map(|(range, style)| match blank(range) {
+            true => Vec::new(),
+            false => Markup::of(style),
+        })
+        .collect();
+    // Where markup that is on in a run ends, ignoring blank runs in between.
+    let end_of = |k: usize, m: Markup| {
+        let mut last = k;
+        for (j, (range, _)) in runs.iter().enumerate().skip(k + 1) {
+            if blank(range) {
+                continue;
+            }
+            if !markup[j].contains(&m) {
+                break;
+            }
+            last = j;
+        }
+        let range = &runs[last].0;
+        range.end - trailing(range)
+    };
+    let code_has_ticks = runs.iter().any(|(range, style)| {
+        style.font.family == Family::Monospace && content[range.clone()].contains(&'`')
+    });
+    let fence = match code_has_ticks {
+        true => ("`` ", " ``"),
+        false => ("`", "`"),
+    };
+
+    let mut text = String::new();
+    let mut open: Vec<(Markup, &'static str)> = Vec::new();
+    let mut pending = String::new();
+    for (k, (range, _)) in runs.iter().enumerate() {
+        let run = &content[range.clone()];
+        if blank(range) {
+            pending.extend(run);
+            continue;
+        }
+        let lead = run.iter().take_while(|c| c.is_whitespace()).count();
+        let trail = trailing(range);
+        let start = range.start + lead;
+
+        let mut closed = None;
+        if let Some(keep) = open.iter().position(|(m, _)| !markup[k].contains(m)) {
+            for (_, close) in open.drain(keep..).rev() {
+                text.push_str(close);
+            }
+            closed = Some(text.len());
+        }
+        text.push_str(&pending);
+        pending.clear();
+        text.extend(&run[..lead]);
+
+        let mut opening: Vec<(Markup, usize)> = markup[k]
+            .iter()
+            .filter(|m| !open.iter().any(|(o, _)| o == *m))
+            .map(|m| (*m, end_of(k, *m)))
+            .collect();
+        // Code cannot contain other markup, so it goes inside.
+        opening.sort_by_key(|(m, end)| (*m == Markup::Code, std::cmp::Reverse(*end)));
+        for (m, end) in opening {
+            let (before, after) = match m {
+                Markup::Underline => ("<u>", "</u>"),
+                Markup::Strong => ("**", "**"),
+                // Underscores read better next to asterisks, but do not work within
+                // a word.
+                Markup::Emphasis => {
+                    let within = start
+                        .checked_sub(1)
+                        .is_some_and(|i| content[i].is_alphanumeric())
+                        || content.get(end).is_some_and(|c| c.is_alphanumeric());
+                    match within {
+                        true => ("*", "*"),
+                        false => ("_", "_"),
+                    }
+                }
+                Markup::Code => fence,
+            };
+            // Asterisks that close markup and open other right away are ambiguous.
+            let (before, after) = match (m, before) {
+                (Markup::Strong | Markup::Emphasis, "*" | "**")
+                    if closed == Some(text.len()) && text.ends_with('*') =>
+                {
+                    match m {
+                        Markup::Strong => ("<strong>", "</strong>"),
+                        _ => ("<em>", "</em>"),
+                    }
+                }
+                _ => (before, after),
+            };
+            text.push_str(before);
+            open.push((m, after));
+        }
+
+        let code = open.iter().any(|(m, _)| *m == Markup::Code);
+        for i in start..range.end - trail {
+            match notes::anchor_id(content[i]) {
+                Some(id) => {
+                    let label = labels.get(&id).map_or("?", String::as_str);
+                    text.push_str(&format!("[^{}]", label));
+                }
+                None if code => text.push(content[i]),
+                None => push_escaped(&mut text, content, i, title),
+            }
+        }
+        pending.extend(&run[run.len() - trail..]);
+    }
+    for (_, close) in open.into_iter().rev() {
+        text.push_str(close);
+    }
+    text.push_str(&pending);
+    text
+}
+
+fn write_line(line: &Line, labels: &HashMap<u32, String>) -> String {
+    let text = write_text(line, labels);
+    match line.paragraph() {
+        ParagraphStyle::Title => {
+            let width = text.chars().count().clamp(3, 80);
+            format!("{}\n{}", text, "=".repeat(width))
+        }
+        ParagraphStyle::Heading1 => format!("# {}", text),
+        ParagraphStyle::Heading2 => format!("## {}", text),
+        ParagraphStyle::Heading3 => format!("### {}", text),
+        ParagraphStyle::BlockQuote => format!("> {}", text),
+        // Markdown has no captions.
+        ParagraphStyle::Body | ParagraphStyle::Caption => text,
+    }
+}
+
+// The label of every note. Notes that were read keep theirs; new ones are numbered
+// with the first numbers not taken yet.
+fn labels(source: &Source, numbering: &Numbering) -> HashMap<u32, String> {
+    let mut labels: HashMap<u32, String> = numbering
+        .order()
+        .iter()
+        .filter_map(|id| Some((*id, source.definitions.get(id)?.label.clone())))
+        .collect();
+    let mut next = 1;
+    for id in numbering.order() {
+        if labels.contains_key(id) {
+            continue;
+        }
+        while labels.values().any(|label| *label == next.to_string()) {
+            next += 1;
+        }
+        labels.insert(*id, next.to_string());
+    }
+    labels
+}
+
+// A footnote definition as it was read, unless the note was edited since.
+fn definition(document: &Document, id: u32, labels: &HashMap<u32, String>) -> String {
+    let body = document.notes.get(id).map_or("", |note| note.body.as_str());
+    match document.markdown.definitions.get(&id) {
+        Some(definition) if definition.body == body => definition.source.clone(),
+        _ => {
+            let chars: Vec<char> = body.chars().collect();
+            let mut escaped = String::new();
+            for i in 0..chars.len() {
+                push_escaped(&mut escaped, &chars, i, false);
+            }
+            format!("[^{}]: {}", labels[&id], escaped)
+        }
+    }
+}
+
+// Write a document as Markdown. Endnotes become footnotes, as Markdown only has those.
+pub fn encode(document: &Document) -> String {
+    let source = &document.markdown;
+    let numbering = Numbering::of(&document.lines, &document.notes);
+    let labels = labels(source, &numbering);
+    let mut blocks = source.blocks.clone();
+
+    let mut text = String::new();
+    let mut previous = None;
+    // Whether the line before was inside a block kept as its source, which goes on.
+    let mut in_block = false;
+    for line in &document.lines {
+        let kept = blocks
+            .get_mut(&fingerprint(line))
+            .filter(|kept| !kept.is_empty())
+            .map(|kept| kept.remove(0));
+        match kept {
+            Some(block) => {
+                if text.is_empty() {
+                    text.push_str(block.source.trim_start());
+                } else {
+                    if !block.source.starts_with('\n') {
+                        text.push_str(separator(previous, line));
+                    }
+                    text.push_str(&block.source);
+                }
+                in_block = block.literal == Some(true);
+            }
+            // Lines edited or added inside a block kept as its source, like a code
+            // block, are written as they are, whatever their style, up to the next
+            // line kept from the block.
+            None if in_block => {
+                text.push('\n');
+                text.extend(line.content.iter());
+            }
+            None if line.content.is_empty() => continue,
+            None => {
+                if !text.is_empty() {
+                    text.push_str(separator(previous, line));
+                }
+                text.push_str(&write_line(line, &labels));
+                in_block = false;
+            }
+        }
+        previous = Some(line);
+    }
+
+    let mut written = Vec::new();
+    for trailer in &source.tail {
+        match trailer {
+            Trailer::Text(raw) if text.is_empty() => text.push_str(raw.trim_start()),
+            Trailer::Text(raw) => text.push_str(raw),
+            Trailer::Note(id) if numbering.order().contains(id) => {
+                text.push_str(&definition(document, *id, &labels));
+                written.push(*id);
+            }
+            Trailer::Note(_) => {}
+        }
+    }
+    // New notes, and those defined in the middle of the document, go at the end.
+    let rest: Vec<u32> = numbering
+        .order()
+        .iter()
+        .filter(|id| !written.contains(id))
+        .copied()
+        .collect();
+    for (i, id) in rest.into_iter().enumerate() {
+        let gap = if i == 0 { "\n\n" } else { "\n" };
+        while !text.is_empty() && !text.ends_with(gap) {
+            text.push('\n');
+        }
+        text.push_str(&definition(document, id, &labels));
+    }
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use iced::Font;
+
+    use super::*;
+
+    const BASE: TextStyle = TextStyle {
+        font: Font::DEFAULT,
+        size: 16.0,
+        underline: false,
+    };
+
+    fn code() -> TextStyle {
+        TextStyle {
+            font: Font::MONOSPACE,
+            ..BASE
+        }
+    }
+
+    // The index of the line with the given text.
+    fn find(document: &Document, text: &str) -> usize {
+        document
+            .lines
+            .iter()
+            .position(|line| line.content.iter().collect::<String>() == text)
+            .unwrap_or_else(|| panic!("no line {:?}", text))
+    }
+
+    fn edited(source: &str, text: &str, added: &str, style: TextStyle) -> Document {
+        let mut document = decode(source, BASE);
+        let index = find(&document, text);
+        document.lines[index].push_styled(added.chars(), style);
+        document
+    }
+
+    #[test]
+    fn untouched_documents_are_written_as_they_were_read() {
+        let source = r#"Document Title
+==============
+
+Intro paragraph that is hard
+wrapped over *three* lines with __bold__ text.[^note]
+
+# Heading
+
+- item one
+- item two
+  - nested item
+    that wraps
+- item three
+
+1. first
+2. second
+
+> A quote that is
+> wrapped.
+
+```rust
+fn main() {
+
+    println!("hi");
+}
+```
+
+Escaped \*stars\* and \_under\_ and 2 * 3 &amp; `` code`tick ``.
+
+    indented code
+
+Setext heading
+--------------
+
+***
+
+<div>
+html
+</div>
+
+[A link](http://example.com) and <u>underlined</u>.
+
+[^note]: The note body.
+
+[ref]: http://example.com
+"#;
+        assert_eq!(encode(&decode(source, BASE)), source);
+    }
+
+    #[test]
+    fn only_edited_lines_are_rewritten() {
+        let source = "Hard wrapped\nand *untouched*.\n\nPlain paragraph\nwrapped in two.\n\n* list\n* items\n";
+        let document = edited(source, "Plain paragraph wrapped in two.", " Edited.", BASE);
+        assert_eq!(
+            encode(&document),
+            "Hard wrapped\nand *untouched*.\n\nPlain paragraph wrapped in two. Edited.\n\n* list\n* items\n"
+        );
+    }
+
+    #[test]
+    fn edited_inline_code_keeps_its_backticks() {
+        let source = "```\nblock\n```\n\n`cargo build`\n";
+        let document = edited(source, "cargo build", " --release", code());
+        let written = encode(&document);
+        assert_eq!(written, "```\nblock\n```\n\n`cargo build --release`\n");
+
+        let again = decode(&written, BASE);
+        let line = &again.lines[find(&again, "cargo build --release")];
+        assert_eq!(line.paragraph(), ParagraphStyle::Body);
+    }
+
+    #[test]
+    fn edited_headings_keep_their_level() {
+        let document = edited("# `foo`\n\nText.\n", "foo", "bar", code());
+        let written = encode(&document);
+        assert_eq!(written, "# `foobar`\n\nText.\n");
+
+        let again = decode(&written, BASE);
+        let line = &again.lines[find(&again, "foobar")];
+        assert_eq!(line.paragraph(), ParagraphStyle::Heading1);
+    }
+
+    #[test]
+    fn edited_code_block_lines_stay_in_the_block() {
+        let source = "Before.\n\n```\nlet a = 1;\nlet b = 2;\n```\n\nAfter.\n";
+        let mut document = edited(source, "let a = 1;", "0", code());
+        let index = find(&document, "let b = 2;");
+        let mut added = Line::new();
+        added.push_styled("let c = 3;".chars(), code());
+        document.lines.insert(index + 1, added);
+        assert_eq!(
+            encode(&document),
+            "Before.\n\n```\nlet a = 1;0\nlet b = 2;\nlet c = 3;\n```\n\nAfter.\n"
+        );
+    }
+
+    #[test]
+    fn restyled_code_block_lines_stay_in_the_block() {
+        let source = "```\nlet a = 1;\nlet b = 2;\n```\n\nAfter.\n";
+        let mut document = decode(source, BASE);
+        let index = find(&document, "let a = 1;");
+        document.lines[index].restyle(0..3, |style| style.font.weight = Weight::Bold);
+        let index = find(&document, "let b = 2;");
+        document.lines[index].restyle(0..10, |style| *style = BASE);
+        document.lines[index].set_paragraph(ParagraphStyle::Heading2);
+        assert_eq!(encode(&document), source);
+    }
+}
@@ -0,0 +1,205 @@
+// document/mod.rs
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::bibliography::References;
+use crate::widgets::notes::Notes;
+use crate::widgets::textarea::{Line, TextStyle};
+
+pub mod dictionary;
+pub mod docx;
+pub mod latex;
+pub mod markdown;
+pub mod native;
+pub mod pdf;
+pub mod plain;
+pub mod progress;
+pub mod recovery;
+pub mod tex;
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    DialogClosed,
+    Io(io::ErrorKind),
+    InvalidEncoding,
+    UnknownFormat,
+    UnsupportedVersion(u32),
+    Malformed(String),
+    Bibliography(String),
+    NoDictionary,
+    Dictionary(String),
+    Export(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::DialogClosed => write!(f, "No file selected"),
+            Error::Io(kind) => write!(f, "I/O error: {}", kind),
+            Error::InvalidEncoding => write!(f, "File is not valid UTF-8"),
+            Error::UnknownFormat => write!(f, "Unknown document format"),
+            Error::UnsupportedVersion(version) => {
+                write!(f, "Document version {} is not supported", version)
+            }
+            Error::Malformed(reason) => write!(f, "Malformed document: {}", reason),
+            Error::Bibliography(reason) => write!(f, "Could not read bibliography: {}", reason),
+            Error::NoDictionary => write!(f, "No spelling dictionary found"),
+            Error::Dictionary(reason) => write!(f, "Could not read dictionary: {}", reason),
+            Error::Export(reason) => write!(f, "Could not export: {}", reason),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error.kind())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Plain,
+    Native,
+    Docx,
+    Markdown,
+}
+
+impl Format {
+    // The format used when saving to the given path.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case(native::EXTENSION) => Format::Native,
+            Some(ext) if ext.eq_ignore_ascii_case(docx::EXTENSION) => Format::Docx,
+            Some(ext)
+                if markdown::EXTENSIONS
+                    .iter()
+                    .any(|md| ext.eq_ignore_ascii_case(md)) =>
+            {
+                Format::Markdown
+            }
+            _ => Format::Plain,
+        }
+    }
+
+    // The format of a file on disk; the magic header wins over the extension.
+    pub fn detect(path: &Path, bytes: &[u8]) -> Self {
+        if native::is_native(bytes) {
+            Format::Native
+        } else {
+            Self::from_path(path)
+        }
+    }
+}
+
+/// A document as it is saved: the styled lines together with the bodies of the
+/// notes anchored in them and the references they can cite.
+#[derive(Debug, Clone, Default)]
+pub struct Document {
+    pub lines: Vec<Line>,
+    pub notes: Notes,
+    pub references: References,
+    pub goal: Option<progress::Goal>,
+    // The Markdown the document was read from, if it was.
+    pub markdown: markdown::Source,
+}
+
+impl Document {
+    // What saving in a format would leave out. Only the native format keeps the
+    // references and the goal; the others are for sharing the text.
+    pub fn left_out(&self, format: Format) -> Vec<&'static str> {
+        let mut left_out = Vec::new();
+        if format != Format::Native {
+            if !self.references.is_empty() {
+                left_out.push("the references");
+            }
+            if self.goal.is_some() {
+                left_out.push("the word-count goal");
+            }
+        }
+        left_out
+    }
+}
+
+/// What a loaded file turned into. Plain text carries no styling, so the editor
+/// applies its defaults to it. A document converted from another format comes with
+/// a description of what it lost on the way.
+#[derive(Debug, Clone)]
+pub enum Contents {
+    Text(String),
+    Styled(Document),
+    Converted {
+        document: Document,
+        dropped: Vec<String>,
+    },
+}
+
+/// Ask the user for a file and load its contents. Markdown says nothing about fonts,
+/// so its text gets the given style.
+pub async fn open_file(style: TextStyle) -> Result<(PathBuf, Contents), Error> {
+    let handle = rfd::AsyncFileDialog::new()
+        .set_title("Open document")
+        .add_filter(
+            "Documents",
+            &[native::EXTENSION, docx::EXTENSION, "md", "markdown", "txt"],
+        )
+        .add_filter("All files", &["*"])
+        .pick_file()
+        .await
+        .ok_or(Error::DialogClosed)?;
+
+    let path = handle.path().to_path_buf();
+    let contents = load(&path, style)?;
+    Ok((path, contents))
+}
+
+/// Write the document to the given path, asking for one if there is none yet.
+pub async fn save_file(path: Option<PathBuf>, document: Document) -> Result<PathBuf, Error> {
+    let path = match path {
+        Some(path) => path,
+        None => rfd::AsyncFileDialog::new()
+            .set_title("Save document")
+            .set_file_name(format!("Untitled.{}", native::EXTENSION))
+            .add_filter("Blackscript document", &[native::EXTENSION])
+            .add_filter("Word document", &[docx::EXTENSION])
+            .add_filter("Markdown", &markdown::EXTENSIONS)
+            .add_filter("Plain text", &["txt"])
+            .save_file()
+            .await
+            .map(|handle| handle.path().to_path_buf())
+            .ok_or(Error::DialogClosed)?,
+    };
+
+    save(&path, &document)?;
+    Ok(path)
+}
+
+pub fn load(path: &Path, style: TextStyle) -> Result<Contents, Error> {
+    let bytes = std::fs::read(path)?;
+    match Format::detect(path, &bytes) {
+        Format::Native => native::decode(&bytes).map(Contents::Styled),
+        Format::Plain => plain::decode(&bytes).map(Contents::Text),
+        Format::Markdown => {
+            plain::decode(&bytes).map(|text| Contents::Styled(markdown::decode(&text, style)))
+        }
+        Format::Docx => docx::decode(&bytes)
+            .map(|(document, dropped)| Contents::Converted { document, dropped }),
+    }
+}
+
+pub fn save(path: &Path, document: &Document) -> Result<(), Error> {
+    let bytes = match Format::from_path(path) {
+        Format::Native => native::encode(document)?,
+        Format::Docx => docx::encode(document)?,
+        Format::Markdown => plain::encode(&markdown::encode(document)),
+        Format::Plain => plain::encode(&plain::join(&document.lines, &document.notes)),
+    };
+    std::fs::write(path, bytes)?;
+    Ok(())
+}
+
+/// The name shown for a document in the window title.
+pub fn display_name(path: Option<&Path>) -> String {
+    path.and_then(|path| path.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("Untitled"))
+}
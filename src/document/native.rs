@@ -0,0 +1,431 @@
+// document/native.rs
+//
+// The native Blackscript document format (.bsd). A file starts with a magic line
+// followed by a JSON body holding the text of every line together with its style
+// runs, so that per-character fonts and sizes survive a round trip. Note anchors are
+// stored as the characters they are in the text, next to a list of note bodies.
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use iced::Font;
+use iced::font::{Family, Stretch, Style, Weight};
+use serde::{Deserialize, Serialize};
+
+use super::progress::Goal;
+use super::{Document, Error};
+use crate::bibliography::Reference;
+use crate::widgets::notes::{Note, NoteKind, Notes};
+use crate::widgets::paragraph::ParagraphStyle;
+use crate::widgets::textarea::{Line, TextStyle};
+
+pub const EXTENSION: &str = "bsd";
+pub const MAGIC: &[u8] = b"BLACKSCRIPT\n";
+pub const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct StoredDocument {
+    version: u32,
+    fonts: Vec<StoredFont>,
+    lines: Vec<StoredLine>,
+    // Missing in files written before notes existed.
+    #[serde(default)]
+    notes: Vec<StoredNote>,
+    // Missing in files written before references existed.
+    #[serde(default)]
+    references: Vec<Reference>,
+    // Missing in files written before goals existed.
+    #[serde(default)]
+    goal: Option<Goal>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredLine {
+    text: String,
+    runs: Vec<StoredRun>,
+    // The paragraph style key. Missing in files written before paragraph styles existed.
+    #[serde(default)]
+    style: String,
+}
+
+// A run of consecutive characters sharing the same font, size and decoration.
+#[derive(Serialize, Deserialize)]
+struct StoredRun {
+    len: usize,
+    font: usize,
+    size: f32,
+    // Missing in files written before underlines existed.
+    #[serde(default)]
+    underline: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredNote {
+    id: u32,
+    kind: String,
+    body: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredFont {
+    family: String,
+    weight: u16,
+    stretch: String,
+    style: String,
+}
+
+pub fn is_native(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub fn encode(document: &Document) -> Result<Vec<u8>, Error> {
+    let lines = &document.lines;
+    let mut fonts: Vec<Font> = Vec::new();
+    let mut font_indices: HashMap<Font, usize> = HashMap::new();
+    let mut stored_lines = Vec::with_capacity(lines.len());
+
+    for line in lines {
+        let mut runs: Vec<StoredRun> = Vec::new();
+        for span in line.spans() {
+            let index = *font_indices.entry(span.font).or_insert_with(|| {
+                fonts.push(span.font);
+                fonts.len() - 1
+            });
+            runs.push(StoredRun {
+                len: span.len,
+                font: index,
+                size: span.size,
+                underline: span.underline,
+            });
+        }
+
+        stored_lines.push(StoredLine {
+            text: line.content.iter().collect(),
+            runs,
+            style: line.paragraph().key().to_string(),
+        });
+    }
+
+    let document = StoredDocument {
+        version: VERSION,
+        fonts: fonts.iter().map(store_font).collect(),
+        lines: stored_lines,
+        notes: document
+            .notes
+            .iter()
+            .map(|(id, note)| StoredNote {
+                id,
+                kind: note.kind.key().to_string(),
+                body: note.body.clone(),
+            })
+            .collect(),
+        references: document.references.iter().cloned().collect(),
+        goal: document.goal,
+    };
+
+    let mut bytes = MAGIC.to_vec();
+    serde_json::to_writer(&mut bytes, &document).map_err(|e| Error::Malformed(e.to_string()))?;
+    Ok(bytes)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Document, Error> {
+    let body = bytes.strip_prefix(MAGIC).ok_or(Error::UnknownFormat)?;
+    let document: StoredDocument =
+        serde_json::from_slice(body).map_err(|e| Error::Malformed(e.to_string()))?;
+    if document.version > VERSION {
+        return Err(Error::UnsupportedVersion(document.version));
+    }
+
+    let fonts: Vec<Font> = document.fonts.into_iter().map(load_font).collect();
+    let mut lines = Vec::with_capacity(document.lines.len().max(1));
+
+    for stored in document.lines {
+        let mut line = Line::new();
+        line.set_paragraph(ParagraphStyle::from_key(&stored.style).unwrap_or_default());
+        let mut chars = stored.text.chars();
+        for run in stored.runs {
+            let font = *fonts
+                .get(run.font)
+                .ok_or_else(|| Error::Malformed(format!("unknown font index {}", run.font)))?;
+            let style = TextStyle {
+                font,
+                size: run.size,
+                underline: run.underline,
+            };
+            let text: Vec<char> = chars.by_ref().take(run.len).collect();
+            if text.len() < run.len {
+                return Err(Error::Malformed(String::from(
+                    "style runs reach past the end of the line",
+                )));
+            }
+            line.push_styled(text, style);
+        }
+        if chars.next().is_some() {
+            return Err(Error::Malformed(String::from(
+                "style runs do not cover the whole line",
+            )));
+        }
+        lines.push(line);
+    }
+
+    if lines.is_empty() {
+        lines.push(Line::new());
+    }
+
+    let mut notes = Notes::default();
+    for stored in document.notes {
+        let note = Note {
+            kind: NoteKind::from_key(&stored.kind).unwrap_or_default(),
+            body: stored.body,
+        };
+        notes.insert(stored.id, note);
+    }
+    Ok(Document {
+        lines,
+        notes,
+        references: document.references.into_iter().collect(),
+        goal: document.goal,
+        ..Document::default()
+    })
+}
+
+fn store_font(font: &Font) -> StoredFont {
+    let family = match font.family {
+        Family::Name(name) => name.to_string(),
+        Family::Serif => String::from("serif"),
+        Family::SansSerif => String::from("sans-serif"),
+        Family::Cursive => String::from("cursive"),
+        Family::Fantasy => String::from("fantasy"),
+        Family::Monospace => String::from("monospace"),
+    };
+    let weight = match font.weight {
+        Weight::Thin => 100,
+        Weight::ExtraLight => 200,
+        Weight::Light => 300,
+        Weight::Normal => 400,
+        Weight::Medium => 500,
+        Weight::Semibold => 600,
+        Weight::Bold => 700,
+        Weight::ExtraBold => 800,
+        Weight::Black => 900,
+    };
+    let stretch = match font.stretch {
+        Stretch::UltraCondensed => "ultra-condensed",
+        Stretch::ExtraCondensed => "extra-condensed",
+        Stretch::Condensed => "condensed",
+        Stretch::SemiCondensed => "semi-condensed",
+        Stretch::Normal => "normal",
+        Stretch::SemiExpanded => "semi-expanded",
+        Stretch::Expanded => "expanded",
+        Stretch::ExtraExpanded => "extra-expanded",
+        Stretch::UltraExpanded => "ultra-expanded",
+    };
+    let style = match font.style {
+        Style::Normal => "normal",
+        Style::Italic => "italic",
+        Style::Oblique => "oblique",
+    };
+
+    StoredFont {
+        family,
+        weight,
+        stretch: stretch.to_string(),
+        style: style.to_string(),
+    }
+}
+
+fn load_font(stored: StoredFont) -> Font {
+    let family = match stored.family.as_str() {
+        "serif" => Family::Serif,
+        "sans-serif" => Family::SansSerif,
+        "cursive" => Family::Cursive,
+        "fantasy" => Family::Fantasy,
+        "monospace" => Family::Monospace,
+        _ => Family::Name(intern(stored.family)),
+    };
+    let weight = match stored.weight {
+        0..=149 => Weight::Thin,
+        150..=249 => Weight::ExtraLight,
+        250..=349 => Weight::Light,
+        350..=449 => Weight::Normal,
+        450..=549 => Weight::Medium,
+        550..=649 => Weight::Semibold,
+        650..=749 => Weight::Bold,
+        750..=849 => Weight::ExtraBold,
+        _ => Weight::Black,
+    };
+    let stretch = match stored.stretch.as_str() {
+        "ultra-condensed" => Stretch::UltraCondensed,
+        "extra-condensed" => Stretch::ExtraCondensed,
+        "condensed" => Stretch::Condensed,
+        "semi-condensed" => Stretch::SemiCondensed,
+        "semi-expanded" => Stretch::SemiExpanded,
+        "expanded" => Stretch::Expanded,
+        "extra-expanded" => Stretch::ExtraExpanded,
+        "ultra-expanded" => Stretch::UltraExpanded,
+        _ => Stretch::Normal,
+    };
+    let style = match stored.style.as_str() {
+        "italic" => Style::Italic,
+        "oblique" => Style::Oblique,
+        _ => Style::Normal,
+    };
+
+    Font {
+        family,
+        weight,
+        stretch,
+        style,
+    }
+}
+
+// Font family names must be 'static, so every distinct name is leaked exactly once.
+pub fn intern(name: String) -> &'static str {
+    static NAMES: OnceLock<Mutex<Vec<&'static str>>> = OnceLock::new();
+    let mut names = NAMES.get_or_init(Default::default).lock().unwrap();
+    if let Some(existing) = names.iter().find(|existing| **existing == name) {
+        return existing;
+    }
+    let leaked: &'static str = Box::leak(name.into_boxed_str());
+    names.push(leaked);
+    leaked
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::bibliography::Name;
+    use crate::widgets::notes;
+
+    fn style(font: Font, size: f32, underline: bool) -> TextStyle {
+        TextStyle {
+            font,
+            size,
+            underline,
+        }
+    }
+
+    fn line(paragraph: ParagraphStyle, runs: &[(&str, TextStyle)]) -> Line {
+        let mut line = Line::new();
+        line.set_paragraph(paragraph);
+        for (text, style) in runs {
+            line.push_styled(text.chars(), *style);
+        }
+        line
+    }
+
+    // A stored document with one line, as JSON after the magic line.
+    fn stored(fonts: &str, text: &str, runs: &str) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(
+            format!(
+                r#"{{"version":1,"fonts":[{}],"lines":[{{"text":"{}","runs":[{}]}}]}}"#,
+                fonts, text, runs
+            )
+            .bytes(),
+        );
+        bytes
+    }
+
+    const SERIF: &str = r#"{"family":"serif","weight":400,"stretch":"normal","style":"normal"}"#;
+
+    #[test]
+    fn documents_survive_a_round_trip() {
+        let named = Font {
+            family: Family::Name("Liberation Serif"),
+            weight: Weight::Bold,
+            stretch: Stretch::Condensed,
+            style: Style::Italic,
+        };
+        let plain = style(Font::DEFAULT, 16.0, false);
+        let anchor = notes::anchor(0).to_string();
+        let mut lines: Vec<Line> = ParagraphStyle::ALL
+            .iter()
+            .map(|paragraph| line(*paragraph, &[(paragraph.key(), plain)]))
+            .collect();
+        lines.push(line(
+            ParagraphStyle::Body,
+            &[
+                ("Plain, ", plain),
+                ("named", style(named, 13.5, false)),
+                (" underlined", style(Font::MONOSPACE, 16.0, true)),
+                (&anchor, plain),
+            ],
+        ));
+        lines.push(Line::new());
+
+        let mut document = Document {
+            lines,
+            goal: Some(Goal {
+                target: 5000,
+                deadline: NaiveDate::from_ymd_opt(2026, 12, 31),
+                daily: Some(250),
+            }),
+            ..Document::default()
+        };
+        document.notes.insert(
+            0,
+            Note {
+                kind: NoteKind::Endnote,
+                body: String::from("The note."),
+            },
+        );
+        document.references.merge(vec![Reference {
+            key: String::from("smith2020"),
+            kind: String::from("book"),
+            authors: vec![Name {
+                family: String::from("Smith"),
+                given: String::from("Jane"),
+            }],
+            title: String::from("A Title"),
+            year: String::from("2020"),
+            ..Reference::default()
+        }]);
+
+        let decoded = decode(&encode(&document).unwrap()).unwrap();
+        assert_eq!(decoded.lines.len(), document.lines.len());
+        for (decoded, line) in decoded.lines.iter().zip(&document.lines) {
+            assert_eq!(decoded.content, line.content);
+            assert_eq!(decoded.spans(), line.spans());
+            assert_eq!(decoded.paragraph(), line.paragraph());
+        }
+        assert_eq!(decoded.notes, document.notes);
+        assert_eq!(decoded.references, document.references);
+        assert_eq!(decoded.goal, document.goal);
+    }
+
+    #[test]
+    fn older_documents_have_no_underlines() {
+        let bytes = stored(SERIF, "Old", r#"{"len":3,"font":0,"size":12.0}"#);
+        let document = decode(&bytes).unwrap();
+        let line = &document.lines[0];
+        assert_eq!(line.content, ['O', 'l', 'd']);
+        let serif = Font {
+            family: Family::Serif,
+            ..Font::DEFAULT
+        };
+        assert_eq!(line.style_at(0), Some(style(serif, 12.0, false)));
+        assert_eq!(line.paragraph(), ParagraphStyle::Body);
+        assert!(document.notes.iter().next().is_none());
+        assert!(document.references.is_empty());
+        assert_eq!(document.goal, None);
+    }
+
+    #[test]
+    fn malformed_documents_are_rejected() {
+        let cases = [
+            stored(SERIF, "Text", r#"{"len":4,"font":1,"size":12.0}"#),
+            stored(SERIF, "Text", r#"{"len":3,"font":0,"size":12.0}"#),
+            stored(SERIF, "Text", r#"{"len":5,"font":0,"size":12.0}"#),
+            stored(
+                SERIF,
+                "Text",
+                r#"{"len":4,"font":0,"size":12.0},{"len":1,"font":0,"size":12.0}"#,
+            ),
+        ];
+        for bytes in cases {
+            assert!(matches!(decode(&bytes), Err(Error::Malformed(_))));
+        }
+    }
+}
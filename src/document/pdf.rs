@@ -0,0 +1,567 @@
+// document/pdf.rs
+//
+// PDF export. Lines are laid out onto pages with the fonts and sizes of their
+// characters. Text is measured with the same font files that get embedded, so lines
+// wrap where the printed text really ends. Fonts are looked up among the installed
+// ones and nothing is fetched from anywhere. Notes follow the text, numbered like
+// their anchors, as they do in plain text.
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use iced::Font;
+use iced::font::{Family, Stretch, Style, Weight};
+use printpdf::{IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference, Point};
+
+use super::{Document, Error};
+use crate::widgets::notes::{self, NoteKind, Numbering};
+use crate::widgets::paragraph::ParagraphStyle;
+use crate::widgets::textarea::{Line, TextStyle};
+
+pub const EXTENSION: &str = "pdf";
+
+// Font sizes in the editor are in pixels at 96 per inch, PDF sizes are in points.
+const POINTS_PER_PIXEL: f32 = 0.75;
+const MM_PER_POINT: f32 = 25.4 / 72.0;
+// The height of a line relative to the largest font size on it, as in the editor.
+const LINE_HEIGHT: f32 = 1.2;
+// The size paragraph spacing and indentation are measured in: the editor's 16 pixels.
+const BASE_SIZE: f32 = 12.0;
+const PAGE_NUMBER_SIZE: f32 = 10.0;
+// Where underlines go below the baseline and how thick they are, relative to the size.
+const UNDERLINE_OFFSET: f32 = 0.12;
+const UNDERLINE_THICKNESS: f32 = 0.06;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PageSize {
+    #[default]
+    A4,
+    Letter,
+}
+
+impl PageSize {
+    pub const ALL: [PageSize; 2] = [PageSize::A4, PageSize::Letter];
+
+    // Width and height in millimetres.
+    fn dimensions(self) -> (f32, f32) {
+        match self {
+            PageSize::A4 => (210.0, 297.0),
+            PageSize::Letter => (215.9, 279.4),
+        }
+    }
+}
+
+impl std::fmt::Display for PageSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PageSize::A4 => write!(f, "A4"),
+            PageSize::Letter => write!(f, "Letter"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageSetup {
+    pub size: PageSize,
+    // On every side of the page, in millimetres.
+    pub margin: f32,
+    // A multiple of the normal line height.
+    pub line_spacing: f32,
+    pub page_numbers: bool,
+}
+
+impl Default for PageSetup {
+    fn default() -> Self {
+        Self {
+            size: PageSize::A4,
+            margin: 25.0,
+            line_spacing: 1.0,
+            page_numbers: true,
+        }
+    }
+}
+
+/// Ask where to save the PDF, then lay out the document and write it there.
+pub async fn export(document: Document, title: String, setup: PageSetup) -> Result<PathBuf, Error> {
+    let path = rfd::AsyncFileDialog::new()
+        .set_title("Export to PDF")
+        .set_file_name(format!("{}.{}", title, EXTENSION))
+        .add_filter("PDF document", &[EXTENSION])
+        .save_file()
+        .await
+        .map(|handle| handle.path().to_path_buf())
+        .ok_or(Error::DialogClosed)?;
+
+    let bytes = render(&document, &title, &setup)?;
+    std::fs::write(&path, bytes)?;
+    Ok(path)
+}
+
+fn export_error(error: impl std::fmt::Display) -> Error {
+    Error::Export(error.to_string())
+}
+
+// A font file embedded in the PDF, together with what it takes to measure text in it.
+struct Face {
+    reference: IndirectFontRef,
+    data: Vec<u8>,
+    // Relative to the font size, like the advances.
+    descent: f32,
+    // None for characters the font has no glyph for.
+    advances: HashMap<char, Option<f32>>,
+}
+
+impl Face {
+    fn embed(data: Vec<u8>, pdf: &PdfDocumentReference) -> Result<Self, Error> {
+        let face = ttf_parser::Face::parse(&data, 0).map_err(export_error)?;
+        let descent = -f32::from(face.descender()) / f32::from(face.units_per_em());
+        let reference = pdf
+            .add_external_font(data.as_slice())
+            .map_err(export_error)?;
+        Ok(Self {
+            reference,
+            data,
+            descent,
+            advances: HashMap::new(),
+        })
+    }
+
+    // How far the pen moves after a character at a size of 1, or None if the font has
+    // no glyph for it.
+    fn advance(&mut self, c: char) -> Option<f32> {
+        *self.advances.entry(c).or_insert_with(|| {
+            let face = ttf_parser::Face::parse(&self.data, 0).ok()?;
+            let glyph = face.glyph_index(c)?;
+            let advance = face.glyph_hor_advance(glyph).unwrap_or_default();
+            Some(f32::from(advance) / f32::from(face.units_per_em()))
+        })
+    }
+}
+
+// The installed fonts, and the faces embedded so far.
+struct Fonts {
+    database: fontdb::Database,
+    faces: Vec<Face>,
+    embedded: HashMap<fontdb::ID, usize>,
+    chosen: HashMap<Font, usize>,
+}
+
+impl Fonts {
+    fn new() -> Self {
+        let mut database = fontdb::Database::new();
+        database.load_system_fonts();
+        Self {
+            database,
+            faces: Vec::new(),
+            embedded: HashMap::new(),
+            chosen: HashMap::new(),
+        }
+    }
+
+    // The index of the face a font is drawn with, embedding it the first time.
+    fn face(&mut self, font: Font, pdf: &PdfDocumentReference) -> Result<usize, Error> {
+        if let Some(index) = self.chosen.get(&font) {
+            return Ok(*index);
+        }
+        let id = self
+            .resolve(font)
+            .ok_or_else(|| Error::Export(String::from("no TrueType font is installed")))?;
+        let index = match self.embedded.get(&id) {
+            Some(index) => *index,
+            None => {
+                let data = self
+                    .database
+                    .with_face_data(id, |data, _| data.to_vec())
+                    .ok_or_else(|| Error::Export(String::from("a font could not be read")))?;
+                self.faces.push(Face::embed(data, pdf)?);
+                self.embedded.insert(id, self.faces.len() - 1);
+                self.faces.len() - 1
+            }
+        };
+        self.chosen.insert(font, index);
+        Ok(index)
+    }
+
+    // The installed face closest to a font. Only plain TrueType files can be embedded,
+    // so collections and fonts with other outlines are passed over.
+    fn resolve(&self, font: Font) -> Option<fontdb::ID> {
+        let family = match font.family {
+            Family::Name(name) => fontdb::Family::Name(name),
+            Family::Serif => fontdb::Family::Serif,
+            Family::SansSerif => fontdb::Family::SansSerif,
+            Family::Cursive => fontdb::Family::Cursive,
+            Family::Fantasy => fontdb::Family::Fantasy,
+            Family::Monospace => fontdb::Family::Monospace,
+        };
+        let weight = fontdb::Weight(weight(font.weight));
+        let style = match font.style {
+            Style::Normal => fontdb::Style::Normal,
+            Style::Italic => fontdb::Style::Italic,
+            Style::Oblique => fontdb::Style::Oblique,
+        };
+        let query = fontdb::Query {
+            families: &[family, fontdb::Family::Serif, fontdb::Family::SansSerif],
+            weight,
+            stretch: stretch(font.stretch),
+            style,
+        };
+        if let Some(id) = self.database.query(&query)
+            && self.is_embeddable(id)
+        {
+            return Some(id);
+        }
+
+        // Any face will do rather than none, preferring the same family and look.
+        let name = match family {
+            fontdb::Family::Name(name) => Some(name),
+            _ => None,
+        };
+        self.database
+            .faces()
+            .filter(|face| self.is_embeddable(face.id))
+            .min_by_key(|face| {
+                let same_family = face
+                    .families
+                    .iter()
+                    .any(|(family, _)| Some(family.as_str()) == name);
+                (
+                    !same_family,
+                    face.style != style,
+                    face.weight.0.abs_diff(weight.0),
+                )
+            })
+            .map(|face| face.id)
+    }
+
+    fn is_embeddable(&self, id: fontdb::ID) -> bool {
+        self.database
+            .with_face_data(id, |data, index| {
+                index == 0 && (data.starts_with(&[0, 1, 0, 0]) || data.starts_with(b"true"))
+            })
+            .unwrap_or(false)
+    }
+}
+
+fn weight(weight: Weight) -> u16 {
+    match weight {
+        Weight::Thin => 100,
+        Weight::ExtraLight => 200,
+        Weight::Light => 300,
+        Weight::Normal => 400,
+        Weight::Medium => 500,
+        Weight::Semibold => 600,
+        Weight::Bold => 700,
+        Weight::ExtraBold => 800,
+        Weight::Black => 900,
+    }
+}
+
+fn stretch(stretch: Stretch) -> fontdb::Stretch {
+    match stretch {
+        Stretch::UltraCondensed => fontdb::Stretch::UltraCondensed,
+        Stretch::ExtraCondensed => fontdb::Stretch::ExtraCondensed,
+        Stretch::Condensed => fontdb::Stretch::Condensed,
+        Stretch::SemiCondensed => fontdb::Stretch::SemiCondensed,
+        Stretch::Normal => fontdb::Stretch::Normal,
+        Stretch::SemiExpanded => fontdb::Stretch::SemiExpanded,
+        Stretch::Expanded => fontdb::Stretch::Expanded,
+        Stretch::ExtraExpanded => fontdb::Stretch::ExtraExpanded,
+        Stretch::UltraExpanded => fontdb::Stretch::UltraExpanded,
+    }
+}
+
+// A character as it is printed. Sizes and distances are in points.
+struct Glyph {
+    c: char,
+    face: usize,
+    size: f32,
+    // The size of the text around a note number, which the line height follows.
+    line_size: f32,
+    rise: f32,
+    underline: bool,
+    advance: f32,
+}
+
+fn glyphs(
+    line: &Line,
+    numbering: &Numbering,
+    fonts: &mut Fonts,
+    pdf: &PdfDocumentReference,
+) -> Result<Vec<Glyph>, Error> {
+    let paragraph = line.paragraph();
+    let mut glyphs = Vec::with_capacity(line.content.len());
+    for (range, style) in line.runs(0..line.content.len()) {
+        let style = paragraph.apply(style);
+        let face = fonts.face(style.font, pdf)?;
+        let size = style.size * POINTS_PER_PIXEL;
+        for c in &line.content[range] {
+            let (printed, glyph_size, rise) = match notes::anchor_id(*c) {
+                Some(id) => (
+                    numbering.label(id).to_string(),
+                    size * notes::SUPERSCRIPT_SCALE,
+                    size * notes::SUPERSCRIPT_RISE,
+                ),
+                None => (c.to_string(), size, 0.0),
+            };
+            // Characters the font has no glyph for are left out, rather than drawn as
+            // a missing-glyph box over the next one.
+            for c in printed.chars() {
+                let Some(advance) = fonts.faces[face].advance(c) else {
+                    continue;
+                };
+                glyphs.push(Glyph {
+                    c,
+                    face,
+                    size: glyph_size,
+                    line_size: size,
+                    rise,
+                    underline: style.underline && rise == 0.0,
+                    advance: advance * glyph_size,
+                });
+            }
+        }
+    }
+    Ok(glyphs)
+}
+
+// Split a paragraph into the glyphs of each printed line, breaking after whitespace
+// where possible. An empty paragraph still takes one line.
+fn wrap(glyphs: &[Glyph], width: f32) -> Vec<Range<usize>> {
+    let mut rows = Vec::new();
+    let mut start = 0;
+    while start < glyphs.len() {
+        let mut end = start + 1;
+        let mut x = glyphs[start].advance;
+        while end < glyphs.len() && x + glyphs[end].advance <= width {
+            x += glyphs[end].advance;
+            end += 1;
+        }
+        if end < glyphs.len()
+            && let Some(space) = (start + 1..=end)
+                .rev()
+                .find(|i| glyphs[i - 1].c.is_whitespace())
+        {
+            end = space;
+        }
+        rows.push(start..end);
+        start = end;
+    }
+    if rows.is_empty() {
+        rows.push(0..0);
+    }
+    rows
+}
+
+// The notes after the text, like plain text has them: a heading for each kind of note,
+// then every note with its number, in the style the document starts with.
+fn note_lines(document: &Document, numbering: &Numbering) -> Vec<Line> {
+    let style = document
+        .lines
+        .iter()
+        .find_map(|line| line.spans().first())
+        .map(|span| span.style())
+        .unwrap_or(TextStyle {
+            font: Font::default(),
+            size: BASE_SIZE / POINTS_PER_PIXEL,
+            underline: false,
+        });
+
+    let mut lines = Vec::new();
+    for kind in [NoteKind::Footnote, NoteKind::Endnote] {
+        let ids: Vec<u32> = numbering
+            .order()
+            .iter()
+            .copied()
+            .filter(|id| document.notes.kind(*id) == kind)
+            .collect();
+        if ids.is_empty() {
+            continue;
+        }
+        let mut heading = Line::new();
+        heading.set_paragraph(ParagraphStyle::Heading2);
+        heading.push_styled(kind.name().chars(), style);
+        lines.push(heading);
+        for id in ids {
+            let body = document.notes.get(id).map_or("", |note| note.body.as_str());
+            let mut line = Line::new();
+            line.push_styled(
+                format!("{}. {}", numbering.label(id), body)
+                    .trim_end()
+                    .chars(),
+                style,
+            );
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+// A page being filled, with distances measured in points from its top-left corner.
+struct Page<'a> {
+    layer: PdfLayerReference,
+    width: f32,
+    height: f32,
+    fonts: &'a Fonts,
+}
+
+impl Page<'_> {
+    fn text(&self, text: &str, face: usize, size: f32, x: f32, baseline: f32) {
+        self.layer.use_text(
+            text,
+            size,
+            Mm(x * MM_PER_POINT),
+            Mm((self.height - baseline) * MM_PER_POINT),
+            &self.fonts.faces[face].reference,
+        );
+    }
+
+    // Centre the number of the page on a baseline.
+    fn number(&self, number: usize, face: usize, baseline: f32) {
+        let label = number.to_string();
+        let advances = &self.fonts.faces[face].advances;
+        let width: f32 = label
+            .chars()
+            .map(|c| advances.get(&c).copied().flatten().unwrap_or_default() * PAGE_NUMBER_SIZE)
+            .sum();
+        self.text(
+            &label,
+            face,
+            PAGE_NUMBER_SIZE,
+            (self.width - width) / 2.0,
+            baseline,
+        );
+    }
+
+    fn underline(&self, from: f32, to: f32, y: f32, size: f32) {
+        let y = Mm((self.height - y) * MM_PER_POINT);
+        self.layer.set_outline_thickness(size * UNDERLINE_THICKNESS);
+        self.layer.add_line(printpdf::Line {
+            points: vec![
+                (Point::new(Mm(from * MM_PER_POINT), y), false),
+                (Point::new(Mm(to * MM_PER_POINT), y), false),
+            ],
+            is_closed: false,
+        });
+    }
+
+    // Print a line of glyphs, one text object for every run of the same face and size.
+    fn row(&self, glyphs: &[Glyph], x: f32, baseline: f32) {
+        let mut start = 0;
+        let mut run_x = x;
+        while start < glyphs.len() {
+            let first = &glyphs[start];
+            let end = glyphs[start..]
+                .iter()
+                .position(|g| g.face != first.face || g.size != first.size || g.rise != first.rise)
+                .map_or(glyphs.len(), |len| start + len);
+            let text: String = glyphs[start..end].iter().map(|g| g.c).collect();
+            self.text(&text, first.face, first.size, run_x, baseline - first.rise);
+            run_x += glyphs[start..end].iter().map(|g| g.advance).sum::<f32>();
+            start = end;
+        }
+
+        // Underlines run under the spaces between underlined words, not past the end.
+        let mut pen = x;
+        let mut i = 0;
+        while i < glyphs.len() {
+            if !glyphs[i].underline {
+                pen += glyphs[i].advance;
+                i += 1;
+                continue;
+            }
+            let (from, size) = (pen, glyphs[i].size);
+            let mut to = pen;
+            while i < glyphs.len() && glyphs[i].underline {
+                pen += glyphs[i].advance;
+                if !glyphs[i].c.is_whitespace() {
+                    to = pen;
+                }
+                i += 1;
+            }
+            if to > from {
+                self.underline(from, to, baseline + UNDERLINE_OFFSET * size, size);
+            }
+        }
+    }
+}
+
+// Lay out the document onto pages and encode the PDF.
+pub fn render(document: &Document, title: &str, setup: &PageSetup) -> Result<Vec<u8>, Error> {
+    let (page_width, page_height) = setup.size.dimensions();
+    let (pdf, first_page, first_layer) =
+        PdfDocument::new(title, Mm(page_width), Mm(page_height), "Text");
+    let mut fonts = Fonts::new();
+
+    let numbering = Numbering::of(&document.lines, &document.notes);
+    let mut lines = document.lines.clone();
+    lines.extend(note_lines(document, &numbering));
+
+    // Lay out everything first, so that every face is embedded before pages are drawn.
+    let width = (page_width - 2.0 * setup.margin) / MM_PER_POINT;
+    let mut paragraphs = Vec::with_capacity(lines.len());
+    for line in &lines {
+        let format = line.paragraph().format();
+        let glyphs = glyphs(line, &numbering, &mut fonts, &pdf)?;
+        let indent = format.indent_left * BASE_SIZE;
+        let available = width - indent - format.indent_right * BASE_SIZE;
+        let rows = wrap(&glyphs, available);
+        paragraphs.push((format, glyphs, indent, rows));
+    }
+    let page_number_face = match lines.iter().find_map(|line| line.spans().first()) {
+        Some(span) => fonts.face(span.font, &pdf)?,
+        None => fonts.face(Font::default(), &pdf)?,
+    };
+
+    let height = page_height / MM_PER_POINT;
+    let margin = setup.margin / MM_PER_POINT;
+    // Digits are measured up front, since pages only borrow the fonts.
+    for digit in '0'..='9' {
+        fonts.faces[page_number_face].advance(digit);
+    }
+    let fonts = &fonts;
+    let start_page = |number: usize, layer: PdfLayerReference| {
+        let page = Page {
+            layer,
+            width: page_width / MM_PER_POINT,
+            height,
+            fonts,
+        };
+        if setup.page_numbers {
+            page.number(number, page_number_face, height - margin / 2.0);
+        }
+        page
+    };
+
+    let mut number = 1;
+    let mut page = start_page(number, pdf.get_page(first_page).get_layer(first_layer));
+    let mut y = margin;
+    for (format, glyphs, indent, rows) in &paragraphs {
+        // Space before a paragraph is dropped at the top of a page.
+        if y > margin {
+            y += format.space_before * BASE_SIZE;
+        }
+        for row in rows {
+            let glyphs = &glyphs[row.clone()];
+            let largest = glyphs
+                .iter()
+                .map(|g| g.line_size)
+                .fold(BASE_SIZE * format.scale, f32::max);
+            let line_height = largest * LINE_HEIGHT * setup.line_spacing;
+            if y + line_height > height - margin && y > margin {
+                number += 1;
+                let (index, layer) = pdf.add_page(Mm(page_width), Mm(page_height), "Text");
+                page = start_page(number, pdf.get_page(index).get_layer(layer));
+                y = margin;
+            }
+            let descent = glyphs
+                .iter()
+                .map(|g| fonts.faces[g.face].descent * g.line_size)
+                .fold(0.0, f32::max);
+            page.row(glyphs, margin + indent, y + line_height - descent);
+            y += line_height;
+        }
+        y += format.space_after * BASE_SIZE;
+    }
+
+    pdf.save_to_bytes().map_err(export_error)
+}
@@ -0,0 +1,54 @@
+// document/plain.rs
+use super::Error;
+use crate::widgets::notes::{self, NoteKind, Notes, Numbering};
+use crate::widgets::textarea::Line;
+
+const UTF8_BOM: &str = "\u{feff}";
+
+// Decode a UTF-8 text file, normalizing line endings to '\n'.
+pub fn decode(bytes: &[u8]) -> Result<String, Error> {
+    let text = std::str::from_utf8(bytes).map_err(|_| Error::InvalidEncoding)?;
+    let text = text.strip_prefix(UTF8_BOM).unwrap_or(text);
+    Ok(text.replace("\r\n", "\n").replace('\r', "\n"))
+}
+
+pub fn encode(text: &str) -> Vec<u8> {
+    text.as_bytes().to_vec()
+}
+
+// Join lines into a single string, dropping all styling. Note anchors become their
+// numbers in brackets, and the notes follow the text.
+pub fn join(lines: &[Line], notes: &Notes) -> String {
+    let numbering = Numbering::of(lines, notes);
+    let mut text = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            text.push('\n');
+        }
+        for c in &line.content {
+            match notes::anchor_id(*c) {
+                Some(id) => text.push_str(&format!("[{}]", numbering.label(id))),
+                None => text.push(*c),
+            }
+        }
+    }
+
+    for kind in [NoteKind::Footnote, NoteKind::Endnote] {
+        let mut ids = numbering
+            .order()
+            .iter()
+            .filter(|id| notes.kind(**id) == kind)
+            .peekable();
+        if ids.peek().is_none() {
+            continue;
+        }
+        text.push_str(&format!("\n\n{}\n", kind.name()));
+        for id in ids {
+            let body = notes.get(*id).map_or("", |note| note.body.as_str());
+            let entry = format!("[{}] {}", numbering.label(*id), body);
+            text.push('\n');
+            text.push_str(entry.trim_end());
+        }
+    }
+    text
+}
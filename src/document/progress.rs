@@ -0,0 +1,106 @@
+// document/progress.rs
+//
+// Word-count goals and the writing history. A goal belongs to a document and is saved
+// with it. The history is kept locally, as a JSON object mapping every day on which
+// something was written to the net number of words written that day.
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use chrono::{Days, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use super::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Goal {
+    pub target: usize,
+    pub deadline: Option<NaiveDate>,
+    // The words to write every day.
+    pub daily: Option<usize>,
+}
+
+impl Goal {
+    // How far along the document is, from 0 to 1.
+    pub fn progress(&self, words: usize) -> f32 {
+        (words as f32 / self.target as f32).min(1.0)
+    }
+
+    // The days left until the deadline, counting today. None once it has passed.
+    pub fn days_left(&self, today: NaiveDate) -> Option<usize> {
+        let days = (self.deadline? - today).num_days() + 1;
+        (days > 0).then_some(days as usize)
+    }
+
+    // The words to write today. Without a daily quota, it is what the deadline takes
+    // spread evenly over the days left, from the count the day started with.
+    pub fn quota(&self, words_at_start: usize, today: NaiveDate) -> Option<usize> {
+        self.daily.or_else(|| {
+            let remaining = self.target.saturating_sub(words_at_start);
+            Some(remaining.div_ceil(self.days_left(today)?))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct History {
+    days: BTreeMap<NaiveDate, i64>,
+}
+
+impl History {
+    // Count words as written on a day; deleting words makes the count negative.
+    pub fn add(&mut self, day: NaiveDate, words: i64) {
+        *self.days.entry(day).or_default() += words;
+    }
+
+    pub fn on(&self, day: NaiveDate) -> i64 {
+        self.days.get(&day).copied().unwrap_or_default()
+    }
+
+    // The given number of days up to and including the last one, oldest first.
+    pub fn recent(&self, last: NaiveDate, count: u64) -> Vec<(NaiveDate, i64)> {
+        (0..count)
+            .rev()
+            .filter_map(|back| last.checked_sub_days(Days::new(back)))
+            .map(|day| (day, self.on(day)))
+            .collect()
+    }
+}
+
+pub fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+// The history file, e.g. ~/.local/share/blackscript/history.json on Linux.
+fn history_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("blackscript").join("history.json"))
+}
+
+/// Read the writing history. Without a history file, nothing was written yet.
+pub async fn load_history() -> Result<History, Error> {
+    let Some(path) = history_path() else {
+        return Ok(History::default());
+    };
+    match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| Error::Malformed(e.to_string())),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(History::default()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Replace the history file with the given history.
+pub async fn save_history(history: History) -> Result<(), Error> {
+    let Some(path) = history_path() else {
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let bytes = serde_json::to_vec_pretty(&history).map_err(|e| Error::Malformed(e.to_string()))?;
+
+    // Like the recovery journal, written to a temporary file first.
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, bytes)?;
+    std::fs::rename(&temporary, &path)?;
+    Ok(())
+}
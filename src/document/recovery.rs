@@ -0,0 +1,166 @@
+// document/recovery.rs
+//
+// Crash-recovery journal. While a document has unsaved changes, a copy of it is
+// periodically written to the local state directory. Each journal starts with a
+// single JSON header line followed by the document in the native format. Every run
+// of the editor writes its own journals, so one left behind by an earlier run stays
+// intact until the user decides on it.
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Document, Error, native};
+
+const JOURNAL_EXTENSION: &str = "journal";
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub path: Option<PathBuf>,
+    pub cursor: (usize, usize),
+    pub document: Document,
+}
+
+// A journal left behind by an earlier run, with the file it was read from.
+#[derive(Debug, Clone)]
+pub struct Found {
+    pub snapshot: Snapshot,
+    pub journal: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    path: Option<PathBuf>,
+    cursor: (usize, usize),
+}
+
+// Where journals are kept, e.g. ~/.local/state/blackscript/recovery on Linux.
+fn directory() -> Option<PathBuf> {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join("blackscript").join("recovery"))
+}
+
+// Every document gets its own journal in every run, keyed by a stable hash of its
+// path and the id of the run.
+fn journal_path(document: Option<&Path>) -> Option<PathBuf> {
+    let name = match document {
+        Some(path) => format!("{:016x}", fnv1a(path.to_string_lossy().as_bytes())),
+        None => String::from("untitled"),
+    };
+    let name = format!("{}-{}", name, session());
+    directory().map(|dir| dir.join(name).with_extension(JOURNAL_EXTENSION))
+}
+
+// The id of this run: the process id and the time it first wrote a journal.
+fn session() -> &'static str {
+    static SESSION: OnceLock<String> = OnceLock::new();
+    SESSION.get_or_init(|| {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos());
+        format!("{:x}-{:x}", std::process::id(), started)
+    })
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Write a recovery copy of the document.
+pub async fn write(snapshot: Snapshot) -> Result<(), Error> {
+    let Some(path) = journal_path(snapshot.path.as_deref()) else {
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let header = Header {
+        path: snapshot.path,
+        cursor: snapshot.cursor,
+    };
+    let mut bytes = serde_json::to_vec(&header).map_err(|e| Error::Malformed(e.to_string()))?;
+    bytes.push(b'\n');
+    bytes.extend(native::encode(&snapshot.document)?);
+
+    // Write to a temporary file first so a crash mid-write never leaves a torn journal.
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, bytes)?;
+    std::fs::rename(&temporary, &path)?;
+    Ok(())
+}
+
+/// Remove this run's journal of a document, typically after it has been saved.
+pub async fn discard(document: Option<PathBuf>) -> Result<(), Error> {
+    let Some(path) = journal_path(document.as_deref()) else {
+        return Ok(());
+    };
+    remove(path).await
+}
+
+/// Remove a journal file, such as one found from an earlier run.
+pub async fn remove(journal: PathBuf) -> Result<(), Error> {
+    match std::fs::remove_file(journal) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Find the most recent journal that is newer than the file it belongs to.
+/// Journals that are older than their saved file are stale and get removed.
+pub async fn find() -> Option<Found> {
+    let entries = std::fs::read_dir(directory()?).ok()?;
+    let mut newest: Option<(SystemTime, Found)> = None;
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some(JOURNAL_EXTENSION) {
+            continue;
+        }
+        let Ok(modified) = entry.metadata().and_then(|meta| meta.modified()) else {
+            continue;
+        };
+        let Some(snapshot) = read(&path) else {
+            continue;
+        };
+
+        let saved = snapshot
+            .path
+            .as_ref()
+            .and_then(|document| std::fs::metadata(document).ok())
+            .and_then(|meta| meta.modified().ok());
+        if saved.is_some_and(|saved| saved >= modified) {
+            let _ = std::fs::remove_file(&path);
+            continue;
+        }
+
+        if newest.as_ref().is_none_or(|(time, _)| modified > *time) {
+            newest = Some((
+                modified,
+                Found {
+                    snapshot,
+                    journal: path,
+                },
+            ));
+        }
+    }
+
+    newest.map(|(_, found)| found)
+}
+
+fn read(path: &Path) -> Option<Snapshot> {
+    let bytes = std::fs::read(path).ok()?;
+    let split = bytes.iter().position(|byte| *byte == b'\n')?;
+    let header: Header = serde_json::from_slice(&bytes[..split]).ok()?;
+    let document = native::decode(&bytes[split + 1..]).ok()?;
+
+    Some(Snapshot {
+        path: header.path,
+        cursor: header.cursor,
+        document,
+    })
+}
@@ -0,0 +1,47 @@
+// document/tex.rs
+//
+// Text as TeX reads it, shared by the LaTeX source and the BibTeX entries written
+// next to it.
+
+// Text as LaTeX typesets it literally.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str(r"\textbackslash{}"),
+            '{' => escaped.push_str(r"\textbraceleft{}"),
+            '}' => escaped.push_str(r"\textbraceright{}"),
+            '~' => escaped.push_str(r"\textasciitilde{}"),
+            '^' => escaped.push_str(r"\textasciicircum{}"),
+            '&' | '%' | '$' | '#' | '_' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\u{a0}' => escaped.push('~'),
+            '\t' => escaped.push(' '),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_is_escaped() {
+        let cases = [
+            ("a & b", r"a \& b"),
+            ("{x}", r"\textbraceleft{}x\textbraceright{}"),
+            (r"C:\dir", r"C:\textbackslash{}dir"),
+            ("~^", r"\textasciitilde{}\textasciicircum{}"),
+            ("50% of $5 #1 a_b", r"50\% of \$5 \#1 a\_b"),
+            ("no\u{a0}break\tand\u{7}bell", "no~break andbell"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(escape(text), expected);
+        }
+    }
+}
@@ -1,17 +1,281 @@
 // main.rs
+use std::path::PathBuf;
 use std::time::Duration;
 
 use iced::{
     Element, Event, Font, Length, Subscription, Task,
-    widget::{Canvas, row, text},
+    keyboard::{Event as KeyEvent, Key, Modifiers, key::Named},
+    widget::{
+        Canvas, button, checkbox, column, container, pick_list, progress_bar, row, scrollable,
+        text, text_input,
+    },
 };
 
-use widgets::textarea::{TextEditorMessage, TextEditorWidget};
+use bibliography::{CitationStyle, Reference, References};
+use document::pdf::{PageSetup, PageSize};
+use document::progress::{self, Goal, History};
+use document::{dictionary, markdown, recovery};
 
+use widgets::lint::{self, Lint, LintOptions, Rule};
+use widgets::notes::NoteKind;
+use widgets::outline;
+use widgets::readability::Statistics;
+use widgets::search::{Search, SearchOptions};
+use widgets::spelling::{Misspelling, Speller};
+use widgets::textarea::{TextEditorMessage, TextEditorWidget, TextPosition};
+
+mod bibliography;
+mod document;
 mod widgets;
 
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+const OUTLINE_WIDTH: f32 = 220.0;
+const NOTES_HEIGHT: f32 = 160.0;
+const STYLE_WIDTH: f32 = 260.0;
+// How often the style checker looks for changes to check.
+const LINT_INTERVAL: Duration = Duration::from_millis(500);
+const STATISTICS_WIDTH: f32 = 300.0;
+const GOALS_WIDTH: f32 = 280.0;
+// How many days of writing history the goals panel shows.
+const HISTORY_DAYS: u64 = 14;
+// How often the statistics panel looks for edits and selection changes.
+const STATISTICS_INTERVAL: Duration = Duration::from_millis(500);
+// The style panel lists at most this many findings.
+const LINT_LIST_LIMIT: usize = 200;
+// How far each outline level is indented.
+const OUTLINE_INDENT: f32 = 12.0;
+
 struct Blackscript {
     text_editor: TextEditorWidget,
+    file: Option<PathBuf>,
+    modified: bool,
+    notice: Option<String>,
+    // Changes that have not yet been written to the recovery journal.
+    journal_pending: bool,
+    recovery: Option<recovery::Found>,
+    find: Option<FindBar>,
+    outline_open: bool,
+    references: References,
+    markdown: markdown::Source,
+    cite: Option<CitePicker>,
+    citation_style: CitationStyle,
+    spelling: Option<SpellingBar>,
+    lint_options: LintOptions,
+    // What was typed as the longest sentence length, which may not be a number yet.
+    lint_length: String,
+    style_check: Option<StyleCheck>,
+    statistics: Option<StatisticsPanel>,
+    goal: Option<Goal>,
+    // Loaded in the background at startup; words are only tallied once it is there.
+    history: Option<History>,
+    // Words were tallied that are not yet in the history file.
+    history_pending: bool,
+    // The word count when the document was opened, and when words were last tallied.
+    session_start: usize,
+    tallied_words: usize,
+    goals: Option<GoalsPanel>,
+    page_setup: PageSetup,
+    pdf_export: Option<PdfExportBar>,
+}
+
+// The find bar, optionally with the replace controls below it.
+#[derive(Debug, Default)]
+struct FindBar {
+    query: String,
+    replacement: String,
+    options: SearchOptions,
+    replacing: bool,
+    invalid: bool,
+}
+
+fn find_input_id() -> text_input::Id {
+    text_input::Id::new("find-query")
+}
+
+// The citation picker. The selection is an index into the matches of the query.
+#[derive(Debug, Default)]
+struct CitePicker {
+    query: String,
+    selected: usize,
+}
+
+// How many matches the citation picker shows.
+const CITE_RESULTS: usize = 8;
+
+fn cite_input_id() -> text_input::Id {
+    text_input::Id::new("cite-query")
+}
+
+// The spelling bar. While proofreading, it moves on to the next misspelled word after
+// each one is dealt with.
+#[derive(Debug)]
+struct SpellingBar {
+    misspelling: Option<Misspelling>,
+    proofreading: bool,
+}
+
+// The style checker, while its panel is open. Checks run in the background one at a
+// time, and the document is stale when it changed after the last check started.
+#[derive(Debug, Default)]
+struct StyleCheck {
+    lint: Option<Lint>,
+    running: bool,
+    stale: bool,
+}
+
+// The statistics panel, while it is open. Like the style checker, counting runs in the
+// background; it starts again when the document was edited or the selection moved.
+#[derive(Debug, Default)]
+struct StatisticsPanel {
+    document: Option<Statistics>,
+    selection: Option<Statistics>,
+    // The selection that was counted.
+    bounds: Option<(TextPosition, TextPosition)>,
+    running: bool,
+    stale: bool,
+}
+
+// The page setup for exporting to PDF, with the numbers as typed.
+#[derive(Debug)]
+struct PdfExportBar {
+    margin: String,
+    line_spacing: String,
+}
+
+// The margins and line spacing the page setup accepts.
+const MARGINS: std::ops::RangeInclusive<f32> = 0.0..=80.0;
+const LINE_SPACINGS: std::ops::RangeInclusive<f32> = 0.5..=4.0;
+
+impl PdfExportBar {
+    // What is wrong with the numbers as typed, if anything. The page setup keeps the
+    // last valid ones meanwhile, so exporting waits until they are fixed.
+    fn problem(&self) -> Option<String> {
+        let within = |input: &str, range: &std::ops::RangeInclusive<f32>| {
+            input
+                .trim()
+                .parse::<f32>()
+                .is_ok_and(|value| range.contains(&value))
+        };
+        if !within(&self.margin, &MARGINS) {
+            return Some(format!(
+                "Margins must be {} to {} mm",
+                MARGINS.start(),
+                MARGINS.end()
+            ));
+        }
+        if !within(&self.line_spacing, &LINE_SPACINGS) {
+            return Some(format!(
+                "Line spacing must be {} to {}",
+                LINE_SPACINGS.start(),
+                LINE_SPACINGS.end()
+            ));
+        }
+        None
+    }
+}
+
+// The goals panel, with the goal's fields as typed.
+#[derive(Debug, Default)]
+struct GoalsPanel {
+    target: String,
+    deadline: String,
+    daily: String,
+}
+
+impl GoalsPanel {
+    fn new(goal: Option<Goal>) -> Self {
+        let Some(goal) = goal else {
+            return Self::default();
+        };
+        Self {
+            target: goal.target.to_string(),
+            deadline: goal
+                .deadline
+                .map(|date| date.format(DATE_FORMAT).to_string())
+                .unwrap_or_default(),
+            daily: goal
+                .daily
+                .map(|daily| daily.to_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    // The goal the fields describe: None without a target, or Err while a field does
+    // not hold a valid number or date.
+    fn goal(&self) -> Result<Option<Goal>, ()> {
+        let target = self.target.trim();
+        if target.is_empty() {
+            return Ok(None);
+        }
+        let target = target.parse::<usize>().map_err(|_| ())?;
+        let deadline = match self.deadline.trim() {
+            "" => None,
+            date => Some(chrono::NaiveDate::parse_from_str(date, DATE_FORMAT).map_err(|_| ())?),
+        };
+        let daily = match self.daily.trim() {
+            "" => None,
+            daily => Some(daily.parse::<usize>().map_err(|_| ())?),
+        };
+        if target == 0 || daily == Some(0) {
+            return Err(());
+        }
+        Ok(Some(Goal {
+            target,
+            deadline,
+            daily,
+        }))
+    }
+}
+
+// How deadlines are typed and shown.
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Debug, Clone, Copy)]
+pub enum GoalField {
+    Target,
+    Deadline,
+    Daily,
+}
+
+fn note_input_id(id: u32) -> text_input::Id {
+    text_input::Id::new(format!("note-{}", id))
+}
+
+// Focusing an id that no widget has takes the focus away from every text input.
+fn unfocus_inputs<T>() -> Task<T> {
+    text_input::focus(text_input::Id::unique())
+}
+
+// The metrics shown in the statistics panel, by name.
+fn statistics_rows(statistics: &Statistics) -> Vec<(&'static str, String)> {
+    let score = |value: Option<f32>| value.map_or(String::from("—"), |v| format!("{:.1}", v));
+    let minutes = |value: f32| match value {
+        0.0 => String::from("—"),
+        v if v < 1.0 => String::from("< 1 min"),
+        v => format!("{} min", v.round()),
+    };
+    vec![
+        ("Words", statistics.words.to_string()),
+        ("Sentences", statistics.sentences.to_string()),
+        ("Paragraphs", statistics.paragraphs.to_string()),
+        (
+            "Words per sentence",
+            score(statistics.average_sentence_length()),
+        ),
+        ("Letters per word", score(statistics.average_word_length())),
+        ("Reading time", minutes(statistics.reading_time())),
+        ("Speaking time", minutes(statistics.speaking_time())),
+        (
+            "Flesch Reading Ease",
+            score(statistics.flesch_reading_ease()),
+        ),
+        (
+            "Flesch–Kincaid Grade",
+            score(statistics.flesch_kincaid_grade()),
+        ),
+        ("Gunning Fog", score(statistics.gunning_fog())),
+        ("SMOG", score(statistics.smog())),
+    ]
 }
 
 impl Default for Blackscript {
@@ -25,6 +289,79 @@ pub enum Message {
     EventOccurred(Event),
     EditorEvent(TextEditorMessage),
     CursorBlink,
+    Open,
+    FileOpened(Result<(PathBuf, document::Contents), document::Error>),
+    Save,
+    SaveAs,
+    FileSaved(Result<PathBuf, document::Error>),
+    Autosave,
+    JournalUpdated(Result<(), document::Error>),
+    RecoveryFound(Option<recovery::Found>),
+    RestoreRecovery,
+    DiscardRecovery,
+    Copy,
+    Cut,
+    Paste,
+    Pasted(Option<String>),
+    Find,
+    Replace,
+    CloseFind,
+    FindQueryChanged(String),
+    ReplacementChanged(String),
+    SearchOptionsChanged(SearchOptions),
+    FindNext,
+    FindPrevious,
+    ReplaceOne,
+    ReplaceAll,
+    ToggleOutline,
+    JumpToHeading(usize),
+    InsertNote(NoteKind),
+    NoteChanged(u32, String),
+    FinishNote,
+    ConvertToEndnotes,
+    Cite,
+    CloseCite,
+    CiteQueryChanged(String),
+    CiteSelectionMoved(bool),
+    SubmitCitation,
+    InsertCitation(String),
+    ImportBibliography,
+    BibliographyImported(Result<Vec<Reference>, document::Error>),
+    CitationStyleSelected(CitationStyle),
+    InsertBibliography,
+    CheckSpelling,
+    CloseSpelling,
+    CorrectSpelling(String),
+    AddToDictionary,
+    IgnoreWord,
+    LoadDictionary,
+    DictionaryLoaded(Result<Speller, document::Error>),
+    DictionaryUpdated(Result<(), document::Error>),
+    ToggleStyleCheck,
+    LintTick,
+    LintFinished(Lint),
+    LintRuleToggled(Rule, bool),
+    LintLengthChanged(String),
+    JumpToFinding(usize, std::ops::Range<usize>),
+    ToggleStatistics,
+    StatisticsTick,
+    StatisticsFinished(Statistics, Option<Statistics>),
+    ToggleGoals,
+    GoalChanged(GoalField, String),
+    HistoryLoaded(Result<History, document::Error>),
+    HistorySaved(Result<(), document::Error>),
+    ExportPdf,
+    ClosePdfExport,
+    PageSizeSelected(PageSize),
+    MarginChanged(String),
+    LineSpacingChanged(String),
+    PageNumbersToggled(bool),
+    SubmitPdfExport,
+    PdfExported(Result<PathBuf, document::Error>),
+    ExportLatex,
+    LatexExported(Result<PathBuf, document::Error>),
+    // Close whatever bar is open, starting with the citation picker.
+    Dismiss,
 }
 
 impl Blackscript {
@@ -33,9 +370,50 @@ impl Blackscript {
             text_editor: TextEditorWidget::new()
                 .with_font(Font::with_name("Courier New"))
                 .with_font_size(16.0),
+            file: None,
+            modified: false,
+            notice: None,
+            journal_pending: false,
+            recovery: None,
+            find: None,
+            outline_open: true,
+            references: References::default(),
+            markdown: markdown::Source::default(),
+            cite: None,
+            citation_style: CitationStyle::default(),
+            spelling: None,
+            lint_options: LintOptions::default(),
+            lint_length: LintOptions::default().max_sentence_words.to_string(),
+            style_check: None,
+            statistics: None,
+            goal: None,
+            history: None,
+            history_pending: false,
+            session_start: 0,
+            tallied_words: 0,
+            goals: None,
+            page_setup: PageSetup::default(),
+            pdf_export: None,
         }
     }
 
+    pub fn boot() -> (Self, Task<Message>) {
+        (
+            Self::new(),
+            Task::batch([
+                Task::perform(recovery::find(), Message::RecoveryFound),
+                Task::perform(dictionary::load_default(), Message::DictionaryLoaded),
+                Task::perform(progress::load_history(), Message::HistoryLoaded),
+            ]),
+        )
+    }
+
+    pub fn title(&self) -> String {
+        let name = document::display_name(self.file.as_deref());
+        let marker = if self.modified { "*" } else { "" };
+        format!("{}{} - Blackscript", marker, name)
+    }
+
     pub fn view(&self) -> Element<'_, Message> {
         let editor_view = Canvas::new(self.text_editor.state())
             .width(Length::Fill)
@@ -44,28 +422,669 @@ impl Blackscript {
         let editor_element: Element<'_, Message> =
             Element::from(editor_view).map(Message::EditorEvent);
 
-        let content =
-            iced::widget::Column::with_children(vec![editor_element, self.render_status_bar()])
-                .spacing(10);
+        let mut children = Vec::new();
+        if let Some(banner) = self.render_recovery_banner() {
+            children.push(banner);
+        }
+        if let Some(find_bar) = self.render_find_bar() {
+            children.push(find_bar);
+        }
+        if let Some(picker) = self.render_cite_picker() {
+            children.push(picker);
+        }
+        if let Some(spelling) = self.render_spelling_bar() {
+            children.push(spelling);
+        }
+        if let Some(export) = self.render_pdf_export_bar() {
+            children.push(export);
+        }
+        children.push(
+            row![self.render_outline(), editor_element]
+                .push_maybe(self.render_statistics_panel())
+                .push_maybe(self.render_goals_panel())
+                .push(self.render_style_panel())
+                .spacing(10)
+                .height(Length::Fill)
+                .into(),
+        );
+        if let Some(notes) = self.render_notes_pane() {
+            children.push(notes);
+        }
+        children.push(self.render_status_bar());
+
+        let content = iced::widget::Column::with_children(children).spacing(10);
 
         content.into()
     }
 
+    fn render_recovery_banner(&self) -> Option<Element<'_, Message>> {
+        let found = self.recovery.as_ref()?;
+        let name = document::display_name(found.snapshot.path.as_deref());
+        let banner = row![
+            text(format!("Unsaved changes to {} were recovered.", name)),
+            iced::widget::horizontal_space(),
+            button("Restore").on_press(Message::RestoreRecovery),
+            button("Discard").on_press(Message::DiscardRecovery),
+        ]
+        .spacing(10);
+        Some(banner.into())
+    }
+
+    fn render_find_bar(&self) -> Option<Element<'_, Message>> {
+        let bar = self.find.as_ref()?;
+        let options = bar.options;
+        let status = if bar.invalid {
+            String::from("Invalid pattern")
+        } else if bar.query.is_empty() {
+            String::new()
+        } else {
+            match self.text_editor.match_count() {
+                1 => String::from("1 match"),
+                count => format!("{} matches", count),
+            }
+        };
+
+        let find_row = row![
+            text_input("Find", &bar.query)
+                .id(find_input_id())
+                .on_input(Message::FindQueryChanged)
+                .on_submit(Message::FindNext)
+                .width(Length::Fixed(240.0)),
+            checkbox("Match case", options.case_sensitive).on_toggle(move |on| {
+                Message::SearchOptionsChanged(SearchOptions {
+                    case_sensitive: on,
+                    ..options
+                })
+            }),
+            checkbox("Whole word", options.whole_word).on_toggle(move |on| {
+                Message::SearchOptionsChanged(SearchOptions {
+                    whole_word: on,
+                    ..options
+                })
+            }),
+            checkbox("Regex", options.regex).on_toggle(move |on| {
+                Message::SearchOptionsChanged(SearchOptions {
+                    regex: on,
+                    ..options
+                })
+            }),
+            button("Previous").on_press(Message::FindPrevious),
+            button("Next").on_press(Message::FindNext),
+            text(status),
+            iced::widget::horizontal_space(),
+            button("Close").on_press(Message::CloseFind),
+        ]
+        .spacing(10)
+        .align_y(iced::Alignment::Center);
+
+        if !bar.replacing {
+            return Some(find_row.into());
+        }
+
+        let replace_row = row![
+            text_input("Replace with", &bar.replacement)
+                .on_input(Message::ReplacementChanged)
+                .on_submit(Message::ReplaceOne)
+                .width(Length::Fixed(240.0)),
+            button("Replace").on_press(Message::ReplaceOne),
+            button("Replace All").on_press(Message::ReplaceAll),
+        ]
+        .spacing(10)
+        .align_y(iced::Alignment::Center);
+
+        Some(column![find_row, replace_row].spacing(5).into())
+    }
+
+    fn render_cite_picker(&self) -> Option<Element<'_, Message>> {
+        let picker = self.cite.as_ref()?;
+
+        let controls = row![
+            text_input("Search references", &picker.query)
+                .id(cite_input_id())
+                .on_input(Message::CiteQueryChanged)
+                .on_submit(Message::SubmitCitation)
+                .width(Length::Fixed(240.0)),
+            button("Import BibTeX…").on_press(Message::ImportBibliography),
+            pick_list(
+                CitationStyle::ALL,
+                Some(self.citation_style),
+                Message::CitationStyleSelected
+            ),
+            button("Insert Bibliography").on_press(Message::InsertBibliography),
+            iced::widget::horizontal_space(),
+            button("Close").on_press(Message::CloseCite),
+        ]
+        .spacing(10)
+        .align_y(iced::Alignment::Center);
+
+        let matches = self.references.search(&picker.query);
+        let results: Vec<Element<'_, Message>> = if self.references.is_empty() {
+            vec![text("No references yet. Import a BibTeX file to cite from it.").into()]
+        } else if matches.is_empty() {
+            vec![text("No matching references").into()]
+        } else {
+            matches
+                .into_iter()
+                .take(CITE_RESULTS)
+                .enumerate()
+                .map(|(index, reference)| {
+                    let style: fn(&iced::Theme, button::Status) -> button::Style =
+                        if index == picker.selected {
+                            button::primary
+                        } else {
+                            button::text
+                        };
+                    button(text(reference.summary()))
+                        .style(style)
+                        .width(Length::Fill)
+                        .on_press(Message::InsertCitation(reference.key.clone()))
+                        .into()
+                })
+                .collect()
+        };
+
+        Some(
+            column![controls, iced::widget::Column::with_children(results)]
+                .spacing(5)
+                .into(),
+        )
+    }
+
+    fn render_spelling_bar(&self) -> Option<Element<'_, Message>> {
+        let bar = self.spelling.as_ref()?;
+        let loaded = self.text_editor.dictionary_name().is_some();
+
+        let mut controls: Vec<Element<'_, Message>> = Vec::new();
+        match &bar.misspelling {
+            _ if !loaded => controls.push(text("No spelling dictionary is loaded").into()),
+            None => controls.push(text("No misspelled word here").into()),
+            Some(misspelling) => {
+                controls.push(text(format!("“{}”", misspelling.word)).into());
+                if misspelling.suggestions.is_empty() {
+                    controls.push(text("No suggestions").into());
+                }
+                for suggestion in &misspelling.suggestions {
+                    controls.push(
+                        button(text(suggestion.clone()))
+                            .on_press(Message::CorrectSpelling(suggestion.clone()))
+                            .into(),
+                    );
+                }
+                controls.push(
+                    button("Add to Dictionary")
+                        .on_press(Message::AddToDictionary)
+                        .into(),
+                );
+                controls.push(button("Ignore").on_press(Message::IgnoreWord).into());
+            }
+        }
+        controls.push(iced::widget::horizontal_space().into());
+        if loaded {
+            controls.push(button("Next").on_press(Message::CheckSpelling).into());
+        }
+        controls.push(
+            button("Load Dictionary…")
+                .on_press(Message::LoadDictionary)
+                .into(),
+        );
+        controls.push(button("Close").on_press(Message::CloseSpelling).into());
+
+        Some(
+            iced::widget::Row::with_children(controls)
+                .spacing(10)
+                .align_y(iced::Alignment::Center)
+                .into(),
+        )
+    }
+
+    fn render_pdf_export_bar(&self) -> Option<Element<'_, Message>> {
+        let bar = self.pdf_export.as_ref()?;
+        let setup = self.page_setup;
+        let problem = bar.problem();
+        let submit = problem.is_none().then_some(Message::SubmitPdfExport);
+
+        let controls = row![
+            text("Page size"),
+            pick_list(PageSize::ALL, Some(setup.size), Message::PageSizeSelected),
+            text("Margins (mm)"),
+            text_input("25", &bar.margin)
+                .on_input(Message::MarginChanged)
+                .on_submit_maybe(submit.clone())
+                .width(Length::Fixed(60.0)),
+            text("Line spacing"),
+            text_input("1.0", &bar.line_spacing)
+                .on_input(Message::LineSpacingChanged)
+                .on_submit_maybe(submit.clone())
+                .width(Length::Fixed(60.0)),
+            checkbox("Page numbers", setup.page_numbers).on_toggle(Message::PageNumbersToggled),
+            text(problem.unwrap_or_default()),
+            iced::widget::horizontal_space(),
+            button("Export…").on_press_maybe(submit),
+            button("Close").on_press(Message::ClosePdfExport),
+        ]
+        .spacing(10)
+        .align_y(iced::Alignment::Center);
+        Some(controls.into())
+    }
+
+    // The headings of the document, indented by level, with the section around the
+    // cursor highlighted.
+    fn render_outline(&self) -> Element<'_, Message> {
+        if !self.outline_open {
+            return button("›").on_press(Message::ToggleOutline).into();
+        }
+
+        let headings = self.text_editor.outline();
+        let (_, vpos) = self.text_editor.cursor_position();
+        let current = outline::section_at(&headings, vpos);
+
+        let entries: Vec<Element<'_, Message>> = headings
+            .into_iter()
+            .enumerate()
+            .map(|(index, heading)| {
+                let title = if heading.title.is_empty() {
+                    String::from("(untitled)")
+                } else {
+                    heading.title
+                };
+                let style: fn(&iced::Theme, button::Status) -> button::Style =
+                    if current == Some(index) {
+                        button::primary
+                    } else {
+                        button::text
+                    };
+                button(text(title))
+                    .style(style)
+                    .width(Length::Fill)
+                    .padding(
+                        iced::Padding::from([4, 8])
+                            .left(8.0 + OUTLINE_INDENT * heading.level as f32),
+                    )
+                    .on_press(Message::JumpToHeading(heading.vpos))
+                    .into()
+            })
+            .collect();
+
+        let body: Element<'_, Message> = if entries.is_empty() {
+            text("No headings").into()
+        } else {
+            scrollable(iced::widget::Column::with_children(entries))
+                .height(Length::Fill)
+                .into()
+        };
+
+        let header = row![
+            text("Outline"),
+            iced::widget::horizontal_space(),
+            button("‹").on_press(Message::ToggleOutline),
+        ]
+        .align_y(iced::Alignment::Center);
+
+        container(column![header, body].spacing(5))
+            .width(Length::Fixed(OUTLINE_WIDTH))
+            .height(Length::Fill)
+            .into()
+    }
+
+    // The style checker's rules and findings, to the right of the canvas.
+    fn render_style_panel(&self) -> Element<'_, Message> {
+        let Some(check) = &self.style_check else {
+            return button("‹").on_press(Message::ToggleStyleCheck).into();
+        };
+
+        let mut rules: Vec<Element<'_, Message>> = Vec::new();
+        for rule in Rule::ALL {
+            let label = match &check.lint {
+                Some(lint) if self.lint_options.is_enabled(rule) => {
+                    format!("{} ({})", rule.name(), lint.count(rule))
+                }
+                _ => rule.name().to_string(),
+            };
+            rules.push(
+                row![
+                    text("■").color(rule.color()),
+                    checkbox(label, self.lint_options.is_enabled(rule))
+                        .on_toggle(move |on| Message::LintRuleToggled(rule, on)),
+                ]
+                .spacing(5)
+                .into(),
+            );
+        }
+        rules.push(
+            row![
+                text("Words per sentence"),
+                text_input("", &self.lint_length)
+                    .on_input(Message::LintLengthChanged)
+                    .width(Length::Fixed(60.0)),
+            ]
+            .spacing(10)
+            .align_y(iced::Alignment::Center)
+            .into(),
+        );
+
+        let findings = check.lint.as_ref().map_or(&[][..], Lint::findings);
+        let mut entries: Vec<Element<'_, Message>> = findings
+            .iter()
+            .take(LINT_LIST_LIMIT)
+            .map(|finding| {
+                button(column![
+                    text(format!("Line {} · {}", finding.vpos + 1, finding.message))
+                        .color(finding.rule.color()),
+                    text(finding.excerpt.clone()).size(12),
+                ])
+                .style(button::text)
+                .width(Length::Fill)
+                .on_press(Message::JumpToFinding(finding.vpos, finding.range.clone()))
+                .into()
+            })
+            .collect();
+        if findings.len() > LINT_LIST_LIMIT {
+            entries.push(text(format!("…and {} more", findings.len() - LINT_LIST_LIMIT)).into());
+        }
+        let body: Element<'_, Message> = match &check.lint {
+            None => text("Checking…").into(),
+            Some(_) if findings.is_empty() => text("Nothing to report").into(),
+            Some(_) => scrollable(iced::widget::Column::with_children(entries))
+                .height(Length::Fill)
+                .into(),
+        };
+
+        let header = row![
+            text("Style"),
+            iced::widget::horizontal_space(),
+            button("›").on_press(Message::ToggleStyleCheck),
+        ]
+        .align_y(iced::Alignment::Center);
+
+        container(
+            column![
+                header,
+                iced::widget::Column::with_children(rules).spacing(5),
+                body
+            ]
+            .spacing(10),
+        )
+        .width(Length::Fixed(STYLE_WIDTH))
+        .height(Length::Fill)
+        .into()
+    }
+
+    // Counts and readability scores of the document and of the selection, side by side.
+    fn render_statistics_panel(&self) -> Option<Element<'_, Message>> {
+        let panel = self.statistics.as_ref()?;
+
+        let document = panel.document.as_ref().map(statistics_rows);
+        let selection = panel.selection.as_ref().map(statistics_rows);
+        // Without a selection its column stays empty.
+        let cell = |rows: &Option<Vec<(&str, String)>>, i: usize| {
+            let value = rows.as_ref().map_or("", |rows| rows[i].1.as_str());
+            text(value.to_string())
+                .width(Length::Fixed(75.0))
+                .align_x(iced::alignment::Horizontal::Right)
+        };
+        let mut rows: Vec<Element<'_, Message>> = vec![
+            row![
+                iced::widget::horizontal_space(),
+                text("Document")
+                    .width(Length::Fixed(75.0))
+                    .align_x(iced::alignment::Horizontal::Right),
+                text("Selection")
+                    .width(Length::Fixed(75.0))
+                    .align_x(iced::alignment::Horizontal::Right),
+            ]
+            .spacing(10)
+            .into(),
+        ];
+        for (i, (name, _)) in statistics_rows(&Statistics::default()).iter().enumerate() {
+            rows.push(
+                row![
+                    text(*name).width(Length::Fill),
+                    cell(&document, i),
+                    cell(&selection, i),
+                ]
+                .spacing(10)
+                .into(),
+            );
+        }
+
+        let header = row![
+            text("Statistics"),
+            iced::widget::horizontal_space(),
+            button("×").on_press(Message::ToggleStatistics),
+        ]
+        .align_y(iced::Alignment::Center);
+
+        let body: Element<'_, Message> = match panel.document {
+            None => text("Counting…").into(),
+            Some(_) => iced::widget::Column::with_children(rows).spacing(5).into(),
+        };
+        let content = container(column![header, body].spacing(10))
+            .width(Length::Fixed(STATISTICS_WIDTH))
+            .height(Length::Fill);
+        Some(content.into())
+    }
+
+    // The goal of the document, how far along it is and the words written lately.
+    fn render_goals_panel(&self) -> Option<Element<'_, Message>> {
+        let panel = self.goals.as_ref()?;
+        let today = progress::today();
+        let words = self.text_editor.word_count();
+        let written = self.written_today();
+
+        let field = |label: &'static str, placeholder: &'static str, value: &str, kind| {
+            row![
+                text(label).width(Length::Fill),
+                text_input(placeholder, value)
+                    .on_input(move |input| Message::GoalChanged(kind, input))
+                    .width(Length::Fixed(110.0)),
+            ]
+            .spacing(10)
+            .align_y(iced::Alignment::Center)
+        };
+        let fields = column![
+            field("Target words", "2500", &panel.target, GoalField::Target),
+            field(
+                "Deadline",
+                "YYYY-MM-DD",
+                &panel.deadline,
+                GoalField::Deadline
+            ),
+            field("Daily quota", "Optional", &panel.daily, GoalField::Daily),
+        ]
+        .spacing(5);
+
+        let mut summary: Vec<Element<'_, Message>> = Vec::new();
+        let mut quota = None;
+        if let Some(goal) = self.goal {
+            summary.push(
+                text(format!(
+                    "{} of {} words ({:.0}%)",
+                    words,
+                    goal.target,
+                    goal.progress(words) * 100.0
+                ))
+                .into(),
+            );
+            if goal.deadline.is_some() {
+                let due = match goal.days_left(today) {
+                    Some(1) => String::from("Due today"),
+                    Some(days) => format!("{} days left", days),
+                    None => String::from("The deadline has passed"),
+                };
+                summary.push(text(due).into());
+            }
+            let words_at_start = (words as i64 - written).max(0) as usize;
+            quota = goal.quota(words_at_start, today);
+        }
+        summary.push(
+            match quota {
+                Some(quota) => text(format!("Today: {} of {} words", written, quota)),
+                None => text(format!("Today: {} words", written)),
+            }
+            .into(),
+        );
+
+        // Bars are relative to the daily quota, or to the best day without one.
+        let mut days = self
+            .history
+            .as_ref()
+            .map(|history| history.recent(today, HISTORY_DAYS))
+            .unwrap_or_default();
+        if let Some((_, last)) = days.last_mut() {
+            *last = written;
+        }
+        let scale = quota
+            .map(|quota| quota as i64)
+            .or_else(|| days.iter().map(|(_, words)| *words).max())
+            .unwrap_or_default()
+            .max(1) as f32;
+        let history = days.iter().rev().map(|(day, words)| {
+            row![
+                text(day.format("%a %d %b").to_string()).width(Length::Fixed(90.0)),
+                progress_bar(0.0..=scale, *words as f32).height(Length::Fixed(8.0)),
+                text(words.to_string())
+                    .width(Length::Fixed(50.0))
+                    .align_x(iced::alignment::Horizontal::Right),
+            ]
+            .spacing(10)
+            .align_y(iced::Alignment::Center)
+            .into()
+        });
+
+        let header = row![
+            text("Goals"),
+            iced::widget::horizontal_space(),
+            button("×").on_press(Message::ToggleGoals),
+        ]
+        .align_y(iced::Alignment::Center);
+
+        let content = container(
+            column![
+                header,
+                fields,
+                iced::widget::Column::with_children(summary).spacing(5),
+                text("History"),
+                scrollable(iced::widget::Column::with_children(history).spacing(5))
+                    .height(Length::Fill),
+            ]
+            .spacing(10),
+        )
+        .width(Length::Fixed(GOALS_WIDTH))
+        .height(Length::Fill);
+        Some(content.into())
+    }
+
+    // The bodies of all notes, below the canvas and numbered like their anchors.
+    fn render_notes_pane(&self) -> Option<Element<'_, Message>> {
+        let entries = self.text_editor.note_entries();
+        if entries.is_empty() {
+            return None;
+        }
+
+        let mut sections: Vec<Element<'_, Message>> = Vec::new();
+        for kind in [NoteKind::Footnote, NoteKind::Endnote] {
+            let rows: Vec<Element<'_, Message>> = entries
+                .iter()
+                .filter(|(_, _, note)| note.kind == kind)
+                .map(|(id, label, note)| {
+                    let id = *id;
+                    row![
+                        text(label.clone()).width(Length::Fixed(30.0)),
+                        text_input("Note text", &note.body)
+                            .id(note_input_id(id))
+                            .on_input(move |body| Message::NoteChanged(id, body))
+                            .on_submit(Message::FinishNote),
+                    ]
+                    .spacing(10)
+                    .align_y(iced::Alignment::Center)
+                    .into()
+                })
+                .collect();
+            if !rows.is_empty() {
+                sections.push(text(kind.name()).into());
+                sections.extend(rows);
+            }
+        }
+
+        let has_footnotes = entries
+            .iter()
+            .any(|(_, _, note)| note.kind == NoteKind::Footnote);
+        let header = row![
+            iced::widget::horizontal_space(),
+            button("Convert to Endnotes")
+                .on_press_maybe(has_footnotes.then_some(Message::ConvertToEndnotes)),
+        ];
+
+        let pane = column![
+            iced::widget::horizontal_rule(1),
+            header,
+            scrollable(iced::widget::Column::with_children(sections).spacing(5))
+                .height(Length::Fixed(NOTES_HEIGHT)),
+        ]
+        .spacing(5);
+        Some(pane.into())
+    }
+
     fn render_status_bar(&self) -> Element<'_, Message> {
         let word_count = self.text_editor.word_count();
         let char_count = self.text_editor.char_count();
-        let counts = text(format!("Words: {}, Characters: {}", word_count, char_count));
+        let counts = match &self.notice {
+            Some(notice) => text(format!(
+                "Words: {}, Characters: {} | {}",
+                word_count, char_count, notice
+            )),
+            None => text(format!("Words: {}, Characters: {}", word_count, char_count)),
+        };
 
         let (hpos, vpos) = self.text_editor.cursor_position();
         let line_number = vpos + 1;
         let column_number = hpos + 1;
         let total_lines = self.text_editor.line_count();
+        let dictionary = self
+            .text_editor
+            .dictionary_name()
+            .map(|name| format!("{} | ", name))
+            .unwrap_or_default();
         let positions = text(format!(
-            "Line: {}/{}, Column: {}",
-            line_number, total_lines, column_number
+            "{}{} | Line: {}/{}, Column: {}",
+            dictionary,
+            self.text_editor.paragraph_style().name(),
+            line_number,
+            total_lines,
+            column_number
         ));
 
-        row![counts, iced::widget::horizontal_space(), positions].into()
+        let session = word_count as i64 - self.session_start as i64;
+        let goal = self.goal.map(|goal| {
+            row![
+                progress_bar(0.0..=1.0, goal.progress(word_count))
+                    .width(Length::Fixed(100.0))
+                    .height(Length::Fixed(8.0)),
+                text(format!("{}/{}", word_count, goal.target)),
+            ]
+            .spacing(5)
+            .align_y(iced::Alignment::Center)
+        });
+
+        row![counts, text(format!("Session: {:+}", session))]
+            .push_maybe(goal)
+            .push(iced::widget::horizontal_space())
+            .push(positions)
+            .push(
+                button("Statistics")
+                    .style(button::text)
+                    .padding(0)
+                    .on_press(Message::ToggleStatistics),
+            )
+            .push(
+                button("Goals")
+                    .style(button::text)
+                    .padding(0)
+                    .on_press(Message::ToggleGoals),
+            )
+            .spacing(10)
+            .align_y(iced::Alignment::Center)
+            .into()
     }
 
     fn update(&mut self, message: Message) -> Task<Message> {
@@ -74,6 +1093,21 @@ impl Blackscript {
                 match event {
                     // Forward keyboard events explicitly.
                     Event::Keyboard(key_event) => {
+                        if let KeyEvent::KeyPressed { key, modifiers, .. } = &key_event
+                            && let Some(message) = Self::shortcut(key, *modifiers)
+                        {
+                            return self.update(message);
+                        }
+                        // The arrow keys pick a match while the citation picker is open.
+                        if self.cite.is_some()
+                            && let KeyEvent::KeyPressed {
+                                key: Key::Named(named @ (Named::ArrowUp | Named::ArrowDown)),
+                                ..
+                            } = &key_event
+                        {
+                            return self
+                                .update(Message::CiteSelectionMoved(*named == Named::ArrowDown));
+                        }
                         if let Some(editor_msg) = self.text_editor.process_keyboard_event(key_event)
                         {
                             return Task::perform(async { editor_msg }, Message::EditorEvent);
@@ -91,11 +1125,17 @@ impl Blackscript {
             Message::EditorEvent(editor_msg) => {
                 // For now, reset the cursor visibility on any cursor change.
                 if let TextEditorMessage::ContentChanged(_, _, _) = editor_msg {
+                    self.mark_modified();
                     // This now toggles the internal state.
                     self.text_editor.toggle_cursor_visibility();
                 } else if let TextEditorMessage::CursorChanged(_, _, _) = editor_msg {
                     // This now toggles the internal state.
                     self.text_editor.toggle_cursor_visibility();
+                } else if let TextEditorMessage::SpellingRequested = editor_msg {
+                    self.spelling = Some(SpellingBar {
+                        misspelling: self.text_editor.misspelling(),
+                        proofreading: false,
+                    });
                 }
                 Task::none()
             }
@@ -104,20 +1144,911 @@ impl Blackscript {
                 self.text_editor.toggle_cursor_visibility();
                 Task::none()
             }
+            Message::Open => Task::perform(
+                document::open_file(self.text_editor.default_style()),
+                Message::FileOpened,
+            ),
+            Message::FileOpened(result) => {
+                match result {
+                    Ok((path, contents)) => {
+                        // Edits to the document being replaced that were made since the
+                        // last autosave go to its journal, not to that of the new one.
+                        let journal = self.write_journal();
+                        let mut notice = None;
+                        let mut file = Some(path.clone());
+                        match contents {
+                            document::Contents::Text(text) => {
+                                self.tally_words();
+                                self.text_editor.set_text(&text);
+                                self.references = References::default();
+                                self.markdown = markdown::Source::default();
+                                self.set_goal(None);
+                                self.start_session();
+                                self.invalidate_lint();
+                                self.invalidate_statistics();
+                            }
+                            document::Contents::Styled(document) => self.load(document),
+                            // A converted document is not bound to the file it came from,
+                            // so saving never writes the lossy conversion over it.
+                            document::Contents::Converted { document, dropped } => {
+                                self.load(document);
+                                file = None;
+                                if !dropped.is_empty() {
+                                    notice = Some(format!(
+                                        "Dropped from {}: {}",
+                                        document::display_name(Some(&path)),
+                                        dropped.join(", ")
+                                    ));
+                                }
+                            }
+                        }
+                        self.file = file;
+                        self.modified = false;
+                        self.notice = notice;
+                        journal
+                    }
+                    Err(error) => {
+                        self.report(error);
+                        Task::none()
+                    }
+                }
+            }
+            Message::Save => Task::batch([
+                self.save_history(),
+                Task::perform(
+                    document::save_file(self.file.clone(), self.document()),
+                    Message::FileSaved,
+                ),
+            ]),
+            Message::SaveAs => Task::perform(
+                document::save_file(None, self.document()),
+                Message::FileSaved,
+            ),
+            Message::FileSaved(result) => match result {
+                Ok(path) => {
+                    // The document is kept open, so what the file could not hold would
+                    // only be missed the next time it is opened.
+                    let left_out = self.document().left_out(document::Format::from_path(&path));
+                    self.notice = (!left_out.is_empty()).then(|| {
+                        format!(
+                            "{} does not keep {}; save as .{} to keep everything",
+                            document::display_name(Some(&path)),
+                            left_out.join(" or "),
+                            document::native::EXTENSION
+                        )
+                    });
+                    let previous = self.file.replace(path.clone());
+                    self.modified = false;
+                    self.journal_pending = false;
+                    Task::batch([
+                        Task::perform(recovery::discard(previous), Message::JournalUpdated),
+                        Task::perform(recovery::discard(Some(path)), Message::JournalUpdated),
+                    ])
+                }
+                Err(error) => {
+                    self.report(error);
+                    Task::none()
+                }
+            },
+            Message::Autosave => Task::batch([self.save_history(), self.write_journal()]),
+            Message::JournalUpdated(result) => {
+                if let Err(error) = result {
+                    self.report(error);
+                }
+                Task::none()
+            }
+            Message::RecoveryFound(snapshot) => {
+                self.recovery = snapshot;
+                Task::none()
+            }
+            Message::RestoreRecovery => {
+                let Some(found) = self.recovery.take() else {
+                    return Task::none();
+                };
+                // Edits made while the banner was up are journaled first, then the
+                // restored changes move to this run's journal before the one they were
+                // found in goes. In that order, a restored untitled document wins over
+                // an untitled one it replaces.
+                let replaced = self.journal_pending.then(|| self.snapshot());
+                let (hpos, vpos) = found.snapshot.cursor;
+                self.load(found.snapshot.document);
+                self.text_editor.set_cursor_position(hpos, vpos);
+                self.file = found.snapshot.path;
+                self.modified = true;
+                self.journal_pending = false;
+                let restored = self.snapshot();
+                Task::perform(
+                    async move {
+                        if let Some(replaced) = replaced {
+                            recovery::write(replaced).await?;
+                        }
+                        recovery::write(restored).await?;
+                        recovery::remove(found.journal).await
+                    },
+                    Message::JournalUpdated,
+                )
+            }
+            Message::Copy => match self.text_editor.selected_text() {
+                Some(text) => iced::clipboard::write(text),
+                None => Task::none(),
+            },
+            Message::Cut => match self.text_editor.cut_selection() {
+                Some(text) => {
+                    self.mark_modified();
+                    iced::clipboard::write(text)
+                }
+                None => Task::none(),
+            },
+            Message::Paste => iced::clipboard::read().map(Message::Pasted),
+            Message::Pasted(contents) => {
+                if let Some(text) = contents.filter(|text| !text.is_empty()) {
+                    self.text_editor.paste(&text);
+                    self.mark_modified();
+                }
+                Task::none()
+            }
+            Message::DiscardRecovery => match self.recovery.take() {
+                Some(found) => {
+                    Task::perform(recovery::remove(found.journal), Message::JournalUpdated)
+                }
+                None => Task::none(),
+            },
+            Message::Find | Message::Replace => {
+                let bar = self.find.get_or_insert_with(FindBar::default);
+                bar.replacing = matches!(message, Message::Replace);
+                // Start from the selected text, as long as it fits in the field.
+                if let Some(selected) = self.text_editor.selected_text()
+                    && !selected.contains('\n')
+                {
+                    bar.query = selected;
+                }
+                // The editor would otherwise receive the keys typed into the bar.
+                self.text_editor.set_focused(false);
+                self.update_search();
+                text_input::focus(find_input_id())
+            }
+            Message::CloseFind => {
+                self.find = None;
+                self.text_editor.set_search(None);
+                self.text_editor.set_focused(true);
+                Task::none()
+            }
+            Message::FindQueryChanged(query) => {
+                if let Some(bar) = &mut self.find {
+                    bar.query = query;
+                }
+                self.update_search();
+                Task::none()
+            }
+            Message::ReplacementChanged(replacement) => {
+                if let Some(bar) = &mut self.find {
+                    bar.replacement = replacement;
+                }
+                Task::none()
+            }
+            Message::SearchOptionsChanged(options) => {
+                if let Some(bar) = &mut self.find {
+                    bar.options = options;
+                }
+                self.update_search();
+                Task::none()
+            }
+            Message::FindNext | Message::FindPrevious => {
+                self.text_editor
+                    .find_next(matches!(message, Message::FindNext));
+                Task::none()
+            }
+            Message::ReplaceOne => {
+                let replacement = self.replacement();
+                if self.text_editor.replace_current(&replacement) {
+                    self.mark_modified();
+                }
+                Task::none()
+            }
+            Message::ReplaceAll => {
+                let replacement = self.replacement();
+                let count = self.text_editor.replace_all(&replacement);
+                if count > 0 {
+                    self.mark_modified();
+                }
+                self.notice = Some(match count {
+                    1 => String::from("Replaced 1 match"),
+                    count => format!("Replaced {} matches", count),
+                });
+                Task::none()
+            }
+            Message::ToggleOutline => {
+                self.outline_open = !self.outline_open;
+                Task::none()
+            }
+            Message::JumpToHeading(vpos) => {
+                self.text_editor.jump_to_line(vpos);
+                Task::none()
+            }
+            Message::InsertNote(kind) => match self.text_editor.insert_note(kind) {
+                Some(id) => {
+                    self.mark_modified();
+                    // Continue with the body of the new note.
+                    self.text_editor.set_focused(false);
+                    text_input::focus(note_input_id(id))
+                }
+                None => Task::none(),
+            },
+            Message::NoteChanged(id, body) => {
+                self.text_editor.set_note_body(id, body);
+                self.mark_modified();
+                Task::none()
+            }
+            Message::FinishNote => {
+                self.text_editor.set_focused(true);
+                unfocus_inputs()
+            }
+            Message::Cite => {
+                self.cite.get_or_insert_with(CitePicker::default);
+                self.text_editor.set_focused(false);
+                text_input::focus(cite_input_id())
+            }
+            Message::CloseCite => {
+                self.cite = None;
+                self.text_editor.set_focused(true);
+                Task::none()
+            }
+            Message::CiteQueryChanged(query) => {
+                if let Some(picker) = &mut self.cite {
+                    picker.query = query;
+                    picker.selected = 0;
+                }
+                Task::none()
+            }
+            Message::CiteSelectionMoved(down) => {
+                if let Some(picker) = &mut self.cite {
+                    let count = self
+                        .references
+                        .search(&picker.query)
+                        .len()
+                        .min(CITE_RESULTS);
+                    picker.selected = match down {
+                        true => (picker.selected + 1).min(count.saturating_sub(1)),
+                        false => picker.selected.saturating_sub(1),
+                    };
+                }
+                Task::none()
+            }
+            Message::SubmitCitation => {
+                let Some(picker) = &self.cite else {
+                    return Task::none();
+                };
+                match self.references.search(&picker.query).get(picker.selected) {
+                    Some(reference) => {
+                        let key = reference.key.clone();
+                        self.update(Message::InsertCitation(key))
+                    }
+                    None => Task::none(),
+                }
+            }
+            Message::InsertCitation(key) => {
+                self.text_editor.paste(&bibliography::citation(&key));
+                self.mark_modified();
+                self.cite = None;
+                self.text_editor.set_focused(true);
+                unfocus_inputs()
+            }
+            Message::ImportBibliography => {
+                Task::perform(bibliography::import_file(), Message::BibliographyImported)
+            }
+            Message::BibliographyImported(result) => {
+                match result {
+                    Ok(references) => {
+                        let count = self.references.merge(references);
+                        self.mark_modified();
+                        self.notice = Some(match count {
+                            1 => String::from("Imported 1 reference"),
+                            count => format!("Imported {} references", count),
+                        });
+                    }
+                    Err(error) => self.report(error),
+                }
+                Task::none()
+            }
+            Message::CitationStyleSelected(style) => {
+                self.citation_style = style;
+                Task::none()
+            }
+            Message::InsertBibliography => {
+                self.insert_bibliography();
+                Task::none()
+            }
+            Message::CheckSpelling => {
+                let misspelling = self.text_editor.next_misspelling();
+                if misspelling.is_none() && self.text_editor.dictionary_name().is_some() {
+                    self.spelling = None;
+                    self.notice = Some(String::from("No misspelled words"));
+                } else {
+                    self.spelling = Some(SpellingBar {
+                        misspelling,
+                        proofreading: true,
+                    });
+                }
+                Task::none()
+            }
+            Message::CloseSpelling => {
+                self.spelling = None;
+                self.text_editor.set_focused(true);
+                Task::none()
+            }
+            Message::CorrectSpelling(replacement) => {
+                if let Some(misspelling) = self.misspelling()
+                    && self.text_editor.correct(&misspelling, &replacement)
+                {
+                    self.mark_modified();
+                }
+                self.spelling_done();
+                Task::none()
+            }
+            Message::AddToDictionary => {
+                let Some(misspelling) = self.misspelling() else {
+                    return Task::none();
+                };
+                self.text_editor.add_to_dictionary(&misspelling.word);
+                self.spelling_done();
+                Task::perform(
+                    dictionary::remember(misspelling.word),
+                    Message::DictionaryUpdated,
+                )
+            }
+            Message::IgnoreWord => {
+                if let Some(misspelling) = self.misspelling() {
+                    self.text_editor.ignore_word(&misspelling.word);
+                }
+                self.spelling_done();
+                Task::none()
+            }
+            Message::LoadDictionary => Task::perform(dictionary::open(), Message::DictionaryLoaded),
+            Message::DictionaryLoaded(result) => {
+                match result {
+                    Ok(speller) => {
+                        self.text_editor.set_speller(Some(speller));
+                        if let Some(bar) = &mut self.spelling {
+                            bar.misspelling = self.text_editor.next_misspelling();
+                        }
+                    }
+                    // Without any dictionary installed the editor just does not check.
+                    Err(document::Error::NoDictionary) => {}
+                    Err(error) => self.report(error),
+                }
+                Task::none()
+            }
+            Message::DictionaryUpdated(result) => {
+                if let Err(error) = result {
+                    self.report(error);
+                }
+                Task::none()
+            }
+            Message::ToggleStyleCheck => {
+                if self.style_check.take().is_some() {
+                    self.text_editor.set_lint(None);
+                    return Task::none();
+                }
+                self.style_check = Some(StyleCheck {
+                    stale: true,
+                    ..StyleCheck::default()
+                });
+                self.start_lint()
+            }
+            Message::LintTick => self.start_lint(),
+            Message::ToggleStatistics => {
+                if self.statistics.take().is_some() {
+                    return Task::none();
+                }
+                self.statistics = Some(StatisticsPanel {
+                    stale: true,
+                    ..StatisticsPanel::default()
+                });
+                self.start_statistics()
+            }
+            Message::StatisticsTick => self.start_statistics(),
+            Message::ToggleGoals => {
+                self.goals = match self.goals {
+                    Some(_) => None,
+                    None => Some(GoalsPanel::new(self.goal)),
+                };
+                Task::none()
+            }
+            Message::GoalChanged(field, input) => {
+                let Some(panel) = &mut self.goals else {
+                    return Task::none();
+                };
+                match field {
+                    GoalField::Target => panel.target = input,
+                    GoalField::Deadline => panel.deadline = input,
+                    GoalField::Daily => panel.daily = input,
+                }
+                // Half-typed fields keep the goal as it was.
+                if let Ok(goal) = panel.goal()
+                    && goal != self.goal
+                {
+                    self.goal = goal;
+                    self.mark_modified();
+                }
+                Task::none()
+            }
+            Message::HistoryLoaded(result) => {
+                match result {
+                    Ok(history) => self.history = Some(history),
+                    Err(error) => self.report(error),
+                }
+                Task::none()
+            }
+            Message::ExportPdf => {
+                self.pdf_export = Some(PdfExportBar {
+                    margin: self.page_setup.margin.to_string(),
+                    line_spacing: self.page_setup.line_spacing.to_string(),
+                });
+                self.text_editor.set_focused(false);
+                Task::none()
+            }
+            Message::ClosePdfExport => {
+                self.pdf_export = None;
+                self.text_editor.set_focused(true);
+                unfocus_inputs()
+            }
+            Message::PageSizeSelected(size) => {
+                self.page_setup.size = size;
+                Task::none()
+            }
+            Message::MarginChanged(input) => {
+                if let Ok(margin) = input.trim().parse::<f32>()
+                    && MARGINS.contains(&margin)
+                {
+                    self.page_setup.margin = margin;
+                }
+                if let Some(bar) = &mut self.pdf_export {
+                    bar.margin = input;
+                }
+                Task::none()
+            }
+            Message::LineSpacingChanged(input) => {
+                if let Ok(spacing) = input.trim().parse::<f32>()
+                    && LINE_SPACINGS.contains(&spacing)
+                {
+                    self.page_setup.line_spacing = spacing;
+                }
+                if let Some(bar) = &mut self.pdf_export {
+                    bar.line_spacing = input;
+                }
+                Task::none()
+            }
+            Message::PageNumbersToggled(on) => {
+                self.page_setup.page_numbers = on;
+                Task::none()
+            }
+            Message::SubmitPdfExport => {
+                if self
+                    .pdf_export
+                    .as_ref()
+                    .and_then(PdfExportBar::problem)
+                    .is_some()
+                {
+                    return Task::none();
+                }
+                Task::perform(
+                    document::pdf::export(self.document(), self.export_name(), self.page_setup),
+                    Message::PdfExported,
+                )
+            }
+            Message::PdfExported(result) => {
+                match result {
+                    Ok(path) => {
+                        self.notice =
+                            Some(format!("Exported {}", document::display_name(Some(&path))));
+                        return self.update(Message::ClosePdfExport);
+                    }
+                    Err(error) => self.report(error),
+                }
+                Task::none()
+            }
+            Message::ExportLatex => Task::perform(
+                document::latex::export(self.document(), self.export_name()),
+                Message::LatexExported,
+            ),
+            Message::LatexExported(result) => {
+                match result {
+                    Ok(path) => {
+                        self.notice =
+                            Some(format!("Exported {}", document::display_name(Some(&path))));
+                    }
+                    Err(error) => self.report(error),
+                }
+                Task::none()
+            }
+            Message::HistorySaved(result) => {
+                if let Err(error) = result {
+                    self.report(error);
+                }
+                Task::none()
+            }
+            Message::StatisticsFinished(document, selection) => {
+                if let Some(panel) = &mut self.statistics {
+                    panel.running = false;
+                    panel.document = Some(document);
+                    panel.selection = selection;
+                }
+                Task::none()
+            }
+            Message::LintFinished(lint) => {
+                // The panel may have been closed while the check ran.
+                if let Some(check) = &mut self.style_check {
+                    check.running = false;
+                    self.text_editor.set_lint(Some(lint.clone()));
+                    check.lint = Some(lint);
+                }
+                Task::none()
+            }
+            Message::LintRuleToggled(rule, on) => {
+                self.lint_options.set_enabled(rule, on);
+                self.invalidate_lint();
+                self.start_lint()
+            }
+            Message::LintLengthChanged(input) => {
+                if let Ok(length) = input.trim().parse::<usize>()
+                    && length > 0
+                {
+                    self.lint_options.max_sentence_words = length;
+                    self.invalidate_lint();
+                }
+                self.lint_length = input;
+                self.start_lint()
+            }
+            Message::JumpToFinding(vpos, range) => {
+                self.text_editor.select_range(vpos, range);
+                unfocus_inputs()
+            }
+            Message::Dismiss => {
+                if self.cite.is_some() {
+                    self.update(Message::CloseCite)
+                } else if self.spelling.is_some() {
+                    self.update(Message::CloseSpelling)
+                } else if self.pdf_export.is_some() {
+                    self.update(Message::ClosePdfExport)
+                } else {
+                    self.update(Message::CloseFind)
+                }
+            }
+            Message::ConvertToEndnotes => {
+                let count = self
+                    .text_editor
+                    .convert_notes(NoteKind::Footnote, NoteKind::Endnote);
+                if count > 0 {
+                    self.mark_modified();
+                }
+                self.notice = Some(match count {
+                    1 => String::from("Converted 1 footnote to an endnote"),
+                    count => format!("Converted {} footnotes to endnotes", count),
+                });
+                Task::none()
+            }
+        }
+    }
+
+    // Compile the query of the find bar and hand it to the editor.
+    fn update_search(&mut self) {
+        let Some(bar) = &mut self.find else {
+            return;
+        };
+        bar.invalid = false;
+        let search = if bar.query.is_empty() {
+            None
+        } else {
+            match Search::new(&bar.query, bar.options) {
+                Ok(search) => Some(search),
+                Err(_) => {
+                    bar.invalid = true;
+                    None
+                }
+            }
+        };
+        self.text_editor.set_search(search);
+    }
+
+    fn document(&self) -> document::Document {
+        document::Document {
+            lines: self.text_editor.all_lines(),
+            notes: self.text_editor.notes(),
+            references: self.references.clone(),
+            goal: self.goal,
+            markdown: self.markdown.clone(),
+        }
+    }
+
+    // The name exports are offered under: that of the open file, without extension.
+    fn export_name(&self) -> String {
+        self.file
+            .as_deref()
+            .and_then(|path| path.file_stem())
+            .map_or(String::from("Untitled"), |stem| {
+                stem.to_string_lossy().into_owned()
+            })
+    }
+
+    fn load(&mut self, document: document::Document) {
+        self.tally_words();
+        self.text_editor
+            .set_contents(document.lines, document.notes);
+        self.start_session();
+        self.references = document.references;
+        self.markdown = document.markdown;
+        self.set_goal(document.goal);
+        self.invalidate_lint();
+        self.invalidate_statistics();
+    }
+
+    // Add a bibliography of every cited reference at the end of the document,
+    // replacing the one generated before.
+    fn insert_bibliography(&mut self) {
+        let lines = self.text_editor.all_lines();
+        let start = bibliography::section_start(&lines).unwrap_or(lines.len());
+        let keys = bibliography::cited_keys(&lines[..start]);
+        let (cited, missing): (Vec<_>, Vec<_>) = keys
+            .iter()
+            .partition(|key| self.references.get(key).is_some());
+        let unresolved = || {
+            let missing: Vec<&str> = missing.iter().map(|key| key.as_str()).collect();
+            format!("No reference found for {}", missing.join(", "))
+        };
+        if cited.is_empty() {
+            self.notice = Some(match missing.is_empty() {
+                true => String::from("Nothing is cited yet"),
+                false => unresolved(),
+            });
+            return;
+        }
+
+        let references: Vec<&Reference> = cited
+            .iter()
+            .filter_map(|key| self.references.get(key))
+            .collect();
+        let section = bibliography::section(
+            &references,
+            self.citation_style,
+            self.text_editor.default_style(),
+        );
+        self.text_editor.replace_to_end(start, section);
+        self.mark_modified();
+        self.notice = Some(if missing.is_empty() {
+            format!("Listed {} cited references", cited.len())
+        } else {
+            unresolved()
+        });
+    }
+
+    fn misspelling(&self) -> Option<Misspelling> {
+        self.spelling.as_ref()?.misspelling.clone()
+    }
+
+    // Close the spelling bar once a word was dealt with, or move on to the next word
+    // while proofreading.
+    fn spelling_done(&mut self) {
+        let proofreading = self.spelling.take().is_some_and(|bar| bar.proofreading);
+        self.text_editor.set_focused(true);
+        if !proofreading {
+            return;
+        }
+        match self.text_editor.next_misspelling() {
+            Some(misspelling) => {
+                self.spelling = Some(SpellingBar {
+                    misspelling: Some(misspelling),
+                    proofreading,
+                });
+            }
+            None => self.notice = Some(String::from("No more misspelled words")),
+        }
+    }
+
+    fn replacement(&self) -> String {
+        self.find
+            .as_ref()
+            .map(|bar| bar.replacement.clone())
+            .unwrap_or_default()
+    }
+
+    // Map application-wide keyboard shortcuts to messages.
+    fn shortcut(key: &Key, modifiers: Modifiers) -> Option<Message> {
+        if *key == Key::Named(Named::Escape) {
+            return Some(Message::Dismiss);
+        }
+        if *key == Key::Named(Named::F7) {
+            return Some(Message::CheckSpelling);
+        }
+        if !modifiers.command() {
+            return None;
+        }
+        let Key::Character(c) = key else {
+            return None;
+        };
+        // The editor handles its own keys first and keeps them from reaching here: undo
+        // and redo, select all, bold, italic, underline, and the paragraph styles on
+        // the digits and Shift with T, Q and C.
+        match c.to_lowercase().as_str() {
+            "f" if modifiers.alt() => Some(Message::InsertNote(NoteKind::Footnote)),
+            "d" if modifiers.alt() => Some(Message::InsertNote(NoteKind::Endnote)),
+            "o" if modifiers.shift() => Some(Message::ToggleOutline),
+            "l" if modifiers.shift() => Some(Message::ToggleStyleCheck),
+            "r" if modifiers.shift() => Some(Message::ToggleStatistics),
+            "g" if modifiers.shift() => Some(Message::ToggleGoals),
+            "e" if modifiers.shift() => Some(Message::ExportPdf),
+            "x" if modifiers.shift() => Some(Message::ExportLatex),
+            "o" => Some(Message::Open),
+            "s" if modifiers.shift() => Some(Message::SaveAs),
+            "s" => Some(Message::Save),
+            "c" if modifiers.alt() => Some(Message::Cite),
+            "c" if !modifiers.shift() => Some(Message::Copy),
+            "x" => Some(Message::Cut),
+            "v" => Some(Message::Paste),
+            "f" => Some(Message::Find),
+            "h" => Some(Message::Replace),
+            _ => None,
+        }
+    }
+
+    fn snapshot(&self) -> recovery::Snapshot {
+        recovery::Snapshot {
+            path: self.file.clone(),
+            cursor: self.text_editor.cursor_position(),
+            document: self.document(),
+        }
+    }
+
+    // Write the changes not yet in the recovery journal to it, if there are any.
+    fn write_journal(&mut self) -> Task<Message> {
+        if !self.journal_pending {
+            return Task::none();
+        }
+        self.journal_pending = false;
+        Task::perform(recovery::write(self.snapshot()), Message::JournalUpdated)
+    }
+
+    fn mark_modified(&mut self) {
+        self.modified = true;
+        self.journal_pending = true;
+        self.invalidate_lint();
+        self.invalidate_statistics();
+    }
+
+    fn invalidate_lint(&mut self) {
+        if let Some(check) = &mut self.style_check {
+            check.stale = true;
+        }
+    }
+
+    // Check the document in the background, unless it has not changed since the last
+    // check or one is still running.
+    fn start_lint(&mut self) -> Task<Message> {
+        let Some(check) = &mut self.style_check else {
+            return Task::none();
+        };
+        if check.running || !check.stale {
+            return Task::none();
+        }
+        check.running = true;
+        check.stale = false;
+        let lines = self.text_editor.all_lines();
+        let options = self.lint_options.clone();
+        Task::perform(
+            async move { lint::check(&lines, &options) },
+            Message::LintFinished,
+        )
+    }
+
+    // Replace the goal of the document, along with what the goals panel shows.
+    fn set_goal(&mut self, goal: Option<Goal>) {
+        self.goal = goal;
+        if let Some(panel) = &mut self.goals {
+            *panel = GoalsPanel::new(goal);
+        }
+    }
+
+    // Count the words written since the last tally as written today. Deleting counts
+    // too, so the history holds net words.
+    fn tally_words(&mut self) {
+        let words = self.text_editor.word_count();
+        let Some(history) = &mut self.history else {
+            return;
+        };
+        if words != self.tallied_words {
+            history.add(progress::today(), words as i64 - self.tallied_words as i64);
+            self.tallied_words = words;
+            self.history_pending = true;
+        }
+    }
+
+    // Start a new session once another document was opened; loading is not writing.
+    // Words written in the previous one must have been tallied before.
+    fn start_session(&mut self) {
+        let words = self.text_editor.word_count();
+        self.session_start = words;
+        self.tallied_words = words;
+    }
+
+    fn save_history(&mut self) -> Task<Message> {
+        self.tally_words();
+        match &self.history {
+            Some(history) if self.history_pending => {
+                self.history_pending = false;
+                Task::perform(
+                    progress::save_history(history.clone()),
+                    Message::HistorySaved,
+                )
+            }
+            _ => Task::none(),
+        }
+    }
+
+    // Net words written today, including those not tallied yet.
+    fn written_today(&self) -> i64 {
+        let untallied = self.text_editor.word_count() as i64 - self.tallied_words as i64;
+        self.history
+            .as_ref()
+            .map_or(0, |history| history.on(progress::today()))
+            + untallied
+    }
+
+    fn invalidate_statistics(&mut self) {
+        if let Some(panel) = &mut self.statistics {
+            panel.stale = true;
+        }
+    }
+
+    // Count the document and the selection in the background, when either changed since
+    // they were last counted.
+    fn start_statistics(&mut self) -> Task<Message> {
+        let Some(panel) = &mut self.statistics else {
+            return Task::none();
+        };
+        let bounds = self.text_editor.selection();
+        if panel.running || (!panel.stale && panel.bounds == bounds) {
+            return Task::none();
+        }
+        panel.running = true;
+        panel.stale = false;
+        panel.bounds = bounds;
+        let lines = self.text_editor.all_lines();
+        let selected = self.text_editor.selected_lines();
+        Task::perform(
+            async move {
+                let selection = selected.map(|lines| Statistics::of(&lines));
+                (Statistics::of(&lines), selection)
+            },
+            |(document, selection)| Message::StatisticsFinished(document, selection),
+        )
+    }
+
+    fn report(&mut self, error: document::Error) {
+        // A cancelled dialog is not worth mentioning.
+        if !matches!(error, document::Error::DialogClosed) {
+            self.notice = Some(error.to_string());
         }
     }
 
     fn subscription(&self) -> Subscription<Message> {
         // Listen for application events and periodically toggle the cursor blink.
-        Subscription::batch(vec![
+        let mut subscriptions = vec![
             iced::event::listen().map(Message::EventOccurred),
             iced::time::every(Duration::from_millis(500)).map(|_| Message::CursorBlink),
-        ])
+            iced::time::every(AUTOSAVE_INTERVAL).map(|_| Message::Autosave),
+        ];
+        if self.style_check.is_some() {
+            subscriptions.push(iced::time::every(LINT_INTERVAL).map(|_| Message::LintTick));
+        }
+        if self.statistics.is_some() {
+            subscriptions
+                .push(iced::time::every(STATISTICS_INTERVAL).map(|_| Message::StatisticsTick));
+        }
+        Subscription::batch(subscriptions)
     }
 }
 
 fn main() -> iced::Result {
-    iced::application("Blackscript", Blackscript::update, Blackscript::view)
+    iced::application(Blackscript::title, Blackscript::update, Blackscript::view)
         .subscription(Blackscript::subscription)
-        .run()
+        .run_with(Blackscript::boot)
 }
@@ -0,0 +1,212 @@
+// history.rs
+use std::collections::VecDeque;
+
+use super::paragraph::ParagraphStyle;
+use super::textarea::{Line, TextPosition};
+
+// The number of undo steps that are kept around.
+const MAX_TRANSACTIONS: usize = 500;
+
+// A single invertible change to the document. The fragment holds the inserted or
+// removed lines including their styles, so undoing restores the exact formatting.
+#[derive(Debug, Clone)]
+pub enum Edit {
+    Insert {
+        at: TextPosition,
+        fragment: Vec<Line>,
+    },
+    Delete {
+        at: TextPosition,
+        fragment: Vec<Line>,
+    },
+    Paragraph {
+        vpos: usize,
+        from: ParagraphStyle,
+        to: ParagraphStyle,
+    },
+}
+
+impl Edit {
+    pub fn inverse(&self) -> Edit {
+        match self {
+            Edit::Insert { at, fragment } => Edit::Delete {
+                at: *at,
+                fragment: fragment.clone(),
+            },
+            Edit::Delete { at, fragment } => Edit::Insert {
+                at: *at,
+                fragment: fragment.clone(),
+            },
+            Edit::Paragraph { vpos, from, to } => Edit::Paragraph {
+                vpos: *vpos,
+                from: *to,
+                to: *from,
+            },
+        }
+    }
+}
+
+// The position just after a fragment that starts at the given position.
+pub fn fragment_end(at: TextPosition, fragment: &[Line]) -> TextPosition {
+    match fragment {
+        [] => at,
+        [only] => TextPosition::new(at.hpos + only.content.len(), at.vpos),
+        [.., last] => TextPosition::new(last.content.len(), at.vpos + fragment.len() - 1),
+    }
+}
+
+// How an edit came about, which decides whether it merges with the previous one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditKind {
+    Typing,
+    Deleting,
+    Other,
+}
+
+// One undo step.
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    pub edits: Vec<Edit>,
+    pub cursor_before: TextPosition,
+    pub cursor_after: TextPosition,
+    kind: EditKind,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EditHistory {
+    undo: VecDeque<Transaction>,
+    redo: Vec<Transaction>,
+    // While a group is open every edit lands in the same transaction.
+    group_depth: usize,
+    group_started: bool,
+    // Set when the cursor moved, so the next edit starts a new step.
+    sealed: bool,
+}
+
+impl EditHistory {
+    pub fn record(
+        &mut self,
+        edit: Edit,
+        cursor_before: TextPosition,
+        cursor_after: TextPosition,
+        kind: EditKind,
+    ) {
+        self.redo.clear();
+
+        if self.group_depth > 0
+            && self.group_started
+            && let Some(last) = self.undo.back_mut()
+        {
+            last.edits.push(edit);
+            last.cursor_after = cursor_after;
+            return;
+        }
+
+        if !self.sealed
+            && self.group_depth == 0
+            && let Some(last) = self.undo.back_mut()
+            && Self::coalesces(last, &edit, cursor_before, kind)
+        {
+            last.edits.push(edit);
+            last.cursor_after = cursor_after;
+            return;
+        }
+
+        self.undo.push_back(Transaction {
+            edits: vec![edit],
+            cursor_before,
+            cursor_after,
+            kind,
+        });
+        if self.undo.len() > MAX_TRANSACTIONS {
+            self.undo.pop_front();
+        }
+        self.group_started = self.group_depth > 0;
+        self.sealed = false;
+    }
+
+    // Consecutive typing and deleting merge into word-sized steps.
+    fn coalesces(
+        last: &Transaction,
+        edit: &Edit,
+        cursor_before: TextPosition,
+        kind: EditKind,
+    ) -> bool {
+        if kind == EditKind::Other || last.kind != kind || last.cursor_after != cursor_before {
+            return false;
+        }
+
+        match (last.edits.last(), edit) {
+            (Some(Edit::Insert { fragment: prev, .. }), Edit::Insert { fragment, .. }) => {
+                // A word character typed after whitespace begins a new step.
+                match (last_char(prev), first_char(fragment)) {
+                    (Some(prev), Some(next)) => !prev.is_whitespace() || next.is_whitespace(),
+                    _ => false,
+                }
+            }
+            (Some(Edit::Delete { fragment: prev, .. }), Edit::Delete { fragment, .. }) => {
+                // Deleting stops merging when it crosses from a word into whitespace.
+                match (first_char(prev), last_char(fragment)) {
+                    (Some(prev), Some(next)) => prev.is_whitespace() || !next.is_whitespace(),
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    // Make sure the next edit starts a new undo step.
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    // Collect every edit until the matching end_group into a single undo step.
+    pub fn begin_group(&mut self) {
+        if self.group_depth == 0 {
+            self.group_started = false;
+        }
+        self.group_depth += 1;
+    }
+
+    pub fn end_group(&mut self) {
+        self.group_depth = self.group_depth.saturating_sub(1);
+        if self.group_depth == 0 {
+            self.sealed = true;
+        }
+    }
+
+    pub fn undo(&mut self) -> Option<Transaction> {
+        let transaction = self.undo.pop_back()?;
+        self.redo.push(transaction.clone());
+        self.sealed = true;
+        Some(transaction)
+    }
+
+    pub fn redo(&mut self) -> Option<Transaction> {
+        let transaction = self.redo.pop()?;
+        self.undo.push_back(transaction.clone());
+        self.sealed = true;
+        Some(transaction)
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.sealed = false;
+    }
+}
+
+fn first_char(fragment: &[Line]) -> Option<char> {
+    match fragment {
+        [only] => only.content.first().copied(),
+        // A line break counts as whitespace.
+        _ => Some('\n'),
+    }
+}
+
+fn last_char(fragment: &[Line]) -> Option<char> {
+    match fragment {
+        [only] => only.content.last().copied(),
+        _ => Some('\n'),
+    }
+}
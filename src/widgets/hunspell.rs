@@ -0,0 +1,867 @@
+// hunspell.rs
+//
+// Hunspell dictionaries. The .dic file lists stems with flags naming the prefixes and
+// suffixes from the .aff file they take, so a word is spelled right when it is a stem,
+// a stem with affixes it allows, or a compound of stems that may be compounded. Only
+// what checking and suggesting needs is read; morphology, conversion tables and the
+// rarer options are skipped.
+use std::collections::HashMap;
+use std::fmt;
+
+type Flag = u32;
+
+// Suggestions past this many are not worth showing.
+const MAX_SUGGESTIONS: usize = 8;
+// Words this close to a misspelling, measured by shared pairs of letters, are offered
+// when no single edit fixes it.
+const NGRAM_THRESHOLD: f32 = 0.5;
+const NGRAM_SUGGESTIONS: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DictionaryError {
+    pub line: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for DictionaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            0 => write!(f, "{}", self.reason),
+            line => write!(f, "line {}: {}", line, self.reason),
+        }
+    }
+}
+
+fn error(line: usize, reason: impl Into<String>) -> DictionaryError {
+    DictionaryError {
+        line,
+        reason: reason.into(),
+    }
+}
+
+// How flags are written: one character each by default, or pairs of characters,
+// comma-separated numbers or single Unicode characters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum FlagMode {
+    #[default]
+    Short,
+    Long,
+    Numeric,
+    Utf8,
+}
+
+impl FlagMode {
+    fn parse(self, text: &str) -> Vec<Flag> {
+        match self {
+            FlagMode::Short | FlagMode::Utf8 => text.chars().map(|c| c as Flag).collect(),
+            FlagMode::Long => text
+                .chars()
+                .collect::<Vec<_>>()
+                .chunks(2)
+                .map(|pair| pair.iter().fold(0, |flag, c| flag << 16 | *c as Flag))
+                .collect(),
+            FlagMode::Numeric => text
+                .split(',')
+                .filter_map(|number| number.trim().parse().ok())
+                .collect(),
+        }
+    }
+}
+
+// What the stem next to an affix has to look like, as a sequence of characters,
+// `.` wildcards and `[...]` or `[^...]` classes.
+#[derive(Debug, Clone, Default)]
+struct Condition {
+    parts: Vec<(Vec<char>, bool)>,
+}
+
+impl Condition {
+    fn parse(text: &str) -> Self {
+        let mut parts = Vec::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                // An empty negated class matches anything.
+                '.' => parts.push((Vec::new(), true)),
+                '[' => {
+                    let mut class: Vec<char> = chars.by_ref().take_while(|c| *c != ']').collect();
+                    let negated = class.first() == Some(&'^');
+                    if negated {
+                        class.remove(0);
+                    }
+                    parts.push((class, negated));
+                }
+                c => parts.push((vec![c], false)),
+            }
+        }
+        Self { parts }
+    }
+
+    fn matches(&self, chars: &[char]) -> bool {
+        chars.len() == self.parts.len()
+            && self
+                .parts
+                .iter()
+                .zip(chars)
+                .all(|((class, negated), c)| class.contains(c) != *negated)
+    }
+
+    fn matches_start(&self, word: &[char]) -> bool {
+        word.len() >= self.parts.len() && self.matches(&word[..self.parts.len()])
+    }
+
+    fn matches_end(&self, word: &[char]) -> bool {
+        word.len() >= self.parts.len() && self.matches(&word[word.len() - self.parts.len()..])
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Affix {
+    flag: Flag,
+    // Whether it combines with affixes from the other side.
+    cross: bool,
+    strip: String,
+    add: String,
+    condition: Condition,
+    // Flags of further affixes that may follow this one.
+    continuation: Vec<Flag>,
+}
+
+impl Affix {
+    // The stem a prefixed word comes from.
+    fn unprefix(&self, word: &str) -> Option<String> {
+        let rest = word.strip_prefix(self.add.as_str())?;
+        if rest.is_empty() && self.strip.is_empty() {
+            return None;
+        }
+        let stem = format!("{}{}", self.strip, rest);
+        let chars: Vec<char> = stem.chars().collect();
+        self.condition.matches_start(&chars).then_some(stem)
+    }
+
+    // The stem a suffixed word comes from.
+    fn unsuffix(&self, word: &str) -> Option<String> {
+        let rest = word.strip_suffix(self.add.as_str())?;
+        if rest.is_empty() && self.strip.is_empty() {
+            return None;
+        }
+        let stem = format!("{}{}", rest, self.strip);
+        let chars: Vec<char> = stem.chars().collect();
+        self.condition.matches_end(&chars).then_some(stem)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Compounding {
+    flag: Option<Flag>,
+    begin: Option<Flag>,
+    middle: Option<Flag>,
+    end: Option<Flag>,
+    // The shortest part of a compound, in characters.
+    min: usize,
+}
+
+impl Compounding {
+    fn enabled(&self) -> bool {
+        self.flag.is_some() || self.begin.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Casing {
+    Lower,
+    Title,
+    Upper,
+    Mixed,
+}
+
+fn casing(word: &str) -> Casing {
+    let mut letters = word.chars().filter(|c| c.is_alphabetic());
+    let Some(first) = letters.next() else {
+        return Casing::Lower;
+    };
+    let rest: Vec<char> = letters.collect();
+    let rest_upper = rest.iter().filter(|c| c.is_uppercase()).count();
+    match (first.is_uppercase(), rest_upper) {
+        (false, 0) => Casing::Lower,
+        (true, 0) => Casing::Title,
+        (true, upper) if upper == rest.len() => Casing::Upper,
+        _ => Casing::Mixed,
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn has(flags: &[Flag], flag: Option<Flag>) -> bool {
+    flag.is_some_and(|flag| flags.contains(&flag))
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Dictionary {
+    // The flags of every stem; a stem listed twice keeps both sets.
+    stems: HashMap<String, Vec<Vec<Flag>>>,
+    prefixes: Vec<Affix>,
+    suffixes: Vec<Affix>,
+    flag_mode: FlagMode,
+    aliases: Vec<Vec<Flag>>,
+    try_chars: Vec<char>,
+    // Groups of keys next to each other on the keyboard.
+    keys: Vec<Vec<char>>,
+    replacements: Vec<(String, String)>,
+    ignore: Vec<char>,
+    forbidden: Option<Flag>,
+    need_affix: Option<Flag>,
+    keep_case: Option<Flag>,
+    only_in_compound: Option<Flag>,
+    no_suggest: Option<Flag>,
+    compounding: Compounding,
+}
+
+impl Dictionary {
+    // Read a dictionary from the raw bytes of its .aff and .dic files, in the
+    // encoding the .aff file names.
+    pub fn from_bytes(aff: &[u8], dic: &[u8]) -> Result<Self, DictionaryError> {
+        let encoding = aff
+            .split(|byte| *byte == b'\n')
+            .find_map(|line| line.strip_prefix(b"SET "))
+            .map(|name| String::from_utf8_lossy(name).trim().to_uppercase())
+            .unwrap_or_else(|| String::from("ISO8859-1"));
+        Self::parse(&decode(aff, &encoding)?, &decode(dic, &encoding)?)
+    }
+
+    pub fn parse(aff: &str, dic: &str) -> Result<Self, DictionaryError> {
+        let mut dictionary = Self {
+            compounding: Compounding {
+                min: 3,
+                ..Compounding::default()
+            },
+            ..Self::default()
+        };
+        dictionary.read_aff(aff)?;
+        dictionary.read_dic(dic);
+        Ok(dictionary)
+    }
+
+    fn read_aff(&mut self, aff: &str) -> Result<(), DictionaryError> {
+        // Affix classes still expecting entries, by kind and flag.
+        let mut pending: HashMap<(String, String), (bool, usize)> = HashMap::new();
+
+        for (index, line) in aff.lines().enumerate() {
+            let number = index + 1;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let Some((&command, arguments)) = fields.split_first() else {
+                continue;
+            };
+            let argument = arguments.first().copied().unwrap_or_default();
+            match command {
+                "FLAG" => {
+                    self.flag_mode = match argument {
+                        "long" => FlagMode::Long,
+                        "num" => FlagMode::Numeric,
+                        "UTF-8" => FlagMode::Utf8,
+                        other => return Err(error(number, format!("unknown flag type {}", other))),
+                    }
+                }
+                // The first AF line only gives the count.
+                "AF" if argument.parse::<usize>().is_err() || arguments.len() > 1 => {
+                    self.aliases.push(self.flag_mode.parse(argument));
+                }
+                "TRY" => self.try_chars = argument.chars().collect(),
+                "KEY" => {
+                    self.keys = argument
+                        .split('|')
+                        .map(|group| group.chars().collect())
+                        .collect();
+                }
+                "REP" if arguments.len() >= 2 => {
+                    let unescape = |text: &str| text.replace('_', " ");
+                    self.replacements
+                        .push((unescape(arguments[0]), unescape(arguments[1])));
+                }
+                "IGNORE" => self.ignore = argument.chars().collect(),
+                "FORBIDDENWORD" => self.forbidden = self.flag(argument),
+                "NEEDAFFIX" | "PSEUDOROOT" => self.need_affix = self.flag(argument),
+                "KEEPCASE" => self.keep_case = self.flag(argument),
+                "ONLYINCOMPOUND" => self.only_in_compound = self.flag(argument),
+                "NOSUGGEST" => self.no_suggest = self.flag(argument),
+                "COMPOUNDFLAG" => self.compounding.flag = self.flag(argument),
+                "COMPOUNDBEGIN" => self.compounding.begin = self.flag(argument),
+                "COMPOUNDMIDDLE" => self.compounding.middle = self.flag(argument),
+                "COMPOUNDEND" => self.compounding.end = self.flag(argument),
+                "COMPOUNDMIN" => {
+                    self.compounding.min = argument.parse().unwrap_or(3).max(1);
+                }
+                "PFX" | "SFX" => {
+                    let key = (command.to_string(), argument.to_string());
+                    match pending.get_mut(&key) {
+                        Some((cross, remaining)) if *remaining > 0 => {
+                            *remaining -= 1;
+                            let affix = self.affix(arguments, *cross, number)?;
+                            match command {
+                                "PFX" => self.prefixes.push(affix),
+                                _ => self.suffixes.push(affix),
+                            }
+                        }
+                        _ => {
+                            let [_, cross, count, ..] = arguments else {
+                                return Err(error(number, "incomplete affix header"));
+                            };
+                            let count = count
+                                .parse()
+                                .map_err(|_| error(number, "affix count is not a number"))?;
+                            pending.insert(key, (*cross == "Y", count));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // An affix entry like `SFX D y ied [^aeiou]y`.
+    fn affix(
+        &self,
+        arguments: &[&str],
+        cross: bool,
+        line: usize,
+    ) -> Result<Affix, DictionaryError> {
+        let [flag, strip, add, rest @ ..] = arguments else {
+            return Err(error(line, "incomplete affix entry"));
+        };
+        let empty = |text: &str| {
+            if text == "0" {
+                String::new()
+            } else {
+                text.to_string()
+            }
+        };
+        let (add, continuation) = match add.split_once('/') {
+            Some((add, flags)) => (empty(add), self.flags(flags)),
+            None => (empty(add), Vec::new()),
+        };
+        Ok(Affix {
+            flag: self.flag(flag).unwrap_or_default(),
+            cross,
+            strip: empty(strip),
+            add,
+            condition: Condition::parse(rest.first().copied().unwrap_or(".")),
+            continuation,
+        })
+    }
+
+    fn flag(&self, text: &str) -> Option<Flag> {
+        self.flag_mode.parse(text).first().copied()
+    }
+
+    // A set of flags, which is a number when the .aff file defines aliases for them.
+    fn flags(&self, text: &str) -> Vec<Flag> {
+        match text.parse::<usize>() {
+            Ok(alias) if !self.aliases.is_empty() => self
+                .aliases
+                .get(alias.wrapping_sub(1))
+                .cloned()
+                .unwrap_or_default(),
+            _ => self.flag_mode.parse(text),
+        }
+    }
+
+    fn read_dic(&mut self, dic: &str) {
+        // The first line only estimates the number of stems.
+        for line in dic.lines().skip(1) {
+            // Morphological fields follow the stem after whitespace.
+            let Some(entry) = line
+                .split(['\t', ' '])
+                .next()
+                .filter(|entry| !entry.is_empty())
+            else {
+                continue;
+            };
+            let (stem, flags) = split_entry(entry);
+            let flags = flags.map(|flags| self.flags(flags)).unwrap_or_default();
+            self.stems.entry(stem).or_default().push(flags);
+        }
+    }
+
+    pub fn check(&self, word: &str) -> bool {
+        let word: String = word.chars().filter(|c| !self.ignore.contains(c)).collect();
+        if word.is_empty() {
+            return true;
+        }
+        if self.accepts(&word, true) {
+            return true;
+        }
+        let lower = word.to_lowercase();
+        match casing(&word) {
+            Casing::Lower | Casing::Mixed => false,
+            Casing::Title => !self.is_forbidden(&word) && self.accepts(&lower, false),
+            Casing::Upper => {
+                !self.is_forbidden(&word)
+                    && (self.accepts(&capitalize(&lower), false) || self.accepts(&lower, false))
+            }
+        }
+    }
+
+    fn is_forbidden(&self, word: &str) -> bool {
+        self.stems
+            .get(word)
+            .is_some_and(|homonyms| homonyms.iter().any(|flags| has(flags, self.forbidden)))
+    }
+
+    // Whether a stem exists with flags that pass a test. Forbidden stems never do, and
+    // stems that keep their case only when the case was not changed.
+    fn has_stem(&self, stem: &str, exact_case: bool, test: impl Fn(&[Flag]) -> bool) -> bool {
+        self.stems.get(stem).is_some_and(|homonyms| {
+            homonyms.iter().any(|flags| {
+                !has(flags, self.forbidden)
+                    && (exact_case || !has(flags, self.keep_case))
+                    && test(flags)
+            })
+        })
+    }
+
+    fn accepts(&self, word: &str, exact_case: bool) -> bool {
+        if self.is_forbidden(word) {
+            return false;
+        }
+        self.has_stem(word, exact_case, |flags| {
+            !has(flags, self.need_affix) && !has(flags, self.only_in_compound)
+        }) || self.suffixed(word, exact_case)
+            || self.prefixed(word, exact_case)
+            || (self.compounding.enabled() && self.compound(word, exact_case, false))
+    }
+
+    fn suffixed(&self, word: &str, exact_case: bool) -> bool {
+        self.suffixes.iter().any(|suffix| {
+            let Some(stem) = suffix.unsuffix(word) else {
+                return false;
+            };
+            self.has_stem(&stem, exact_case, |flags| {
+                flags.contains(&suffix.flag) && !has(flags, self.only_in_compound)
+            }) ||
+            // Two suffixes in a row, where the inner one allows the outer.
+            self.suffixes
+                .iter()
+                .filter(|inner| inner.continuation.contains(&suffix.flag))
+                .any(|inner| {
+                    inner.unsuffix(&stem).is_some_and(|root| {
+                        self.has_stem(&root, exact_case, |flags| flags.contains(&inner.flag))
+                    })
+                })
+        })
+    }
+
+    fn prefixed(&self, word: &str, exact_case: bool) -> bool {
+        self.prefixes.iter().any(|prefix| {
+            let Some(stem) = prefix.unprefix(word) else {
+                return false;
+            };
+            self.has_stem(&stem, exact_case, |flags| {
+                flags.contains(&prefix.flag) && !has(flags, self.only_in_compound)
+            }) || (prefix.cross
+                && self
+                    .suffixes
+                    .iter()
+                    .filter(|suffix| suffix.cross)
+                    .any(|suffix| {
+                        suffix.unsuffix(&stem).is_some_and(|root| {
+                            self.has_stem(&root, exact_case, |flags| {
+                                flags.contains(&suffix.flag)
+                                    && (flags.contains(&prefix.flag)
+                                        || suffix.continuation.contains(&prefix.flag))
+                            })
+                        })
+                    }))
+        })
+    }
+
+    // Whether a word splits into stems that may be compounded, the first one allowed
+    // to begin a compound (or continue one), the last to end it.
+    fn compound(&self, word: &str, exact_case: bool, inside: bool) -> bool {
+        let chars: Vec<char> = word.chars().collect();
+        let min = self.compounding.min;
+        if chars.len() < 2 * min {
+            return false;
+        }
+        let part = |text: &str, position: Option<Flag>| {
+            self.has_stem(text, exact_case, |flags| {
+                has(flags, self.compounding.flag) || has(flags, position)
+            })
+        };
+        let opening = match inside {
+            true => self.compounding.middle,
+            false => self.compounding.begin,
+        };
+        (min..=chars.len() - min).any(|split| {
+            let head: String = chars[..split].iter().collect();
+            let tail: String = chars[split..].iter().collect();
+            part(&head, opening)
+                && (part(&tail, self.compounding.end) || self.compound(&tail, exact_case, true))
+        })
+    }
+
+    // Corrections for a misspelled word, most likely first.
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let mut found: Vec<String> = Vec::new();
+        let offer = |candidate: String, found: &mut Vec<String>| {
+            if found.len() < MAX_SUGGESTIONS
+                && candidate != word
+                && !found.contains(&candidate)
+                && candidate.split(' ').all(|part| self.suggestible(part))
+            {
+                found.push(candidate);
+            }
+        };
+
+        // The word in another case.
+        let lower = word.to_lowercase();
+        offer(lower.clone(), &mut found);
+        offer(capitalize(&lower), &mut found);
+
+        let chars: Vec<char> = word.chars().collect();
+        for candidate in self.edits(word, &chars) {
+            offer(candidate, &mut found);
+        }
+        if found.is_empty() {
+            for candidate in self.similar_stems(&lower) {
+                offer(candidate, &mut found);
+            }
+        }
+
+        // Keep the capitals the word was typed with.
+        match casing(word) {
+            Casing::Title => found.iter_mut().for_each(|s| *s = capitalize(s)),
+            Casing::Upper => found.iter_mut().for_each(|s| *s = s.to_uppercase()),
+            _ => {}
+        }
+        let mut unique = Vec::new();
+        for suggestion in found {
+            if suggestion != word && !unique.contains(&suggestion) {
+                unique.push(suggestion);
+            }
+        }
+        unique
+    }
+
+    fn suggestible(&self, word: &str) -> bool {
+        !word.is_empty()
+            && self.check(word)
+            && !self
+                .stems
+                .get(word)
+                .is_some_and(|homonyms| homonyms.iter().all(|flags| has(flags, self.no_suggest)))
+    }
+
+    // Candidates one edit away, in the order Hunspell tries them: common replacements,
+    // swapped letters, neighbouring keys, extra, missing and wrong letters, and a
+    // missing space.
+    fn edits(&self, word: &str, chars: &[char]) -> Vec<String> {
+        let mut candidates = Vec::new();
+        let collect = |chars: &[char]| chars.iter().collect::<String>();
+
+        for (from, to) in &self.replacements {
+            let anchored_start = from.starts_with('^');
+            let anchored_end = from.ends_with('$') && from.len() > 1;
+            let pattern = from.trim_start_matches('^').trim_end_matches('$');
+            if pattern.is_empty() {
+                continue;
+            }
+            for (offset, _) in word.match_indices(pattern) {
+                if (anchored_start && offset != 0)
+                    || (anchored_end && offset + pattern.len() != word.len())
+                {
+                    continue;
+                }
+                candidates.push(format!(
+                    "{}{}{}",
+                    &word[..offset],
+                    to,
+                    &word[offset + pattern.len()..]
+                ));
+            }
+        }
+        for i in 1..chars.len() {
+            let mut swapped = chars.to_vec();
+            swapped.swap(i - 1, i);
+            candidates.push(collect(&swapped));
+        }
+        for (i, c) in chars.iter().enumerate() {
+            for group in self.keys.iter().filter(|group| group.contains(c)) {
+                let at = group.iter().position(|key| key == c).unwrap_or_default();
+                for neighbour in [at.checked_sub(1), Some(at + 1)]
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|n| group.get(n))
+                {
+                    let mut replaced = chars.to_vec();
+                    replaced[i] = *neighbour;
+                    candidates.push(collect(&replaced));
+                }
+            }
+        }
+        for i in 0..chars.len() {
+            let mut shorter = chars.to_vec();
+            shorter.remove(i);
+            candidates.push(collect(&shorter));
+        }
+        for i in 0..=chars.len() {
+            for c in &self.try_chars {
+                let mut longer = chars.to_vec();
+                longer.insert(i, *c);
+                candidates.push(collect(&longer));
+            }
+        }
+        for i in 0..chars.len() {
+            for c in self.try_chars.iter().filter(|c| **c != chars[i]) {
+                let mut replaced = chars.to_vec();
+                replaced[i] = *c;
+                candidates.push(collect(&replaced));
+            }
+        }
+        for i in 1..chars.len() {
+            candidates.push(format!("{} {}", collect(&chars[..i]), collect(&chars[i..])));
+        }
+        candidates
+    }
+
+    // Stems that share most of their pairs of letters with the word, for misspellings
+    // too far off for a single edit.
+    fn similar_stems(&self, word: &str) -> Vec<String> {
+        let pairs = |text: &str| {
+            let chars: Vec<char> = text.to_lowercase().chars().collect();
+            chars.windows(2).map(|w| (w[0], w[1])).collect::<Vec<_>>()
+        };
+        let wanted = pairs(word);
+        let length = word.chars().count();
+        if wanted.is_empty() {
+            return Vec::new();
+        }
+
+        let mut scored: Vec<(f32, &String)> = self
+            .stems
+            .iter()
+            .filter(|(stem, homonyms)| {
+                stem.chars().count().abs_diff(length) <= 2
+                    && homonyms.iter().any(|flags| {
+                        !has(flags, self.forbidden)
+                            && !has(flags, self.no_suggest)
+                            && !has(flags, self.need_affix)
+                            && !has(flags, self.only_in_compound)
+                    })
+            })
+            .filter_map(|(stem, _)| {
+                let candidate = pairs(stem);
+                let shared = wanted
+                    .iter()
+                    .filter(|pair| candidate.contains(pair))
+                    .count();
+                let score = 2.0 * shared as f32 / (wanted.len() + candidate.len()) as f32;
+                (score >= NGRAM_THRESHOLD).then_some((score, stem))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        scored
+            .into_iter()
+            .take(NGRAM_SUGGESTIONS)
+            .map(|(_, stem)| stem.clone())
+            .collect()
+    }
+}
+
+// Split a .dic entry into the stem and its flags. A slash in the stem is escaped.
+fn split_entry(entry: &str) -> (String, Option<&str>) {
+    let mut stem = String::new();
+    let mut chars = entry.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '\\' if chars.peek().is_some_and(|(_, next)| *next == '/') => {
+                stem.push('/');
+                chars.next();
+            }
+            '/' => return (stem, Some(&entry[offset + 1..])),
+            c => stem.push(c),
+        }
+    }
+    (stem, None)
+}
+
+fn decode(bytes: &[u8], encoding: &str) -> Result<String, DictionaryError> {
+    // The few characters where Latin-9 differs from Latin-1.
+    const LATIN9: [(u8, char); 8] = [
+        (0xA4, '€'),
+        (0xA6, 'Š'),
+        (0xA8, 'š'),
+        (0xB4, 'Ž'),
+        (0xB8, 'ž'),
+        (0xBC, 'Œ'),
+        (0xBD, 'œ'),
+        (0xBE, 'Ÿ'),
+    ];
+    match encoding {
+        "UTF-8" | "UTF8" => {
+            let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+            String::from_utf8(bytes.to_vec()).map_err(|_| error(0, "the files are not valid UTF-8"))
+        }
+        "ISO8859-1" | "ISO-8859-1" => Ok(bytes.iter().map(|byte| char::from(*byte)).collect()),
+        "ISO8859-15" | "ISO-8859-15" => Ok(bytes
+            .iter()
+            .map(|byte| {
+                LATIN9
+                    .iter()
+                    .find(|(code, _)| code == byte)
+                    .map_or(char::from(*byte), |(_, c)| *c)
+            })
+            .collect()),
+        other => Err(error(0, format!("unsupported encoding {}", other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(aff: &str, dic: &[&str]) -> Dictionary {
+        let dic = format!("{}\n{}\n", dic.len(), dic.join("\n"));
+        Dictionary::parse(aff, &dic).expect("valid dictionary")
+    }
+
+    #[test]
+    fn suffixes_follow_their_condition() {
+        let aff = "SFX D Y 2\nSFX D y ied [^aeiou]y\nSFX D 0 ed [^y]\n";
+        let dictionary = load(aff, &["carry/D", "play/D", "walk/D"]);
+        assert!(dictionary.check("carried"));
+        assert!(dictionary.check("walked"));
+        assert!(dictionary.check("carry"));
+        assert!(!dictionary.check("played"));
+        assert!(!dictionary.check("plaied"));
+        assert!(!dictionary.check("carryed"));
+    }
+
+    #[test]
+    fn long_flags() {
+        let aff = "FLAG long\nSFX Aa Y 1\nSFX Aa 0 s .\nSFX Ab Y 1\nSFX Ab 0 ed .\n";
+        let dictionary = load(aff, &["dog/Aa", "jump/AaAb"]);
+        assert!(dictionary.check("dogs"));
+        assert!(dictionary.check("jumps"));
+        assert!(dictionary.check("jumped"));
+        assert!(!dictionary.check("dogged"));
+    }
+
+    #[test]
+    fn numeric_flags() {
+        let aff = "FLAG num\nSFX 101 Y 1\nSFX 101 0 s .\nSFX 7 Y 1\nSFX 7 0 ed .\n";
+        let dictionary = load(aff, &["dog/101", "jump/7,101"]);
+        assert!(dictionary.check("dogs"));
+        assert!(dictionary.check("jumps"));
+        assert!(dictionary.check("jumped"));
+        assert!(!dictionary.check("dogged"));
+    }
+
+    #[test]
+    fn unicode_flags() {
+        let aff = "FLAG UTF-8\nSFX Ü Y 1\nSFX Ü 0 s .\n";
+        let dictionary = load(aff, &["tree/Ü", "bush"]);
+        assert!(dictionary.check("trees"));
+        assert!(!dictionary.check("bushs"));
+    }
+
+    #[test]
+    fn flag_aliases() {
+        let aff = "AF 2\nAF S\nAF SD\nSFX S Y 1\nSFX S 0 s .\nSFX D Y 1\nSFX D 0 ed .\n";
+        let dictionary = load(aff, &["cat/1", "walk/2"]);
+        assert!(dictionary.check("cats"));
+        assert!(dictionary.check("walks"));
+        assert!(dictionary.check("walked"));
+        assert!(!dictionary.check("catted"));
+    }
+
+    #[test]
+    fn cross_products_need_both_affixes_to_allow_them() {
+        let aff = "PFX U Y 1\nPFX U 0 un .\nPFX R N 1\nPFX R 0 re .\nSFX D Y 1\nSFX D 0 ed .\n";
+        let dictionary = load(aff, &["lock/UD", "write/RD"]);
+        assert!(dictionary.check("unlock"));
+        assert!(dictionary.check("unlocked"));
+        assert!(dictionary.check("rewrite"));
+        assert!(!dictionary.check("rewriteed"));
+    }
+
+    #[test]
+    fn two_level_suffixes() {
+        let aff = "SFX A Y 1\nSFX A 0 able/B .\nSFX B Y 1\nSFX B 0 s .\n";
+        let dictionary = load(aff, &["read/A"]);
+        assert!(dictionary.check("readable"));
+        assert!(dictionary.check("readables"));
+        assert!(!dictionary.check("reads"));
+    }
+
+    #[test]
+    fn compounds() {
+        let aff = "COMPOUNDFLAG X\nCOMPOUNDMIN 3\n";
+        let dictionary = load(aff, &["foot/X", "ball/X", "net"]);
+        assert!(dictionary.check("football"));
+        assert!(dictionary.check("footballfoot"));
+        assert!(!dictionary.check("footnet"));
+
+        let aff = "COMPOUNDBEGIN B\nCOMPOUNDEND E\n";
+        let dictionary = load(aff, &["sun/B", "set/E"]);
+        assert!(dictionary.check("sunset"));
+        assert!(!dictionary.check("setsun"));
+    }
+
+    #[test]
+    fn keep_case() {
+        let aff = "KEEPCASE K\n";
+        let dictionary = load(aff, &["ok/K", "fine"]);
+        assert!(dictionary.check("ok"));
+        assert!(!dictionary.check("Ok"));
+        assert!(!dictionary.check("OK"));
+        assert!(dictionary.check("Fine"));
+        assert!(dictionary.check("FINE"));
+    }
+
+    #[test]
+    fn forbidden_words() {
+        let aff = "FORBIDDENWORD !\nSFX S Y 1\nSFX S 0 s .\n";
+        let dictionary = load(aff, &["sheep/S", "sheeps/!"]);
+        assert!(dictionary.check("sheep"));
+        assert!(!dictionary.check("sheeps"));
+        assert!(!dictionary.check("Sheeps"));
+        assert!(
+            !dictionary
+                .suggest("sheepz")
+                .contains(&String::from("sheeps"))
+        );
+    }
+
+    #[test]
+    fn latin_encodings() {
+        let dictionary =
+            Dictionary::from_bytes(b"SET ISO8859-1\n", b"1\ncaf\xe9\n").expect("latin-1");
+        assert!(dictionary.check("café"));
+
+        let dictionary =
+            Dictionary::from_bytes(b"SET ISO8859-15\n", b"1\n\xbduvre\n").expect("latin-9");
+        assert!(dictionary.check("œuvre"));
+
+        assert!(Dictionary::from_bytes(b"SET KOI8-R\n", b"1\nword\n").is_err());
+    }
+
+    #[test]
+    fn suggestions_come_in_edit_order_with_the_typed_capitals() {
+        let aff = "TRY esianrtolcdugmphbyfvkwz\nREP 1\nREP f ph\n";
+        let dictionary = load(aff, &["the", "ten", "phone", "elephant"]);
+        // A swap comes before a wrong letter.
+        assert_eq!(dictionary.suggest("Teh"), ["The", "Ten"]);
+        // Replacements come first of all.
+        assert_eq!(dictionary.suggest("fone")[0], "phone");
+        // Too far off for one edit, but close by pairs of letters.
+        assert_eq!(dictionary.suggest("elefint"), Vec::<String>::new());
+        assert_eq!(dictionary.suggest("elefant"), ["elephant"]);
+    }
+}
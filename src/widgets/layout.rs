@@ -0,0 +1,219 @@
+// layout.rs
+use std::ops::Range;
+use std::sync::Arc;
+
+use super::metrics;
+use super::notes::{self, Numbering};
+use super::textarea::Line;
+
+// The height of a visual line relative to the largest font size on it.
+pub const LINE_SPACING: f32 = 1.2;
+
+// Everything that decides where lines wrap. Changing it invalidates every layout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutParams {
+    pub max_width: f32,
+    // The size paragraph spacing and indentation are measured in.
+    pub base_size: f32,
+    // Note anchors are as wide as their numbers.
+    pub notes: Arc<Numbering>,
+}
+
+impl Default for LayoutParams {
+    fn default() -> Self {
+        Self {
+            max_width: 1000.0,
+            base_size: 16.0,
+            notes: Arc::default(),
+        }
+    }
+}
+
+// Where a line wraps and how much room it takes. `breaks` holds the start of every
+// visual line, so the first entry is always 0. `offsets` holds the x position of
+// every character boundary from the start of the line, measured with the fonts and
+// sizes of the line. `tops` holds the top of every visual line relative to the top
+// of the paragraph, followed by the bottom of the last one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineLayout {
+    breaks: Vec<usize>,
+    offsets: Vec<f32>,
+    tops: Vec<f32>,
+    height: f32,
+    indent: f32,
+}
+
+impl LineLayout {
+    pub fn compute(line: &Line, params: &LayoutParams) -> Self {
+        let paragraph = line.paragraph();
+        let format = paragraph.format();
+        let content = &line.content;
+
+        let mut offsets = Vec::with_capacity(content.len() + 1);
+        let mut x = 0.0;
+        offsets.push(x);
+        let mut pos = 0;
+        for span in line.spans() {
+            let style = paragraph.apply(span.style());
+            for c in &content[pos..pos + span.len] {
+                x += match notes::anchor_id(*c) {
+                    Some(id) => params
+                        .notes
+                        .label(id)
+                        .chars()
+                        .map(|c| {
+                            metrics::advance(c, style.font, style.size * notes::SUPERSCRIPT_SCALE)
+                        })
+                        .sum(),
+                    None => metrics::advance(*c, style.font, style.size),
+                };
+                offsets.push(x);
+            }
+            pos += span.len;
+        }
+
+        let indent = format.indent_left * params.base_size;
+        let max_width = params.max_width - indent - format.indent_right * params.base_size;
+        let mut breaks = vec![0];
+        let mut start = 0;
+        while start < content.len() {
+            let wrap_pos = find_wrap_position(content, &offsets, start, max_width);
+            if wrap_pos >= content.len() {
+                break;
+            }
+            breaks.push(wrap_pos);
+            start = wrap_pos;
+        }
+
+        // Every visual line is as tall as the largest text on it, but never shorter
+        // than the paragraph's own size.
+        let minimum = params.base_size * format.scale;
+        let mut y = format.space_before * params.base_size;
+        let mut tops = Vec::with_capacity(breaks.len() + 1);
+        tops.push(y);
+        for (index, start) in breaks.iter().enumerate() {
+            let end = breaks.get(index + 1).copied().unwrap_or(content.len());
+            let largest = line
+                .runs(*start..end)
+                .into_iter()
+                .map(|(_, style)| paragraph.apply(style).size)
+                .fold(minimum, f32::max);
+            y += largest * LINE_SPACING;
+            tops.push(y);
+        }
+        let height = y + format.space_after * params.base_size;
+
+        Self {
+            breaks,
+            offsets,
+            tops,
+            height,
+            indent,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn visual_lines(&self) -> usize {
+        self.breaks.len()
+    }
+
+    // The total height of the paragraph, including the space around it.
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    // The top of a visual line relative to the top of the paragraph.
+    pub fn top(&self, visual_line: usize) -> f32 {
+        self.tops[visual_line.min(self.visual_lines() - 1)]
+    }
+
+    pub fn line_height(&self, visual_line: usize) -> f32 {
+        let visual_line = visual_line.min(self.visual_lines() - 1);
+        self.tops[visual_line + 1] - self.tops[visual_line]
+    }
+
+    // The visual line at a height within the paragraph.
+    pub fn visual_line_at(&self, y: f32) -> usize {
+        self.tops[1..self.visual_lines()]
+            .iter()
+            .take_while(|bottom| **bottom <= y)
+            .count()
+    }
+
+    // The characters shown on one visual line.
+    pub fn segment(&self, visual_line: usize) -> Range<usize> {
+        let start = self.breaks.get(visual_line).copied().unwrap_or(self.len());
+        let end = self
+            .breaks
+            .get(visual_line + 1)
+            .copied()
+            .unwrap_or(self.len());
+        start..end
+    }
+
+    pub fn is_last_segment(&self, visual_line: usize) -> bool {
+        visual_line + 1 >= self.breaks.len()
+    }
+
+    // The last position the cursor can take on a visual line. A wrap point belongs to
+    // the next visual line, so earlier lines end in front of it.
+    pub fn segment_end(&self, visual_line: usize) -> usize {
+        let range = self.segment(visual_line);
+        if self.is_last_segment(visual_line) {
+            range.end
+        } else {
+            range.end - 1
+        }
+    }
+
+    // The visual line of a position. A position right at a wrap point starts the
+    // next visual line.
+    pub fn visual_line(&self, hpos: usize) -> usize {
+        self.breaks[1..]
+            .iter()
+            .take_while(|wrap_pos| **wrap_pos <= hpos)
+            .count()
+    }
+
+    // The x position of a character boundary, relative to the left edge of the text
+    // area. This includes the paragraph's indentation.
+    pub fn x(&self, visual_line: usize, hpos: usize) -> f32 {
+        let start = self.segment(visual_line).start;
+        self.indent + self.offsets[hpos.min(self.len())] - self.offsets[start]
+    }
+
+    // The character boundary on a visual line that is closest to an x position.
+    pub fn hit(&self, visual_line: usize, x: f32) -> usize {
+        let range = self.segment(visual_line);
+        let origin = self.offsets[range.start] - self.indent;
+        (range.start..range.end)
+            .find(|i| origin + x < (self.offsets[*i] + self.offsets[*i + 1]) / 2.0)
+            .unwrap_or(range.end)
+            .min(self.segment_end(visual_line))
+    }
+}
+
+// Fit as many characters as the width allows, preferring to break after whitespace.
+// At least one character always fits.
+fn find_wrap_position(content: &[char], offsets: &[f32], start: usize, max_width: f32) -> usize {
+    let mut end = start + 1;
+    while end < content.len() && offsets[end + 1] - offsets[start] <= max_width {
+        end += 1;
+    }
+    if end >= content.len() {
+        return content.len();
+    }
+
+    // Look for a space to break at
+    for i in (start..end).rev() {
+        if content[i].is_whitespace() {
+            return i + 1; // Break after the whitespace
+        }
+    }
+
+    // If no space was found, we have to break in the middle of a word
+    end
+}
@@ -0,0 +1,576 @@
+// lint.rs
+//
+// A rule-based style checker for prose. Every rule looks at the words of a paragraph
+// on its own and reports ranges within it. Checking a whole document takes a while, so
+// it works on a copy of the lines and remembers a fingerprint of each one; findings
+// are only shown on lines that have not changed since.
+use std::collections::BTreeSet;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+
+use iced::Color;
+
+use super::prose::{Word, sentences, words};
+use super::textarea::Line;
+
+// Excerpts of findings are cut off after this many characters.
+const EXCERPT_LENGTH: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Rule {
+    Passive,
+    Weasel,
+    Repeated,
+    Adverb,
+    LongSentence,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 5] = [
+        Rule::Passive,
+        Rule::Weasel,
+        Rule::Repeated,
+        Rule::Adverb,
+        Rule::LongSentence,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::Passive => "Passive voice",
+            Rule::Weasel => "Weasel words",
+            Rule::Repeated => "Repeated words",
+            Rule::Adverb => "Adverbs",
+            Rule::LongSentence => "Long sentences",
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            Rule::Passive => Color::from_rgb(0.3, 0.6, 1.0),
+            Rule::Weasel => Color::from_rgb(1.0, 0.65, 0.2),
+            Rule::Repeated => Color::from_rgb(0.9, 0.3, 0.8),
+            Rule::Adverb => Color::from_rgb(0.3, 0.8, 0.5),
+            Rule::LongSentence => Color::from_rgb(0.95, 0.85, 0.3),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintOptions {
+    pub enabled: BTreeSet<Rule>,
+    // Sentences with more words than this are long.
+    pub max_sentence_words: usize,
+}
+
+impl Default for LintOptions {
+    fn default() -> Self {
+        Self {
+            enabled: Rule::ALL.into_iter().collect(),
+            max_sentence_words: 35,
+        }
+    }
+}
+
+impl LintOptions {
+    pub fn is_enabled(&self, rule: Rule) -> bool {
+        self.enabled.contains(&rule)
+    }
+
+    pub fn set_enabled(&mut self, rule: Rule, on: bool) {
+        if on {
+            self.enabled.insert(rule);
+        } else {
+            self.enabled.remove(&rule);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub rule: Rule,
+    pub vpos: usize,
+    pub range: Range<usize>,
+    pub message: String,
+    // The flagged text, shortened when it is long.
+    pub excerpt: String,
+}
+
+// The findings for a copy of the document, in document order.
+#[derive(Debug, Clone, Default)]
+pub struct Lint {
+    findings: Vec<Finding>,
+    fingerprints: Vec<u64>,
+}
+
+impl Lint {
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    pub fn count(&self, rule: Rule) -> usize {
+        self.findings.iter().filter(|f| f.rule == rule).count()
+    }
+
+    // Whether a line still reads as it did when it was checked.
+    pub fn is_current(&self, vpos: usize, line: &Line) -> bool {
+        self.fingerprints.get(vpos) == Some(&fingerprint(&line.content))
+    }
+
+    pub fn on_line(&self, vpos: usize) -> &[Finding] {
+        let start = self.findings.partition_point(|f| f.vpos < vpos);
+        let end = self.findings.partition_point(|f| f.vpos <= vpos);
+        &self.findings[start..end]
+    }
+}
+
+fn excerpt(text: &[char]) -> String {
+    match text.len() > EXCERPT_LENGTH {
+        true => text[..EXCERPT_LENGTH].iter().chain(['…'].iter()).collect(),
+        false => text.iter().collect(),
+    }
+}
+
+fn fingerprint(content: &[char]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Check every paragraph of a document. Headings are not prose and are skipped.
+pub fn check(lines: &[Line], options: &LintOptions) -> Lint {
+    let mut findings = Vec::new();
+    for (vpos, line) in lines.iter().enumerate() {
+        if line.paragraph().is_heading() {
+            continue;
+        }
+        let mut found = check_paragraph(&line.content, options);
+        found.sort_by_key(|(_, range, _)| (range.start, range.end));
+        findings.extend(found.into_iter().map(|(rule, range, message)| Finding {
+            rule,
+            vpos,
+            excerpt: excerpt(&line.content[range.clone()]),
+            range,
+            message,
+        }));
+    }
+    Lint {
+        findings,
+        fingerprints: lines
+            .iter()
+            .map(|line| fingerprint(&line.content))
+            .collect(),
+    }
+}
+
+const BE_VERBS: [&str; 12] = [
+    "am", "is", "are", "was", "were", "be", "been", "being", "get", "gets", "got", "gotten",
+];
+
+const IRREGULAR_PARTICIPLES: [&str; 60] = [
+    "arisen",
+    "awoken",
+    "beaten",
+    "become",
+    "begun",
+    "bent",
+    "bitten",
+    "blown",
+    "bought",
+    "brought",
+    "built",
+    "caught",
+    "chosen",
+    "cut",
+    "dealt",
+    "done",
+    "drawn",
+    "driven",
+    "eaten",
+    "fallen",
+    "felt",
+    "fought",
+    "found",
+    "forgotten",
+    "forgiven",
+    "frozen",
+    "given",
+    "gone",
+    "grown",
+    "held",
+    "hidden",
+    "hit",
+    "hurt",
+    "kept",
+    "known",
+    "laid",
+    "led",
+    "left",
+    "lent",
+    "lost",
+    "made",
+    "meant",
+    "met",
+    "paid",
+    "put",
+    "read",
+    "said",
+    "seen",
+    "sent",
+    "set",
+    "shown",
+    "shut",
+    "sold",
+    "spent",
+    "spoken",
+    "stolen",
+    "taken",
+    "taught",
+    "thought",
+    "written",
+];
+
+const WEASEL_WORDS: [&str; 33] = [
+    "very",
+    "extremely",
+    "exceedingly",
+    "fairly",
+    "quite",
+    "rather",
+    "somewhat",
+    "really",
+    "various",
+    "several",
+    "many",
+    "numerous",
+    "arguably",
+    "clearly",
+    "obviously",
+    "remarkably",
+    "significantly",
+    "substantially",
+    "relatively",
+    "virtually",
+    "basically",
+    "essentially",
+    "mostly",
+    "largely",
+    "huge",
+    "vast",
+    "tiny",
+    "surprisingly",
+    "interestingly",
+    "completely",
+    "totally",
+    "literally",
+    "undoubtedly",
+];
+
+const WEASEL_PHRASES: [&[&str]; 10] = [
+    &["it", "is", "believed"],
+    &["it", "is", "thought"],
+    &["it", "is", "said"],
+    &["it", "has", "been", "suggested"],
+    &["it", "is", "widely", "accepted"],
+    &["some", "people", "say"],
+    &["many", "people", "believe"],
+    &["studies", "show"],
+    &["research", "shows"],
+    &["experts", "agree"],
+];
+
+// Words ending in -ly that are not adverbs.
+const NOT_ADVERBS: [&str; 40] = [
+    "only",
+    "family",
+    "reply",
+    "apply",
+    "supply",
+    "imply",
+    "comply",
+    "multiply",
+    "rely",
+    "ally",
+    "early",
+    "july",
+    "italy",
+    "holy",
+    "ugly",
+    "belly",
+    "jelly",
+    "fly",
+    "butterfly",
+    "anomaly",
+    "assembly",
+    "monopoly",
+    "daily",
+    "weekly",
+    "monthly",
+    "yearly",
+    "friendly",
+    "lonely",
+    "lovely",
+    "silly",
+    "likely",
+    "unlikely",
+    "costly",
+    "elderly",
+    "curly",
+    "rally",
+    "tally",
+    "folly",
+    "lily",
+    "orderly",
+];
+
+// Whether only whitespace separates two words, so they belong to one phrase.
+fn adjacent(content: &[char], first: &Word, second: &Word) -> bool {
+    content[first.range.end..second.range.start]
+        .iter()
+        .all(|c| c.is_whitespace())
+}
+
+fn check_paragraph(content: &[char], options: &LintOptions) -> Vec<(Rule, Range<usize>, String)> {
+    let words = words(content);
+    let mut found = Vec::new();
+
+    if options.is_enabled(Rule::Passive) {
+        for (i, word) in words.iter().enumerate() {
+            if !BE_VERBS.contains(&word.lower.as_str()) {
+                continue;
+            }
+            // An adverb may sit between the verb and the participle: "was quickly
+            // written".
+            let mut next = i + 1;
+            if words
+                .get(next)
+                .is_some_and(|w| w.lower.ends_with("ly") || w.lower == "not")
+            {
+                next += 1;
+            }
+            let Some(participle) = words.get(next) else {
+                continue;
+            };
+            let is_participle = (participle.lower.ends_with("ed") && participle.lower.len() > 3)
+                || IRREGULAR_PARTICIPLES.contains(&participle.lower.as_str());
+            if is_participle
+                && words[i..=next]
+                    .windows(2)
+                    .all(|w| adjacent(content, &w[0], &w[1]))
+            {
+                found.push((
+                    Rule::Passive,
+                    word.range.start..participle.range.end,
+                    String::from("Passive voice"),
+                ));
+            }
+        }
+    }
+
+    if options.is_enabled(Rule::Weasel) {
+        for (i, word) in words.iter().enumerate() {
+            if WEASEL_WORDS.contains(&word.lower.as_str()) {
+                found.push((
+                    Rule::Weasel,
+                    word.range.clone(),
+                    String::from("Weasel word"),
+                ));
+            }
+            for phrase in WEASEL_PHRASES {
+                let Some(candidate) = words.get(i..i + phrase.len()) else {
+                    continue;
+                };
+                if candidate
+                    .iter()
+                    .zip(phrase.iter())
+                    .all(|(w, p)| w.lower == *p)
+                    && candidate
+                        .windows(2)
+                        .all(|w| adjacent(content, &w[0], &w[1]))
+                {
+                    found.push((
+                        Rule::Weasel,
+                        word.range.start..candidate[phrase.len() - 1].range.end,
+                        String::from("Vague attribution"),
+                    ));
+                }
+            }
+        }
+    }
+
+    if options.is_enabled(Rule::Repeated) {
+        for pair in words.windows(2) {
+            let [first, second] = pair else {
+                continue;
+            };
+            if first.lower == second.lower
+                && first.lower.chars().any(char::is_alphabetic)
+                && adjacent(content, first, second)
+            {
+                found.push((
+                    Rule::Repeated,
+                    first.range.start..second.range.end,
+                    format!("Repeated word “{}”", first.lower),
+                ));
+            }
+        }
+    }
+
+    if options.is_enabled(Rule::Adverb) {
+        for word in &words {
+            let lower = word.lower.as_str();
+            // Weasel words that are adverbs are reported once, as weasel words.
+            let weasel = options.is_enabled(Rule::Weasel) && WEASEL_WORDS.contains(&lower);
+            if lower.ends_with("ly")
+                && lower.chars().count() > 4
+                && !NOT_ADVERBS.contains(&lower)
+                && !weasel
+            {
+                found.push((Rule::Adverb, word.range.clone(), String::from("Adverb")));
+            }
+        }
+    }
+
+    if options.is_enabled(Rule::LongSentence) {
+        for sentence in sentences(content, &words) {
+            let count = words
+                .iter()
+                .filter(|w| sentence.start <= w.range.start && w.range.end <= sentence.end)
+                .count();
+            if count > options.max_sentence_words {
+                found.push((
+                    Rule::LongSentence,
+                    sentence,
+                    format!("Long sentence ({} words)", count),
+                ));
+            }
+        }
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The text of what a single rule flags in a paragraph.
+    fn flagged(rule: Rule, text: &str) -> Vec<String> {
+        let options = LintOptions {
+            enabled: [rule].into_iter().collect(),
+            max_sentence_words: 5,
+        };
+        let content: Vec<char> = text.chars().collect();
+        check_paragraph(&content, &options)
+            .into_iter()
+            .map(|(found, range, _)| {
+                assert_eq!(found, rule);
+                content[range].iter().collect()
+            })
+            .collect()
+    }
+
+    fn assert_flags(rule: Rule, cases: &[(&str, &[&str])]) {
+        for (text, expected) in cases {
+            assert_eq!(flagged(rule, text), *expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn passive_voice() {
+        assert_flags(
+            Rule::Passive,
+            &[
+                ("The letter was written by Ann.", &["was written"]),
+                ("It is quickly forgotten.", &["is quickly forgotten"]),
+                ("They were not invited.", &["were not invited"]),
+                ("The cake got eaten.", &["got eaten"]),
+                ("Ann wrote the letter.", &[]),
+                // Too short to be a participle, and not followed directly.
+                ("It is red. She was, indeed, surprised.", &[]),
+                ("He is a seasoned player.", &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn weasel_words() {
+        assert_flags(
+            Rule::Weasel,
+            &[
+                ("It is very clearly wrong.", &["very", "clearly"]),
+                (
+                    "It is widely accepted that it works.",
+                    &["It is widely accepted"],
+                ),
+                (
+                    "Studies show, experts agree.",
+                    &["Studies show", "experts agree"],
+                ),
+                ("It is, believed by some, true.", &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn repeated_words() {
+        assert_flags(
+            Rule::Repeated,
+            &[
+                ("It was the the best.", &["the the"]),
+                ("That That is odd.", &["That That"]),
+                ("Had had, he said.", &["Had had"]),
+                ("Not the. The end.", &[]),
+                ("In 1999 1999 times.", &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn adverbs() {
+        assert_flags(
+            Rule::Adverb,
+            &[
+                ("She ran quickly and quietly.", &["quickly", "quietly"]),
+                ("My family will only reply early.", &[]),
+                // Weasel words are reported as such only when that rule is on.
+                ("It is really good.", &["really"]),
+                ("Fly slyly.", &["slyly"]),
+            ],
+        );
+    }
+
+    #[test]
+    fn long_sentences() {
+        assert_flags(
+            Rule::LongSentence,
+            &[
+                (
+                    "One two three. Four five six seven eight nine.",
+                    &["Four five six seven eight nine."],
+                ),
+                (
+                    "Dr. Smith et al. wrote one two.",
+                    &["Dr. Smith et al. wrote one two."],
+                ),
+                ("Short one. Short two!", &[]),
+            ],
+        );
+    }
+
+    #[test]
+    fn headings_are_not_checked() {
+        let mut heading = Line::new();
+        heading.set_paragraph(crate::widgets::paragraph::ParagraphStyle::Heading1);
+        heading.push_run("It was written".chars(), iced::Font::DEFAULT, 16.0);
+        let mut body = heading.clone();
+        body.set_paragraph(crate::widgets::paragraph::ParagraphStyle::Body);
+
+        let lint = check(&[heading.clone(), body.clone()], &LintOptions::default());
+        assert!(lint.on_line(0).is_empty());
+        assert_eq!(lint.count(Rule::Passive), 1);
+        assert!(lint.is_current(1, &body));
+        body.push_run(".".chars(), iced::Font::DEFAULT, 16.0);
+        assert!(!lint.is_current(1, &body));
+    }
+}
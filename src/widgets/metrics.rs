@@ -0,0 +1,46 @@
+// metrics.rs
+//
+// Glyph measurement. The advance of every character is measured once per font with
+// the renderer's own text layout at a reference size and scaled to the size in use,
+// since advances grow linearly with the font size.
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use iced::advanced::text::{self, Paragraph as _};
+use iced::alignment::{Horizontal, Vertical};
+use iced::{Font, Pixels, Size};
+
+type Paragraph = <iced::Renderer as text::Renderer>::Paragraph;
+
+const REFERENCE_SIZE: f32 = 64.0;
+
+thread_local! {
+    static ADVANCES: RefCell<HashMap<(Font, char), f32>> = RefCell::new(HashMap::new());
+}
+
+// How far the pen moves after drawing a character.
+pub fn advance(c: char, font: Font, size: f32) -> f32 {
+    let reference = ADVANCES.with(|advances| {
+        *advances
+            .borrow_mut()
+            .entry((font, c))
+            .or_insert_with(|| measure(c, font))
+    });
+    reference * size / REFERENCE_SIZE
+}
+
+fn measure(c: char, font: Font) -> f32 {
+    let mut buffer = [0; 4];
+    let paragraph = Paragraph::with_text(text::Text {
+        content: c.encode_utf8(&mut buffer),
+        bounds: Size::INFINITY,
+        size: Pixels(REFERENCE_SIZE),
+        line_height: text::LineHeight::Relative(1.0),
+        font,
+        horizontal_alignment: Horizontal::Left,
+        vertical_alignment: Vertical::Top,
+        shaping: text::Shaping::Basic,
+        wrapping: text::Wrapping::None,
+    });
+    paragraph.min_bounds().width
+}
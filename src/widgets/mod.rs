@@ -1 +1,14 @@
+pub mod history;
+pub mod hunspell;
+pub mod layout;
+pub mod lint;
+pub mod metrics;
+pub mod notes;
+pub mod outline;
+pub mod paragraph;
+pub mod prose;
+pub mod readability;
+pub mod rope;
+pub mod search;
+pub mod spelling;
 pub mod textarea;
@@ -0,0 +1,209 @@
+// notes.rs
+//
+// Footnotes and endnotes. A note is anchored in the text by a single character from
+// a private use plane that encodes the id of the note, so anchors move, copy and undo
+// together with the text around them. The bodies live in a separate store, and the
+// numbers follow from the order of the anchors in the document.
+use std::collections::{BTreeMap, HashMap};
+
+use super::textarea::Line;
+
+const ANCHOR_BASE: u32 = 0xF0000;
+const ANCHOR_LAST: u32 = 0xFFFFD;
+
+// Note numbers are drawn this much smaller than the text around them.
+pub const SUPERSCRIPT_SCALE: f32 = 0.6;
+// How far the baseline of a note number is raised, relative to the text size.
+pub const SUPERSCRIPT_RISE: f32 = 0.35;
+
+pub fn anchor(id: u32) -> char {
+    char::from_u32(ANCHOR_BASE + id).expect("note ids stay within the private use plane")
+}
+
+pub fn anchor_id(c: char) -> Option<u32> {
+    let code = c as u32;
+    (ANCHOR_BASE..=ANCHOR_LAST)
+        .contains(&code)
+        .then(|| code - ANCHOR_BASE)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum NoteKind {
+    #[default]
+    Footnote,
+    Endnote,
+}
+
+impl NoteKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            NoteKind::Footnote => "Footnotes",
+            NoteKind::Endnote => "Endnotes",
+        }
+    }
+
+    // The identifier used in saved documents.
+    pub fn key(self) -> &'static str {
+        match self {
+            NoteKind::Footnote => "footnote",
+            NoteKind::Endnote => "endnote",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        [NoteKind::Footnote, NoteKind::Endnote]
+            .into_iter()
+            .find(|kind| kind.key() == key)
+    }
+
+    // Footnotes count with arabic numerals and endnotes with roman ones, so the two
+    // never share a label.
+    fn label(self, number: usize) -> String {
+        match self {
+            NoteKind::Footnote => number.to_string(),
+            NoteKind::Endnote => roman(number),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Note {
+    pub kind: NoteKind,
+    pub body: String,
+}
+
+// The bodies of all notes by id. Bodies whose anchor was deleted are kept, so undoing
+// the deletion brings the note back; they are dropped when the document is saved.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Notes {
+    notes: BTreeMap<u32, Note>,
+}
+
+impl Notes {
+    // Create an empty note, or nothing once every id is taken.
+    pub fn add(&mut self, kind: NoteKind) -> Option<u32> {
+        let id = self.notes.last_key_value().map_or(0, |(id, _)| id + 1);
+        if id > ANCHOR_LAST - ANCHOR_BASE {
+            return None;
+        }
+        self.insert(
+            id,
+            Note {
+                kind,
+                body: String::new(),
+            },
+        );
+        Some(id)
+    }
+
+    pub fn insert(&mut self, id: u32, note: Note) {
+        self.notes.insert(id, note);
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Note> {
+        self.notes.get(&id)
+    }
+
+    pub fn kind(&self, id: u32) -> NoteKind {
+        self.get(id).map(|note| note.kind).unwrap_or_default()
+    }
+
+    pub fn set_body(&mut self, id: u32, body: String) {
+        self.notes.entry(id).or_default().body = body;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &Note)> {
+        self.notes.iter().map(|(id, note)| (*id, note))
+    }
+
+    // Turn every note of one kind into the other, returning how many changed.
+    pub fn convert(&mut self, from: NoteKind, to: NoteKind) -> usize {
+        let mut count = 0;
+        for note in self.notes.values_mut().filter(|note| note.kind == from) {
+            note.kind = to;
+            count += 1;
+        }
+        count
+    }
+
+    // Only the notes that are still anchored somewhere in the text.
+    pub fn anchored(&self, numbering: &Numbering) -> Notes {
+        Notes {
+            notes: numbering
+                .order()
+                .iter()
+                .map(|id| (*id, self.get(*id).cloned().unwrap_or_default()))
+                .collect(),
+        }
+    }
+}
+
+// The label of every anchored note, in the order the anchors appear.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Numbering {
+    order: Vec<u32>,
+    labels: HashMap<u32, String>,
+}
+
+impl Numbering {
+    // Number the notes in document order. Footnotes and endnotes count separately,
+    // and an anchor that was copied elsewhere keeps the number of its first occurrence.
+    pub fn of<'a>(lines: impl IntoIterator<Item = &'a Line>, notes: &Notes) -> Self {
+        let mut numbering = Self::default();
+        let mut counts: HashMap<NoteKind, usize> = HashMap::new();
+        for id in lines
+            .into_iter()
+            .flat_map(|line| line.content.iter().filter_map(|c| anchor_id(*c)))
+        {
+            if numbering.labels.contains_key(&id) {
+                continue;
+            }
+            let kind = notes.kind(id);
+            let count = counts.entry(kind).or_default();
+            *count += 1;
+            numbering.order.push(id);
+            numbering.labels.insert(id, kind.label(*count));
+        }
+        numbering
+    }
+
+    pub fn order(&self) -> &[u32] {
+        &self.order
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    // The label shown for an anchor. Anchors are only unnumbered for a moment while
+    // an edit is in progress.
+    pub fn label(&self, id: u32) -> &str {
+        self.labels.get(&id).map_or("?", String::as_str)
+    }
+}
+
+fn roman(mut number: usize) -> String {
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+    let mut text = String::new();
+    for (value, numeral) in NUMERALS {
+        while number >= value {
+            text.push_str(numeral);
+            number -= value;
+        }
+    }
+    text
+}
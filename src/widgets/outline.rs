@@ -0,0 +1,67 @@
+// outline.rs
+//
+// The document outline. Headings are kept as a flat list in document order; the tree
+// follows from their levels, as every heading owns the entries after it up to the
+// next heading of the same or a higher level.
+use super::notes;
+use super::textarea::Line;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heading {
+    pub vpos: usize,
+    pub level: usize,
+    pub title: String,
+}
+
+pub fn headings<'a>(lines: impl IntoIterator<Item = &'a Line>) -> Vec<Heading> {
+    lines
+        .into_iter()
+        .enumerate()
+        .filter_map(|(vpos, line)| {
+            let level = line.paragraph().heading_level()?;
+            // Note anchors have no glyph outside the text area.
+            let title: String = line
+                .content
+                .iter()
+                .filter(|c| notes::anchor_id(**c).is_none())
+                .collect();
+            Some(Heading {
+                vpos,
+                level,
+                title: title.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+// The index of the heading whose section contains a line, which is the last heading
+// at or before it.
+pub fn section_at(headings: &[Heading], vpos: usize) -> Option<usize> {
+    headings
+        .partition_point(|heading| heading.vpos <= vpos)
+        .checked_sub(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::widgets::paragraph::ParagraphStyle;
+
+    #[test]
+    fn titles_leave_out_note_anchors() {
+        let mut heading = Line::new();
+        heading.set_paragraph(ParagraphStyle::Heading2);
+        let text = format!(" Method{} ", notes::anchor(3));
+        heading.push_run(text.chars(), iced::Font::DEFAULT, 16.0);
+        let lines = [Line::new(), heading];
+
+        assert_eq!(
+            headings(&lines),
+            [Heading {
+                vpos: 1,
+                level: 2,
+                title: String::from("Method"),
+            }]
+        );
+    }
+}
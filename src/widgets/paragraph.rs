@@ -0,0 +1,162 @@
+// paragraph.rs
+//
+// Paragraph styles. Every line carries one; it scales the sizes of its characters,
+// may force a weight or slant, and adds spacing and indentation around the text.
+use iced::font::{Style as FontStyle, Weight};
+
+use super::textarea::TextStyle;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParagraphStyle {
+    Title,
+    Heading1,
+    Heading2,
+    Heading3,
+    #[default]
+    Body,
+    BlockQuote,
+    Caption,
+}
+
+// What a paragraph style does. Spacing and indentation are in multiples of the base
+// font size, so they grow with it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParagraphFormat {
+    pub scale: f32,
+    pub bold: bool,
+    pub italic: bool,
+    pub space_before: f32,
+    pub space_after: f32,
+    pub indent_left: f32,
+    pub indent_right: f32,
+}
+
+impl ParagraphStyle {
+    pub const ALL: [ParagraphStyle; 7] = [
+        ParagraphStyle::Title,
+        ParagraphStyle::Heading1,
+        ParagraphStyle::Heading2,
+        ParagraphStyle::Heading3,
+        ParagraphStyle::Body,
+        ParagraphStyle::BlockQuote,
+        ParagraphStyle::Caption,
+    ];
+
+    pub fn format(self) -> ParagraphFormat {
+        let plain = ParagraphFormat {
+            scale: 1.0,
+            bold: false,
+            italic: false,
+            space_before: 0.0,
+            space_after: 0.0,
+            indent_left: 0.0,
+            indent_right: 0.0,
+        };
+        match self {
+            ParagraphStyle::Title => ParagraphFormat {
+                scale: 2.0,
+                bold: true,
+                space_after: 0.8,
+                ..plain
+            },
+            ParagraphStyle::Heading1 => ParagraphFormat {
+                scale: 1.6,
+                bold: true,
+                space_before: 1.0,
+                space_after: 0.4,
+                ..plain
+            },
+            ParagraphStyle::Heading2 => ParagraphFormat {
+                scale: 1.35,
+                bold: true,
+                space_before: 0.8,
+                space_after: 0.3,
+                ..plain
+            },
+            ParagraphStyle::Heading3 => ParagraphFormat {
+                scale: 1.15,
+                bold: true,
+                space_before: 0.6,
+                space_after: 0.2,
+                ..plain
+            },
+            ParagraphStyle::Body => plain,
+            ParagraphStyle::BlockQuote => ParagraphFormat {
+                italic: true,
+                space_before: 0.4,
+                space_after: 0.4,
+                indent_left: 2.5,
+                indent_right: 2.5,
+                ..plain
+            },
+            ParagraphStyle::Caption => ParagraphFormat {
+                scale: 0.85,
+                italic: true,
+                space_before: 0.2,
+                space_after: 0.6,
+                ..plain
+            },
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ParagraphStyle::Title => "Title",
+            ParagraphStyle::Heading1 => "Heading 1",
+            ParagraphStyle::Heading2 => "Heading 2",
+            ParagraphStyle::Heading3 => "Heading 3",
+            ParagraphStyle::Body => "Body",
+            ParagraphStyle::BlockQuote => "Block Quote",
+            ParagraphStyle::Caption => "Caption",
+        }
+    }
+
+    // The identifier used in saved documents.
+    pub fn key(self) -> &'static str {
+        match self {
+            ParagraphStyle::Title => "title",
+            ParagraphStyle::Heading1 => "heading-1",
+            ParagraphStyle::Heading2 => "heading-2",
+            ParagraphStyle::Heading3 => "heading-3",
+            ParagraphStyle::Body => "body",
+            ParagraphStyle::BlockQuote => "block-quote",
+            ParagraphStyle::Caption => "caption",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|style| style.key() == key)
+    }
+
+    pub fn is_heading(self) -> bool {
+        self.heading_level().is_some()
+    }
+
+    // The depth of a heading in the document outline, with the title at the top.
+    pub fn heading_level(self) -> Option<usize> {
+        match self {
+            ParagraphStyle::Title => Some(0),
+            ParagraphStyle::Heading1 => Some(1),
+            ParagraphStyle::Heading2 => Some(2),
+            ParagraphStyle::Heading3 => Some(3),
+            _ => None,
+        }
+    }
+
+    // How a character with the given style looks inside a paragraph of this style.
+    pub fn apply(self, style: TextStyle) -> TextStyle {
+        let format = self.format();
+        let mut font = style.font;
+        if format.bold && font.weight == Weight::Normal {
+            font.weight = Weight::Bold;
+        }
+        if format.italic {
+            font.style = FontStyle::Italic;
+        }
+        TextStyle {
+            font,
+            size: style.size * format.scale,
+            ..style
+        }
+    }
+}
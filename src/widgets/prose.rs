@@ -0,0 +1,171 @@
+// prose.rs
+//
+// Splitting paragraphs into words and sentences, for the checks that read prose.
+use std::ops::Range;
+
+// Abbreviations whose full stop does not end a sentence.
+const ABBREVIATIONS: [&str; 18] = [
+    "etc", "al", "cf", "vs", "dr", "mr", "mrs", "ms", "prof", "fig", "figs", "no", "vol", "pp",
+    "p", "ch", "ed", "eds",
+];
+
+pub struct Word {
+    pub range: Range<usize>,
+    // The word in lowercase, for comparing.
+    pub lower: String,
+}
+
+// Runs of letters and digits, with apostrophes and hyphens inside them.
+pub fn words(content: &[char]) -> Vec<Word> {
+    let joiner = |c: char| matches!(c, '\'' | '’' | '-');
+    let mut words = Vec::new();
+    let mut i = 0;
+    while i < content.len() {
+        if !content[i].is_alphanumeric() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < content.len()
+            && (content[i].is_alphanumeric()
+                || (joiner(content[i]) && content.get(i + 1).is_some_and(|c| c.is_alphanumeric())))
+        {
+            i += 1;
+        }
+        words.push(Word {
+            range: start..i,
+            lower: content[start..i].iter().collect::<String>().to_lowercase(),
+        });
+    }
+    words
+}
+
+// The ranges of the sentences in a paragraph, without the whitespace between them.
+pub fn sentences(content: &[char], words: &[Word]) -> Vec<Range<usize>> {
+    let mut sentences = Vec::new();
+    let mut start = None;
+    let mut i = 0;
+    while i < content.len() {
+        let c = content[i];
+        if start.is_none() && !c.is_whitespace() {
+            start = Some(i);
+        }
+        let ends = matches!(c, '.' | '!' | '?')
+            && !(c == '.' && abbreviation_before(words, i))
+            && content
+                .get(i + 1)
+                .is_none_or(|next| next.is_whitespace() || matches!(next, '"' | '”' | '’' | ')'));
+        if ends && let Some(from) = start.take() {
+            // Closing quotes and brackets belong to the sentence they end.
+            let mut end = i + 1;
+            while content
+                .get(end)
+                .is_some_and(|c| matches!(c, '"' | '”' | '’' | ')'))
+            {
+                end += 1;
+            }
+            sentences.push(from..end);
+            i = end;
+            continue;
+        }
+        i += 1;
+    }
+    if let Some(from) = start {
+        let end = content.len()
+            - content
+                .iter()
+                .rev()
+                .take_while(|c| c.is_whitespace())
+                .count();
+        if from < end {
+            sentences.push(from..end);
+        }
+    }
+    sentences
+}
+
+// Whether the full stop at a position ends an abbreviation or an initial.
+fn abbreviation_before(words: &[Word], stop: usize) -> bool {
+    let Some(word) = words.iter().rev().find(|w| w.range.end <= stop) else {
+        return false;
+    };
+    if word.range.end != stop {
+        return false;
+    }
+    // Single letters cover initials as well as abbreviations like "e.g.".
+    let initial = word.range.len() == 1 && word.lower.chars().all(char::is_alphabetic);
+    initial || ABBREVIATIONS.contains(&word.lower.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split_words(text: &str) -> Vec<String> {
+        let content: Vec<char> = text.chars().collect();
+        words(&content)
+            .into_iter()
+            .map(|word| content[word.range].iter().collect())
+            .collect()
+    }
+
+    fn split_sentences(text: &str) -> Vec<String> {
+        let content: Vec<char> = text.chars().collect();
+        sentences(&content, &words(&content))
+            .into_iter()
+            .map(|sentence| content[sentence].iter().collect())
+            .collect()
+    }
+
+    #[test]
+    fn words_keep_inner_apostrophes_and_hyphens() {
+        let cases: [(&str, &[&str]); 5] = [
+            ("Don't stop", &["Don't", "stop"]),
+            ("a well-known end-", &["a", "well-known", "end"]),
+            ("'quoted' it’s", &["quoted", "it’s"]),
+            ("yes—no, 3.5", &["yes", "no", "3", "5"]),
+            ("café naïve", &["café", "naïve"]),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(split_words(text), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn words_are_compared_in_lowercase() {
+        let content: Vec<char> = "The THE".chars().collect();
+        let lower: Vec<String> = words(&content).into_iter().map(|w| w.lower).collect();
+        assert_eq!(lower, ["the", "the"]);
+    }
+
+    #[test]
+    fn sentences_end_at_stops_but_not_abbreviations() {
+        let cases: [(&str, &[&str]); 7] = [
+            (
+                "Hello there. How are you? Fine!",
+                &["Hello there.", "How are you?", "Fine!"],
+            ),
+            (
+                "Dr. Smith arrived. He sat.",
+                &["Dr. Smith arrived.", "He sat."],
+            ),
+            (
+                "See p. 4 by J. R. R. Tolkien.",
+                &["See p. 4 by J. R. R. Tolkien."],
+            ),
+            (
+                "Apples, pears etc. are fruit.",
+                &["Apples, pears etc. are fruit."],
+            ),
+            ("Version 2.0 is out.", &["Version 2.0 is out."]),
+            (
+                "He said “Stop.” (Then left.) Done",
+                &["He said “Stop.”", "(Then left.)", "Done"],
+            ),
+            ("  Trailing words  ", &["Trailing words"]),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(split_sentences(text), expected, "{:?}", text);
+        }
+    }
+}
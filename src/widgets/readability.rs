@@ -0,0 +1,185 @@
+// readability.rs
+//
+// Readability statistics. The counts come from the words and sentences of every body
+// paragraph; syllables are estimated from groups of vowels, which is what the
+// readability formulas were designed around anyway.
+use super::prose::{sentences, words};
+use super::textarea::Line;
+
+// Typical silent reading and speaking rates, in words per minute.
+const READING_SPEED: f32 = 238.0;
+const SPEAKING_SPEED: f32 = 150.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Statistics {
+    pub words: usize,
+    pub sentences: usize,
+    pub paragraphs: usize,
+    pub letters: usize,
+    pub syllables: usize,
+    // Words of three syllables or more.
+    pub polysyllables: usize,
+}
+
+impl Statistics {
+    // Count the body paragraphs of some lines. Headings are not prose and are skipped.
+    pub fn of(lines: &[Line]) -> Self {
+        let mut statistics = Self::default();
+        for line in lines.iter().filter(|line| !line.paragraph().is_heading()) {
+            let words = words(&line.content);
+            if words.is_empty() {
+                continue;
+            }
+            statistics.paragraphs += 1;
+            statistics.sentences += sentences(&line.content, &words)
+                .iter()
+                .filter(|sentence| {
+                    words
+                        .iter()
+                        .any(|w| sentence.start <= w.range.start && w.range.end <= sentence.end)
+                })
+                .count();
+            for word in &words {
+                let count = syllables(&word.lower);
+                statistics.words += 1;
+                statistics.letters += word.lower.chars().filter(|c| c.is_alphanumeric()).count();
+                statistics.syllables += count;
+                if count >= 3 {
+                    statistics.polysyllables += 1;
+                }
+            }
+        }
+        statistics
+    }
+
+    fn syllables_per_word(&self) -> Option<f32> {
+        (self.words > 0).then(|| self.syllables as f32 / self.words as f32)
+    }
+
+    // In words.
+    pub fn average_sentence_length(&self) -> Option<f32> {
+        (self.sentences > 0).then(|| self.words as f32 / self.sentences as f32)
+    }
+
+    // In letters.
+    pub fn average_word_length(&self) -> Option<f32> {
+        (self.words > 0).then(|| self.letters as f32 / self.words as f32)
+    }
+
+    // In minutes.
+    pub fn reading_time(&self) -> f32 {
+        self.words as f32 / READING_SPEED
+    }
+
+    pub fn speaking_time(&self) -> f32 {
+        self.words as f32 / SPEAKING_SPEED
+    }
+
+    pub fn flesch_reading_ease(&self) -> Option<f32> {
+        Some(206.835 - 1.015 * self.average_sentence_length()? - 84.6 * self.syllables_per_word()?)
+    }
+
+    pub fn flesch_kincaid_grade(&self) -> Option<f32> {
+        Some(0.39 * self.average_sentence_length()? + 11.8 * self.syllables_per_word()? - 15.59)
+    }
+
+    pub fn gunning_fog(&self) -> Option<f32> {
+        let sentence_length = self.average_sentence_length()?;
+        let complex = self.polysyllables as f32 / self.words as f32;
+        Some(0.4 * (sentence_length + 100.0 * complex))
+    }
+
+    // SMOG was calibrated on samples of 30 sentences, and is scaled to match.
+    pub fn smog(&self) -> Option<f32> {
+        (self.sentences > 0).then(|| {
+            1.043 * (self.polysyllables as f32 * 30.0 / self.sentences as f32).sqrt() + 3.1291
+        })
+    }
+}
+
+// Estimate the syllables of a lowercase word: silent endings like the e in "make" or
+// the ed in "jumped" are dropped, then every group of vowels counts once.
+pub fn syllables(word: &str) -> usize {
+    let mut chars: Vec<char> = word.chars().filter(|c| c.is_alphabetic()).collect();
+    if chars.len() <= 3 {
+        return 1;
+    }
+    let keeps_ending = |c: char| "laeiouy".contains(c);
+    let n = chars.len();
+    if (chars.ends_with(&['e', 's']) && !keeps_ending(chars[n - 3]))
+        || (chars.ends_with(&['e', 'd']) && !"td".contains(chars[n - 3]))
+    {
+        chars.truncate(n - 2);
+    } else if chars.ends_with(&['e']) && !keeps_ending(chars[n - 2]) {
+        chars.truncate(n - 1);
+    }
+    if chars.first() == Some(&'y') {
+        chars.remove(0);
+    }
+
+    let is_vowel = |c: &char| "aeiouy".contains(*c);
+    let groups = chars
+        .iter()
+        .enumerate()
+        .filter(|(i, c)| is_vowel(c) && (*i == 0 || !is_vowel(&chars[i - 1])))
+        .count();
+    groups.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::widgets::paragraph::ParagraphStyle;
+
+    #[test]
+    fn syllables_are_estimated_from_vowel_groups() {
+        let cases = [
+            ("cat", 1),
+            ("make", 1),
+            ("jumped", 1),
+            ("wanted", 2),
+            ("table", 2),
+            ("queue", 1),
+            ("yellow", 2),
+            ("syllable", 3),
+            ("beautiful", 3),
+            ("readability", 5),
+            ("rhythm", 1),
+        ];
+        for (word, expected) in cases {
+            assert_eq!(syllables(word), expected, "{:?}", word);
+        }
+    }
+
+    #[test]
+    fn statistics_count_body_paragraphs() {
+        let line = |text: &str, paragraph| {
+            let mut line = Line::new();
+            line.push_run(text.chars(), iced::Font::DEFAULT, 16.0);
+            line.set_paragraph(paragraph);
+            line
+        };
+        let lines = [
+            line("A heading is skipped", ParagraphStyle::Heading1),
+            line("The cat sat. It was happy.", ParagraphStyle::Body),
+            line("", ParagraphStyle::Body),
+            line("...", ParagraphStyle::Body),
+            line("Wonderful.", ParagraphStyle::BlockQuote),
+        ];
+
+        let statistics = Statistics::of(&lines);
+        assert_eq!(
+            statistics,
+            Statistics {
+                words: 7,
+                sentences: 3,
+                paragraphs: 2,
+                letters: 28,
+                syllables: 10,
+                polysyllables: 1,
+            }
+        );
+        assert_eq!(statistics.average_sentence_length(), Some(7.0 / 3.0));
+        assert_eq!(Statistics::default().flesch_reading_ease(), None);
+    }
+}
@@ -0,0 +1,823 @@
+// rope.rs
+//
+// A B-tree of lines. Every node caches a summary of the lines below it, which keeps
+// indexing, inserting and removing lines O(log n) and makes the document-wide word
+// and character counts available without walking the text. Leaves also keep the wrap
+// layout of every line, so visual line counts and heights are prefix sums over the tree.
+use std::ops::{AddAssign, Index, Range, SubAssign};
+
+use super::layout::{LayoutParams, LineLayout};
+use super::textarea::Line;
+
+// Lines per leaf and children per internal node before a node is split. Below half
+// of that, a node is merged with a sibling.
+const MAX_LEAF: usize = 64;
+const MAX_CHILDREN: usize = 16;
+
+// Heights are summed in fixed point, so that adding and removing lines never drifts.
+const SUBPIXELS: f32 = 64.0;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub lines: usize,
+    pub chars: usize,
+    pub words: usize,
+    pub visual_lines: usize,
+    // In 1/SUBPIXELS of a pixel.
+    pub height: u64,
+}
+
+impl Summary {
+    pub fn of(line: &Line, layout: &LineLayout) -> Self {
+        Self {
+            lines: 1,
+            chars: line.char_count(),
+            words: line.word_count(),
+            visual_lines: layout.visual_lines(),
+            height: height_units(layout),
+        }
+    }
+}
+
+fn height_units(layout: &LineLayout) -> u64 {
+    (layout.height() * SUBPIXELS).round() as u64
+}
+
+impl AddAssign for Summary {
+    fn add_assign(&mut self, other: Self) {
+        self.lines += other.lines;
+        self.chars += other.chars;
+        self.words += other.words;
+        self.visual_lines += other.visual_lines;
+        self.height += other.height;
+    }
+}
+
+impl SubAssign for Summary {
+    fn sub_assign(&mut self, other: Self) {
+        self.lines -= other.lines;
+        self.chars -= other.chars;
+        self.words -= other.words;
+        self.visual_lines -= other.visual_lines;
+        self.height -= other.height;
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Leaf {
+        lines: Vec<Line>,
+        layouts: Vec<LineLayout>,
+        summary: Summary,
+    },
+    Internal {
+        children: Vec<Node>,
+        summary: Summary,
+    },
+}
+
+impl Node {
+    fn leaf(lines: Vec<Line>, layouts: Vec<LineLayout>) -> Self {
+        let mut summary = Summary::default();
+        for (line, layout) in lines.iter().zip(&layouts) {
+            summary += Summary::of(line, layout);
+        }
+        Node::Leaf {
+            lines,
+            layouts,
+            summary,
+        }
+    }
+
+    fn internal(children: Vec<Node>) -> Self {
+        let mut summary = Summary::default();
+        for child in &children {
+            summary += child.summary();
+        }
+        Node::Internal { children, summary }
+    }
+
+    fn summary(&self) -> Summary {
+        match self {
+            Node::Leaf { summary, .. } | Node::Internal { summary, .. } => *summary,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.summary().lines
+    }
+
+    fn is_underfull(&self) -> bool {
+        match self {
+            Node::Leaf { lines, .. } => lines.len() < MAX_LEAF / 2,
+            Node::Internal { children, .. } => children.len() < MAX_CHILDREN / 2,
+        }
+    }
+
+    // Merge an underfull child with a sibling. If the two do not fit in one node,
+    // they are split evenly instead, which leaves both at least half full. All leaves
+    // are at the same depth, so siblings are always of the same kind.
+    fn rebalance(children: &mut Vec<Node>, child: usize) {
+        let left = if child + 1 < children.len() {
+            child
+        } else {
+            child - 1
+        };
+        let right = children.remove(left + 1);
+        let merged = match (&mut children[left], right) {
+            (
+                Node::Leaf { lines, layouts, .. },
+                Node::Leaf {
+                    lines: right_lines,
+                    layouts: right_layouts,
+                    ..
+                },
+            ) => {
+                lines.extend(right_lines);
+                layouts.extend(right_layouts);
+                if lines.len() > MAX_LEAF {
+                    let right = lines.split_off(lines.len() / 2);
+                    let right_layouts = layouts.split_off(layouts.len() / 2);
+                    vec![
+                        Node::leaf(std::mem::take(lines), std::mem::take(layouts)),
+                        Node::leaf(right, right_layouts),
+                    ]
+                } else {
+                    vec![Node::leaf(std::mem::take(lines), std::mem::take(layouts))]
+                }
+            }
+            (
+                Node::Internal { children, .. },
+                Node::Internal {
+                    children: right_children,
+                    ..
+                },
+            ) => {
+                children.extend(right_children);
+                if children.len() > MAX_CHILDREN {
+                    let right = children.split_off(children.len() / 2);
+                    vec![
+                        Node::internal(std::mem::take(children)),
+                        Node::internal(right),
+                    ]
+                } else {
+                    vec![Node::internal(std::mem::take(children))]
+                }
+            }
+            _ => unreachable!("siblings are at the same depth"),
+        };
+        children.splice(left..=left, merged);
+    }
+
+    // Find the child holding a line, returning its index and the line's index within it.
+    fn locate(children: &[Node], mut index: usize) -> (usize, usize) {
+        for (i, child) in children.iter().enumerate() {
+            if index < child.len() || i + 1 == children.len() {
+                return (i, index);
+            }
+            index -= child.len();
+        }
+        (0, index)
+    }
+
+    fn get(&self, index: usize) -> (&Line, &LineLayout) {
+        match self {
+            Node::Leaf { lines, layouts, .. } => (&lines[index], &layouts[index]),
+            Node::Internal { children, .. } => {
+                let (child, index) = Self::locate(children, index);
+                children[child].get(index)
+            }
+        }
+    }
+
+    // The number of visual lines before a line.
+    fn visual_offset(&self, index: usize) -> usize {
+        match self {
+            Node::Leaf { layouts, .. } => layouts[..index].iter().map(|l| l.visual_lines()).sum(),
+            Node::Internal { children, .. } => {
+                let (child, inner) = Self::locate(children, index);
+                let before: usize = children[..child]
+                    .iter()
+                    .map(|c| c.summary().visual_lines)
+                    .sum();
+                before + children[child].visual_offset(inner)
+            }
+        }
+    }
+
+    // The height of all lines before a line.
+    fn height_before(&self, index: usize) -> u64 {
+        match self {
+            Node::Leaf { layouts, .. } => layouts[..index].iter().map(height_units).sum(),
+            Node::Internal { children, .. } => {
+                let (child, inner) = Self::locate(children, index);
+                let before: u64 = children[..child].iter().map(|c| c.summary().height).sum();
+                before + children[child].height_before(inner)
+            }
+        }
+    }
+
+    // Find the line at a height.
+    fn line_at_height(&self, mut height: u64) -> usize {
+        match self {
+            Node::Leaf { layouts, .. } => {
+                for (i, layout) in layouts.iter().enumerate() {
+                    let line_height = height_units(layout);
+                    if height < line_height || i + 1 == layouts.len() {
+                        return i;
+                    }
+                    height -= line_height;
+                }
+                0
+            }
+            Node::Internal { children, .. } => {
+                let mut before = 0;
+                for (i, child) in children.iter().enumerate() {
+                    let child_height = child.summary().height;
+                    if height < child_height || i + 1 == children.len() {
+                        return before + child.line_at_height(height);
+                    }
+                    height -= child_height;
+                    before += child.len();
+                }
+                before
+            }
+        }
+    }
+
+    // Find the line containing a visual line, returning the line and the visual line
+    // within it.
+    fn line_at_visual(&self, mut visual: usize) -> (usize, usize) {
+        match self {
+            Node::Leaf { layouts, .. } => {
+                for (i, layout) in layouts.iter().enumerate() {
+                    if visual < layout.visual_lines() || i + 1 == layouts.len() {
+                        return (i, visual);
+                    }
+                    visual -= layout.visual_lines();
+                }
+                (0, visual)
+            }
+            Node::Internal { children, .. } => {
+                let mut before = 0;
+                for (i, child) in children.iter().enumerate() {
+                    let count = child.summary().visual_lines;
+                    if visual < count || i + 1 == children.len() {
+                        let (line, sub) = child.line_at_visual(visual);
+                        return (before + line, sub);
+                    }
+                    visual -= count;
+                    before += child.len();
+                }
+                (before, visual)
+            }
+        }
+    }
+
+    // Apply a change to one line, lay it out again and return how the summary changed.
+    fn update<R>(
+        &mut self,
+        index: usize,
+        params: &LayoutParams,
+        f: impl FnOnce(&mut Line) -> R,
+    ) -> (Summary, Summary, R) {
+        match self {
+            Node::Leaf {
+                lines,
+                layouts,
+                summary,
+            } => {
+                let line = &mut lines[index];
+                let before = Summary::of(line, &layouts[index]);
+                let result = f(line);
+                layouts[index] = LineLayout::compute(line, params);
+                let after = Summary::of(line, &layouts[index]);
+                *summary -= before;
+                *summary += after;
+                (before, after, result)
+            }
+            Node::Internal { children, summary } => {
+                let (child, index) = Self::locate(children, index);
+                let (before, after, result) = children[child].update(index, params, f);
+                *summary -= before;
+                *summary += after;
+                (before, after, result)
+            }
+        }
+    }
+
+    // Insert a line, returning a new right sibling if the node had to be split.
+    fn insert(
+        &mut self,
+        index: usize,
+        line: Line,
+        layout: LineLayout,
+        added: Summary,
+    ) -> Option<Node> {
+        match self {
+            Node::Leaf {
+                lines,
+                layouts,
+                summary,
+            } => {
+                *summary += added;
+                lines.insert(index, line);
+                layouts.insert(index, layout);
+                if lines.len() > MAX_LEAF {
+                    let right = lines.split_off(lines.len() / 2);
+                    let right_layouts = layouts.split_off(layouts.len() / 2);
+                    *self = Node::leaf(std::mem::take(lines), std::mem::take(layouts));
+                    return Some(Node::leaf(right, right_layouts));
+                }
+                None
+            }
+            Node::Internal { children, summary } => {
+                *summary += added;
+                let (child, index) = Self::locate(children, index);
+                if let Some(split) = children[child].insert(index, line, layout, added) {
+                    children.insert(child + 1, split);
+                    if children.len() > MAX_CHILDREN {
+                        let right = children.split_off(children.len() / 2);
+                        *self = Node::internal(std::mem::take(children));
+                        return Some(Node::internal(right));
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    fn remove(&mut self, index: usize) -> (Line, Summary) {
+        match self {
+            Node::Leaf {
+                lines,
+                layouts,
+                summary,
+            } => {
+                let line = lines.remove(index);
+                let layout = layouts.remove(index);
+                let removed = Summary::of(&line, &layout);
+                *summary -= removed;
+                (line, removed)
+            }
+            Node::Internal { children, summary } => {
+                let (child, index) = Self::locate(children, index);
+                let (line, removed) = children[child].remove(index);
+                *summary -= removed;
+                if children[child].is_underfull() && children.len() > 1 {
+                    Self::rebalance(children, child);
+                }
+                (line, removed)
+            }
+        }
+    }
+
+    // Lay out every line again, e.g. after the available width changed.
+    fn relayout(&mut self, params: &LayoutParams) {
+        match self {
+            Node::Leaf { lines, .. } => {
+                let lines = std::mem::take(lines);
+                let layouts = lines
+                    .iter()
+                    .map(|line| LineLayout::compute(line, params))
+                    .collect();
+                *self = Node::leaf(lines, layouts);
+            }
+            Node::Internal { children, .. } => {
+                for child in children.iter_mut() {
+                    child.relayout(params);
+                }
+                *self = Node::internal(std::mem::take(children));
+            }
+        }
+    }
+
+    fn collect_into(&self, out: &mut Vec<Line>) {
+        match self {
+            Node::Leaf { lines, .. } => out.extend_from_slice(lines),
+            Node::Internal { children, .. } => {
+                for child in children {
+                    child.collect_into(out);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LineRope {
+    root: Node,
+    params: LayoutParams,
+}
+
+impl Default for LineRope {
+    fn default() -> Self {
+        Self::from_lines(vec![Line::new()])
+    }
+}
+
+impl LineRope {
+    pub fn from_lines(lines: Vec<Line>) -> Self {
+        Self::with_params(lines, LayoutParams::default())
+    }
+
+    pub fn with_params(lines: Vec<Line>, params: LayoutParams) -> Self {
+        // Build the tree bottom-up from full leaves.
+        let mut level: Vec<Node> = lines
+            .chunks(MAX_LEAF)
+            .map(|chunk| {
+                let layouts = chunk
+                    .iter()
+                    .map(|line| LineLayout::compute(line, &params))
+                    .collect();
+                Node::leaf(chunk.to_vec(), layouts)
+            })
+            .collect();
+        if level.is_empty() {
+            level.push(Node::leaf(Vec::new(), Vec::new()));
+        }
+        while level.len() > 1 {
+            level = level
+                .chunks(MAX_CHILDREN)
+                .map(|chunk| Node::internal(chunk.to_vec()))
+                .collect();
+        }
+        Self {
+            root: level.remove(0),
+            params,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.root.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn summary(&self) -> Summary {
+        self.root.summary()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Line> {
+        (index < self.len()).then(|| self.root.get(index).0)
+    }
+
+    pub fn params(&self) -> &LayoutParams {
+        &self.params
+    }
+
+    /// Change what lines are wrapped by. Every line is laid out again, but only if
+    /// the parameters actually changed.
+    pub fn set_params(&mut self, params: LayoutParams) {
+        if params != self.params {
+            self.params = params;
+            self.root.relayout(&self.params);
+        }
+    }
+
+    pub fn layout(&self, index: usize) -> &LineLayout {
+        assert!(index < self.len(), "line {} out of bounds", index);
+        self.root.get(index).1
+    }
+
+    pub fn visual_line_count(&self) -> usize {
+        self.summary().visual_lines
+    }
+
+    // The index of the first visual line of a line.
+    pub fn visual_offset(&self, index: usize) -> usize {
+        if index >= self.len() {
+            return self.visual_line_count();
+        }
+        self.root.visual_offset(index)
+    }
+
+    // The line showing a visual line, and which of its visual lines it is. Visual
+    // lines past the end map onto the last one.
+    pub fn line_at_visual(&self, visual: usize) -> (usize, usize) {
+        let last = self.visual_line_count().saturating_sub(1);
+        self.root.line_at_visual(visual.min(last))
+    }
+
+    // The total height of all lines in pixels.
+    pub fn height(&self) -> f32 {
+        self.summary().height as f32 / SUBPIXELS
+    }
+
+    // How far below the top of the document a line starts.
+    pub fn y_offset(&self, index: usize) -> f32 {
+        if index >= self.len() {
+            return self.height();
+        }
+        self.root.height_before(index) as f32 / SUBPIXELS
+    }
+
+    // The line at a distance from the top of the document. Heights past the end map
+    // onto the last line.
+    pub fn line_at_y(&self, y: f32) -> usize {
+        let height = (y.max(0.0) * SUBPIXELS) as u64;
+        self.root.line_at_height(height)
+    }
+
+    /// Change a line in place. The summaries and its layout are updated afterwards.
+    pub fn update<R>(&mut self, index: usize, f: impl FnOnce(&mut Line) -> R) -> R {
+        assert!(index < self.len(), "line {} out of bounds", index);
+        self.root.update(index, &self.params, f).2
+    }
+
+    pub fn insert(&mut self, index: usize, line: Line) {
+        assert!(index <= self.len(), "line {} out of bounds", index);
+        let layout = LineLayout::compute(&line, &self.params);
+        let added = Summary::of(&line, &layout);
+        if let Some(split) = self.root.insert(index, line, layout, added) {
+            let left = std::mem::replace(&mut self.root, Node::leaf(Vec::new(), Vec::new()));
+            self.root = Node::internal(vec![left, split]);
+        }
+    }
+
+    pub fn insert_many(&mut self, index: usize, lines: Vec<Line>) {
+        for (offset, line) in lines.into_iter().enumerate() {
+            self.insert(index + offset, line);
+        }
+    }
+
+    pub fn push(&mut self, line: Line) {
+        self.insert(self.len(), line);
+    }
+
+    pub fn remove(&mut self, index: usize) -> Line {
+        assert!(index < self.len(), "line {} out of bounds", index);
+        let (line, _) = self.root.remove(index);
+        // Collapse the root while it only has a single child.
+        while let Node::Internal { children, .. } = &mut self.root {
+            if children.len() != 1 {
+                break;
+            }
+            self.root = children.remove(0);
+        }
+        line
+    }
+
+    pub fn remove_range(&mut self, range: Range<usize>) -> Vec<Line> {
+        let start = range.start;
+        range.map(|_| self.remove(start)).collect()
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        self.iter_from(0)
+    }
+
+    // Iterate over the lines, starting at the given one.
+    pub fn iter_from(&self, start: usize) -> Iter<'_> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            leaf: [].iter().zip([].iter()),
+        };
+        let mut node = &self.root;
+        let mut index = start.min(self.len());
+        loop {
+            match node {
+                Node::Leaf { lines, layouts, .. } => {
+                    let index = index.min(lines.len());
+                    iter.leaf = lines[index..].iter().zip(layouts[index..].iter());
+                    return iter;
+                }
+                Node::Internal { children, .. } => {
+                    let (child, inner) = Node::locate(children, index);
+                    iter.stack.push(children[child + 1..].iter());
+                    node = &children[child];
+                    index = inner;
+                }
+            }
+        }
+    }
+
+    // Iterate over the lines together with their layouts.
+    pub fn layouts_from(&self, start: usize) -> Layouts<'_> {
+        Layouts(self.iter_from(start))
+    }
+
+    pub fn to_vec(&self) -> Vec<Line> {
+        let mut lines = Vec::with_capacity(self.len());
+        self.root.collect_into(&mut lines);
+        lines
+    }
+}
+
+impl Index<usize> for LineRope {
+    type Output = Line;
+
+    fn index(&self, index: usize) -> &Line {
+        self.get(index)
+            .unwrap_or_else(|| panic!("line {} out of bounds", index))
+    }
+}
+
+type LeafIter<'a> = std::iter::Zip<std::slice::Iter<'a, Line>, std::slice::Iter<'a, LineLayout>>;
+
+pub struct Iter<'a> {
+    // Siblings still to visit on every level above the current leaf.
+    stack: Vec<std::slice::Iter<'a, Node>>,
+    leaf: LeafIter<'a>,
+}
+
+impl<'a> Iter<'a> {
+    fn next_entry(&mut self) -> Option<(&'a Line, &'a LineLayout)> {
+        loop {
+            if let Some(entry) = self.leaf.next() {
+                return Some(entry);
+            }
+            // Climb until a level has siblings left, then descend to its first leaf.
+            let mut node = loop {
+                let level = self.stack.last_mut()?;
+                match level.next() {
+                    Some(node) => break node,
+                    None => {
+                        self.stack.pop();
+                    }
+                }
+            };
+            loop {
+                match node {
+                    Node::Leaf { lines, layouts, .. } => {
+                        self.leaf = lines.iter().zip(layouts.iter());
+                        break;
+                    }
+                    Node::Internal { children, .. } => {
+                        let mut children = children.iter();
+                        let first = children.next();
+                        self.stack.push(children);
+                        match first {
+                            Some(first) => node = first,
+                            None => break,
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Line;
+
+    fn next(&mut self) -> Option<&'a Line> {
+        self.next_entry().map(|(line, _)| line)
+    }
+}
+
+pub struct Layouts<'a>(Iter<'a>);
+
+impl<'a> Iterator for Layouts<'a> {
+    type Item = (&'a Line, &'a LineLayout);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next_entry()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::widgets::paragraph::ParagraphStyle;
+
+    const WORDS: [&str; 8] = ["a", "rope", "of", "lines", "wraps", "when", "long", "."];
+
+    // A line of a few words, some long enough to wrap, in a style that depends on n.
+    fn line(n: usize) -> Line {
+        let mut line = Line::new();
+        let words: Vec<&str> = (0..n % 13).map(|i| WORDS[(n + i) % WORDS.len()]).collect();
+        line.push_run(words.join(" ").chars(), iced::Font::DEFAULT, 16.0);
+        line.set_paragraph(ParagraphStyle::ALL[n % ParagraphStyle::ALL.len()]);
+        line
+    }
+
+    fn params() -> LayoutParams {
+        LayoutParams {
+            max_width: 120.0,
+            ..LayoutParams::default()
+        }
+    }
+
+    // The depth of every leaf, checking each node's summary and that no node below the
+    // root is empty on the way.
+    fn check_node(node: &Node, root: bool) -> usize {
+        assert!(root || node.len() > 0, "empty node below the root");
+        match node {
+            Node::Leaf {
+                lines,
+                layouts,
+                summary,
+            } => {
+                assert_eq!(
+                    *summary,
+                    Node::leaf(lines.clone(), layouts.clone()).summary()
+                );
+                1
+            }
+            Node::Internal { children, summary } => {
+                assert_eq!(*summary, Node::internal(children.clone()).summary());
+                let depths: Vec<usize> = children.iter().map(|c| check_node(c, false)).collect();
+                assert!(depths.windows(2).all(|pair| pair[0] == pair[1]));
+                depths[0] + 1
+            }
+        }
+    }
+
+    // Compare the rope against the lines it should hold, laid out one by one.
+    fn check(rope: &LineRope, model: &[Line]) {
+        check_node(&rope.root, true);
+        let contents: Vec<&Vec<char>> = rope.iter().map(|line| &line.content).collect();
+        let expected: Vec<&Vec<char>> = model.iter().map(|line| &line.content).collect();
+        assert_eq!(contents, expected);
+
+        let mut summary = Summary::default();
+        for (index, line) in model.iter().enumerate() {
+            let layout = LineLayout::compute(line, rope.params());
+            assert_eq!(rope.visual_offset(index), summary.visual_lines);
+            assert_eq!(rope.y_offset(index), summary.height as f32 / SUBPIXELS);
+            for visual in 0..layout.visual_lines() {
+                assert_eq!(
+                    rope.line_at_visual(summary.visual_lines + visual),
+                    (index, visual)
+                );
+            }
+            let middle = (summary.height + height_units(&layout) / 2) as f32 / SUBPIXELS;
+            assert_eq!(rope.line_at_y(middle), index);
+            summary += Summary::of(line, &layout);
+        }
+        assert_eq!(rope.summary(), summary);
+        assert_eq!(rope.visual_offset(model.len()), summary.visual_lines);
+        assert_eq!(rope.y_offset(model.len()), rope.height());
+    }
+
+    #[test]
+    fn edits_match_a_vector_of_lines() {
+        // Enough lines for internal nodes below the root.
+        let mut model: Vec<Line> = (0..MAX_LEAF * MAX_CHILDREN + 100).map(line).collect();
+        let mut rope = LineRope::with_params(model.clone(), params());
+        check(&rope, &model);
+
+        // A simple generator, so that the edits land all over the tree.
+        let mut seed = 7usize;
+        let mut next = |bound: usize| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % bound.max(1)
+        };
+        for step in 0..120 {
+            match step % 6 {
+                0 | 1 => {
+                    let index = next(model.len() + 1);
+                    rope.insert(index, line(step));
+                    model.insert(index, line(step));
+                }
+                2 => {
+                    // Enough lines at once to split leaves and then internal nodes.
+                    let index = next(model.len() + 1);
+                    let lines: Vec<Line> = (0..next(200)).map(|n| line(n + step)).collect();
+                    rope.insert_many(index, lines.clone());
+                    model.splice(index..index, lines);
+                }
+                3 | 4 if !model.is_empty() => {
+                    let index = next(model.len());
+                    assert_eq!(rope.remove(index).content, model.remove(index).content);
+                }
+                _ => {
+                    // Enough lines at once to empty leaves and merge their neighbours.
+                    let start = next(model.len());
+                    let end = start + next(model.len() - start + 1);
+                    let removed: Vec<Vec<char>> =
+                        model.drain(start..end).map(|line| line.content).collect();
+                    let from_rope: Vec<Vec<char>> = rope
+                        .remove_range(start..end)
+                        .into_iter()
+                        .map(|line| line.content)
+                        .collect();
+                    assert_eq!(from_rope, removed);
+                }
+            }
+            check(&rope, &model);
+        }
+    }
+
+    #[test]
+    fn removing_keeps_the_tree_shallow() {
+        let count = MAX_LEAF * MAX_CHILDREN * 3;
+        let mut rope = LineRope::from_lines((0..count).map(line).collect());
+        let mut model = rope.to_vec();
+        // Remove every other line until few are left; without merging, the tree would
+        // keep all of its leaves, each holding a line or two.
+        while model.len() > MAX_LEAF {
+            for index in (0..model.len()).rev().step_by(2) {
+                rope.remove(index);
+                model.remove(index);
+            }
+        }
+        check(&rope, &model);
+        assert_eq!(check_node(&rope.root, true), 1);
+    }
+}
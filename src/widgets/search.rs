@@ -0,0 +1,161 @@
+// search.rs
+//
+// Find and replace. Every query is compiled into a regex, so plain text, whole-word
+// and case-insensitive searches all share one matcher. Matches never span lines.
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+
+use super::textarea::Line;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    pub regex: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Search {
+    regex: Regex,
+    // Regex replacements may refer to capture groups, plain ones are taken literally.
+    expand: bool,
+    whole_word: bool,
+}
+
+impl Search {
+    pub fn new(pattern: &str, options: SearchOptions) -> Result<Self, regex::Error> {
+        let source = if options.regex {
+            pattern.to_string()
+        } else {
+            regex::escape(pattern)
+        };
+        let regex = RegexBuilder::new(&source)
+            .case_insensitive(!options.case_sensitive)
+            .build()?;
+        Ok(Self {
+            regex,
+            expand: options.regex,
+            whole_word: options.whole_word,
+        })
+    }
+
+    // The character ranges of all non-empty matches in a line. A match that is not a
+    // whole word when one is asked for is skipped, and the search goes on from the
+    // next character, since a match overlapping it may still be one.
+    pub fn find_in(&self, line: &Line) -> Vec<Range<usize>> {
+        let text: String = line.content.iter().collect();
+        let boundaries = char_boundaries(&text);
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        while let Some(found) = self.regex.find_at(&text, start) {
+            if !found.is_empty() && (!self.whole_word || is_whole_word(&text, found.range())) {
+                ranges.push(to_char(&boundaries, found.start())..to_char(&boundaries, found.end()));
+                start = found.end();
+            } else {
+                match text[found.start()..].chars().next() {
+                    Some(c) => start = found.start() + c.len_utf8(),
+                    None => break,
+                }
+            }
+        }
+        ranges
+    }
+
+    // The text that replaces a match, with capture groups filled in for regex searches.
+    pub fn replacement(&self, line: &Line, range: Range<usize>, replacement: &str) -> String {
+        if !self.expand {
+            return replacement.to_string();
+        }
+        let text: String = line.content.iter().collect();
+        let boundaries = char_boundaries(&text);
+        let start = boundaries.get(range.start).copied().unwrap_or(text.len());
+        let mut expanded = String::new();
+        match self.regex.captures_at(&text, start) {
+            Some(captures) if captures.get(0).is_some_and(|found| found.start() == start) => {
+                captures.expand(replacement, &mut expanded);
+            }
+            _ => expanded.push_str(replacement),
+        }
+        expanded
+    }
+}
+
+// Whether a match stands on its own: it may not continue a word on either side. An
+// edge of the match that is not part of a word, like the pluses of "C++", needs no
+// boundary, which is where \b falls short.
+fn is_whole_word(text: &str, range: Range<usize>) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let matched = &text[range.clone()];
+    let before = text[..range.start].chars().next_back();
+    let after = text[range.end..].chars().next();
+    let starts = !matched.chars().next().is_some_and(is_word) || !before.is_some_and(is_word);
+    let ends = !matched.chars().next_back().is_some_and(is_word) || !after.is_some_and(is_word);
+    starts && ends
+}
+
+// The byte offset of every character.
+fn char_boundaries(text: &str) -> Vec<usize> {
+    text.char_indices().map(|(offset, _)| offset).collect()
+}
+
+fn to_char(boundaries: &[usize], byte: usize) -> usize {
+    boundaries.partition_point(|offset| *offset < byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The start and end of every match.
+    fn find(pattern: &str, options: SearchOptions, text: &str) -> Vec<(usize, usize)> {
+        let mut line = Line::new();
+        line.push_run(text.chars(), iced::Font::DEFAULT, 16.0);
+        Search::new(pattern, options)
+            .unwrap()
+            .find_in(&line)
+            .into_iter()
+            .map(|range| (range.start, range.end))
+            .collect()
+    }
+
+    const WHOLE_WORD: SearchOptions = SearchOptions {
+        case_sensitive: true,
+        whole_word: true,
+        regex: false,
+    };
+
+    #[test]
+    fn whole_words() {
+        let cases = [
+            ("word", "words word sword", vec![(6, 10)]),
+            ("C++", "C++ and xC++, C++x", vec![(0, 3), (14, 17)]),
+            ("(a)", "(a) or f(a)", vec![(0, 3), (8, 11)]),
+            ("aa", "aaa aa", vec![(4, 6)]),
+            ("é", "café é", vec![(5, 6)]),
+            ("_x", "a_x _x", vec![(4, 6)]),
+        ];
+        for (pattern, text, expected) in cases {
+            assert_eq!(find(pattern, WHOLE_WORD, text), expected, "{:?}", pattern);
+        }
+    }
+
+    #[test]
+    fn whole_word_regexes() {
+        let options = SearchOptions {
+            regex: true,
+            ..WHOLE_WORD
+        };
+        assert_eq!(
+            find(r"C\+\+|C#", options, "C# or C++"),
+            vec![(0, 2), (6, 9)]
+        );
+        assert_eq!(find(r"\d+", options, "12 a34 56"), vec![(0, 2), (7, 9)]);
+    }
+
+    #[test]
+    fn plain_searches_match_inside_words() {
+        let options = SearchOptions::default();
+        assert_eq!(find("c++", options, "xC++y"), vec![(1, 4)]);
+    }
+}
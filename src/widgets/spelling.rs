@@ -0,0 +1,140 @@
+// spelling.rs
+//
+// Spell checking. A speller pairs a Hunspell dictionary with the words the user added
+// to their personal list and the ones they chose to ignore for this session. Lines are
+// split into words here, skipping what is not prose, like numbers, links and citation
+// keys.
+use std::collections::HashSet;
+use std::ops::Range;
+use std::sync::Arc;
+
+use super::hunspell::Dictionary;
+use super::textarea::Line;
+
+// Curly apostrophes are checked as the straight ones dictionaries use.
+const APOSTROPHES: [char; 2] = ['\'', '’'];
+
+// A misspelled word in the document and what it could be instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Misspelling {
+    pub vpos: usize,
+    pub range: Range<usize>,
+    pub word: String,
+    pub suggestions: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Speller {
+    // The name of the dictionary, like "en_US".
+    name: String,
+    dictionary: Arc<Dictionary>,
+    personal: HashSet<String>,
+    ignored: HashSet<String>,
+}
+
+impl Speller {
+    pub fn new(name: String, dictionary: Dictionary, personal: Vec<String>) -> Self {
+        Self {
+            name,
+            dictionary: Arc::new(dictionary),
+            personal: personal.into_iter().collect(),
+            ignored: HashSet::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn check(&self, word: &str) -> bool {
+        let word = word.replace('’', "'");
+        self.ignored.contains(&word)
+            || self.personal.contains(&word)
+            || self.personal.contains(&word.to_lowercase())
+            || self.dictionary.check(&word)
+    }
+
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let curly = word.contains('’');
+        self.dictionary
+            .suggest(&word.replace('’', "'"))
+            .into_iter()
+            .map(|suggestion| match curly {
+                true => suggestion.replace('\'', "’"),
+                false => suggestion,
+            })
+            .collect()
+    }
+
+    // Accept a word from now on, in any document.
+    pub fn add(&mut self, word: &str) {
+        self.personal.insert(word.replace('’', "'"));
+    }
+
+    // Accept a word until the editor is closed.
+    pub fn ignore(&mut self, word: &str) {
+        self.ignored.insert(word.replace('’', "'"));
+    }
+
+    // The character ranges of the misspelled words in a line.
+    pub fn misspelled(&self, line: &Line) -> Vec<Range<usize>> {
+        words(&line.content)
+            .into_iter()
+            .filter(|range| {
+                let word: String = line.content[range.clone()].iter().collect();
+                !self.check(&word)
+            })
+            .collect()
+    }
+}
+
+// The ranges of the words worth checking in a line of text.
+pub fn words(content: &[char]) -> Vec<Range<usize>> {
+    let is_word_char = |c: char| c.is_alphanumeric() || APOSTROPHES.contains(&c);
+    let mut words = Vec::new();
+    let mut chunk_start = 0;
+
+    // Whitespace separates chunks, and a chunk that looks like a link, an address or
+    // a citation is skipped whole.
+    for chunk_end in
+        (0..=content.len()).filter(|i| content.get(*i).is_none_or(|c| c.is_whitespace()))
+    {
+        let chunk = &content[chunk_start..chunk_end];
+        chunk_start = chunk_end + 1;
+        let text: String = chunk.iter().collect();
+        if chunk.is_empty()
+            || text.contains('@')
+            || text.contains("://")
+            || text.starts_with("www.")
+        {
+            continue;
+        }
+
+        let offset = chunk_end - chunk.len();
+        let mut i = 0;
+        while i < chunk.len() {
+            if !is_word_char(chunk[i]) {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < chunk.len() && is_word_char(chunk[i]) {
+                i += 1;
+            }
+            // Quotes around a word are not part of it.
+            let mut word = start..i;
+            while word.start < word.end && APOSTROPHES.contains(&chunk[word.start]) {
+                word.start += 1;
+            }
+            while word.end > word.start && APOSTROPHES.contains(&chunk[word.end - 1]) {
+                word.end -= 1;
+            }
+            let letters = &chunk[word.clone()];
+            if letters.iter().any(|c| c.is_alphabetic()) && !letters.iter().any(|c| c.is_numeric())
+            {
+                words.push(offset + word.start..offset + word.end);
+            }
+        }
+    }
+    words
+}
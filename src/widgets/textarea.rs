@@ -3,6 +3,7 @@ use iced::{
     Color, Event, Font, Point, Rectangle, Size,
     advanced::graphics::geometry::{self, Frame},
     alignment::{Horizontal, Vertical},
+    font::{Style as FontStyle, Weight},
     keyboard::{Event as KeyEvent, Modifiers, key::Named},
     mouse::{Cursor, Event as MouseEvent},
     widget::{
@@ -12,6 +13,37 @@ use iced::{
 };
 use std::cell::RefCell;
 use std::cmp::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::history::{Edit, EditHistory, EditKind, fragment_end};
+use super::layout::{LINE_SPACING, LayoutParams};
+use super::lint::Lint;
+use super::metrics;
+use super::notes::{self, Note, NoteKind, Notes, Numbering};
+use super::outline::{self, Heading};
+use super::paragraph::ParagraphStyle;
+use super::rope::LineRope;
+use super::search::Search;
+use super::spelling::{Misspelling, Speller};
+
+// Two clicks closer together than this count as a double click.
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+
+const SELECTION_COLOR: Color = Color::from_rgba(0.3, 0.5, 1.0, 0.35);
+const MATCH_COLOR: Color = Color::from_rgba(1.0, 0.8, 0.2, 0.3);
+const MISSPELLING_COLOR: Color = Color::from_rgb(0.95, 0.25, 0.25);
+// The squiggle under misspelled words rises and falls this much every half wavelength.
+const SQUIGGLE_HEIGHT: f32 = 1.5;
+const SQUIGGLE_WAVELENGTH: f32 = 4.0;
+// Style findings are underlined this far below the squiggles.
+const LINT_OFFSET: f32 = 2.5;
+
+// Roughly how far most fonts reach above and below the baseline, and where they put
+// underlines, relative to the font size.
+const ASCENT: f32 = 0.8;
+const DESCENT: f32 = 0.3;
+const UNDERLINE_OFFSET: f32 = 0.15;
 
 // === Text Editor Message Types ===
 
@@ -22,103 +54,376 @@ pub enum TextEditorMessage {
     Delete,
     CursorChanged(usize, usize, i32), // (hpos, vpos, scrolldir)
     ContentChanged(usize, usize, i32),
+    // A misspelled word was right-clicked and is now selected.
+    SpellingRequested,
 }
 
 // === Line Struct (Text Storage & Styling) ===
 
+// How a single character looks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    pub font: Font,
+    pub size: f32,
+    pub underline: bool,
+}
+
+// A run of consecutive characters sharing the same font, size and decoration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StyleSpan {
+    pub len: usize,
+    pub font: Font,
+    pub size: f32,
+    pub underline: bool,
+}
+
+impl StyleSpan {
+    pub fn style(&self) -> TextStyle {
+        TextStyle {
+            font: self.font,
+            size: self.size,
+            underline: self.underline,
+        }
+    }
+
+    fn same_style(&self, other: &StyleSpan) -> bool {
+        self.style() == other.style()
+    }
+}
+
+// The character styles that can be switched on and off from the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emphasis {
+    Bold,
+    Italic,
+    Underline,
+}
+
+impl Emphasis {
+    fn is_set(self, style: &TextStyle) -> bool {
+        match self {
+            Emphasis::Bold => matches!(
+                style.font.weight,
+                Weight::Semibold | Weight::Bold | Weight::ExtraBold | Weight::Black
+            ),
+            Emphasis::Italic => style.font.style != FontStyle::Normal,
+            Emphasis::Underline => style.underline,
+        }
+    }
+
+    fn apply(self, style: &mut TextStyle, on: bool) {
+        match self {
+            Emphasis::Bold => {
+                style.font.weight = if on { Weight::Bold } else { Weight::Normal };
+            }
+            Emphasis::Italic => {
+                style.font.style = if on {
+                    FontStyle::Italic
+                } else {
+                    FontStyle::Normal
+                };
+            }
+            Emphasis::Underline => style.underline = on,
+        }
+    }
+}
+
+// The style used for characters that somehow ended up without one.
+const FALLBACK_FONT_SIZE: f32 = 12.0;
+
+// A line stores its characters next to style spans that cover them exactly. Every
+// line is a paragraph with its own paragraph style.
 #[derive(Debug, Default, Clone)]
 pub struct Line {
     pub content: Vec<char>,
-    pub fonts: Vec<Font>,
-    pub font_sizes: Vec<f32>,
+    spans: Vec<StyleSpan>,
+    paragraph: ParagraphStyle,
 }
 
 impl Line {
     pub fn new() -> Self {
         Self {
             content: Vec::new(),
-            fonts: Vec::new(),
-            font_sizes: Vec::new(),
+            spans: Vec::new(),
+            paragraph: ParagraphStyle::Body,
         }
     }
 
-    // Ensure fonts and font_sizes are properly sized.
+    pub fn paragraph(&self) -> ParagraphStyle {
+        self.paragraph
+    }
+
+    pub fn set_paragraph(&mut self, paragraph: ParagraphStyle) {
+        self.paragraph = paragraph;
+    }
+
+    // Whether a footnote or endnote is anchored in this line.
+    pub fn has_anchor(&self) -> bool {
+        self.content.iter().any(|c| notes::anchor_id(*c).is_some())
+    }
+
+    // Ensure the style spans cover exactly the content.
     pub fn ensure_styles_match(&mut self) {
         let content_len = self.content.len();
-        match self.fonts.len().cmp(&content_len) {
-            Ordering::Less => self.fonts.resize(content_len, Font::default()),
-            Ordering::Greater => self.fonts.truncate(content_len),
-            Ordering::Equal => {}
-        }
-        match self.font_sizes.len().cmp(&content_len) {
-            Ordering::Less => self.font_sizes.resize(content_len, 12.0),
-            Ordering::Greater => self.font_sizes.truncate(content_len),
+        let styled_len: usize = self.spans.iter().map(|span| span.len).sum();
+        match styled_len.cmp(&content_len) {
+            Ordering::Less => {
+                let missing = content_len - styled_len;
+                match self.spans.last_mut() {
+                    Some(last) => last.len += missing,
+                    None => self.spans.push(StyleSpan {
+                        len: missing,
+                        font: Font::default(),
+                        size: FALLBACK_FONT_SIZE,
+                        underline: false,
+                    }),
+                }
+            }
+            Ordering::Greater => {
+                self.split_spans_at(content_len);
+                let keep = self.span_boundary(content_len);
+                self.spans.truncate(keep);
+            }
             Ordering::Equal => {}
         }
     }
 
     // Insert a character at a specific position with a given style.
     pub fn insert_char(&mut self, pos: usize, c: char, font: Font, font_size: f32) {
+        let index = self.split_spans_at(pos);
         self.content.insert(pos, c);
-        self.fonts.insert(pos, font);
-        self.font_sizes.insert(pos, font_size);
+        self.spans.insert(
+            index,
+            StyleSpan {
+                len: 1,
+                font,
+                size: font_size,
+                underline: false,
+            },
+        );
+        self.merge_spans();
     }
 
-    // Remove a character at a specific position.
-    pub fn remove_char(&mut self, pos: usize) -> Option<char> {
-        if pos < self.content.len() {
-            let c = self.content.remove(pos);
-            if pos < self.fonts.len() {
-                self.fonts.remove(pos);
-            }
-            if pos < self.font_sizes.len() {
-                self.font_sizes.remove(pos);
-            }
-            Some(c)
-        } else {
-            None
+    // Append a run of characters sharing one font and size.
+    pub fn push_run(&mut self, text: impl IntoIterator<Item = char>, font: Font, size: f32) {
+        let style = TextStyle {
+            font,
+            size,
+            underline: false,
+        };
+        self.push_styled(text, style);
+    }
+
+    // Append a run of characters sharing one style.
+    pub fn push_styled(&mut self, text: impl IntoIterator<Item = char>, style: TextStyle) {
+        self.ensure_styles_match();
+        let before = self.content.len();
+        self.content.extend(text);
+        self.spans.push(StyleSpan {
+            len: self.content.len() - before,
+            font: style.font,
+            size: style.size,
+            underline: style.underline,
+        });
+        self.merge_spans();
+    }
+
+    // Change the style of a range of characters.
+    pub fn restyle(&mut self, range: std::ops::Range<usize>, mut f: impl FnMut(&mut TextStyle)) {
+        self.ensure_styles_match();
+        let end = range.end.min(self.content.len());
+        let start = range.start.min(end);
+        let first = self.split_spans_at(start);
+        let last = self.split_spans_at(end);
+        for span in &mut self.spans[first..last] {
+            let mut style = span.style();
+            f(&mut style);
+            *span = StyleSpan {
+                len: span.len,
+                font: style.font,
+                size: style.size,
+                underline: style.underline,
+            };
         }
+        self.merge_spans();
     }
 
-    // Drain characters in a range.
-    pub fn drain_chars(&mut self, range: std::ops::Range<usize>) -> Vec<char> {
-        let chars: Vec<char> = self.content.drain(range.clone()).collect();
-        if !range.is_empty() && range.start < self.fonts.len() {
-            let end = range.end.min(self.fonts.len());
-            self.fonts.drain(range.start..end);
+    // The style runs within a range of characters, clipped to it.
+    pub fn runs(&self, range: std::ops::Range<usize>) -> Vec<(std::ops::Range<usize>, TextStyle)> {
+        let mut runs = Vec::new();
+        let mut start = 0;
+        for span in &self.spans {
+            let end = start + span.len;
+            let from = start.max(range.start);
+            let to = end.min(range.end);
+            if from < to {
+                runs.push((from..to, span.style()));
+            }
+            start = end;
         }
-        if !range.is_empty() && range.start < self.font_sizes.len() {
-            let end = range.end.min(self.font_sizes.len());
-            self.font_sizes.drain(range.start..end);
+        runs
+    }
+
+    // Split the line in two at a position, returning everything after it.
+    pub fn split_off(&mut self, pos: usize) -> Line {
+        self.ensure_styles_match();
+        let pos = pos.min(self.content.len());
+        let index = self.split_spans_at(pos);
+        Line {
+            content: self.content.split_off(pos),
+            spans: self.spans.split_off(index),
+            paragraph: self.paragraph,
         }
-        chars
+    }
+
+    // Copy a range of characters together with their styles.
+    pub fn slice(&self, range: std::ops::Range<usize>) -> Line {
+        let mut line = self.clone();
+        line.extract(range)
+    }
+
+    // Remove a range of characters together with their styles.
+    pub fn extract(&mut self, range: std::ops::Range<usize>) -> Line {
+        self.ensure_styles_match();
+        let end = range.end.min(self.content.len());
+        let start = range.start.min(end);
+        let first = self.split_spans_at(start);
+        let last = self.split_spans_at(end);
+        let extracted = Line {
+            content: self.content.drain(start..end).collect(),
+            spans: self.spans.drain(first..last).collect(),
+            paragraph: self.paragraph,
+        };
+        self.merge_spans();
+        extracted
     }
 
     // Append another line to this one.
     pub fn append(&mut self, other: &Line) {
+        self.ensure_styles_match();
         self.content.extend_from_slice(&other.content);
-        self.fonts.extend_from_slice(&other.fonts);
-        self.font_sizes.extend_from_slice(&other.font_sizes);
+        self.spans.extend_from_slice(&other.spans);
+        self.ensure_styles_match();
+        self.merge_spans();
     }
 
-    #[allow(dead_code)]
     pub fn font(&self, n: usize) -> Option<Font> {
-        // check if exists otherwise return default
-        if n < self.fonts.len() {
-            Some(self.fonts[n])
-        } else {
-            None
-        }
+        self.span_at(n).map(|span| span.font)
     }
 
-    #[allow(dead_code)]
     pub fn font_size(&self, n: usize) -> Option<f32> {
-        // check if exists otherwise return default
-        if n < self.font_sizes.len() {
-            Some(self.font_sizes[n])
-        } else {
-            None
+        self.span_at(n).map(|span| span.size)
+    }
+
+    pub fn style_at(&self, n: usize) -> Option<TextStyle> {
+        self.span_at(n).map(StyleSpan::style)
+    }
+
+    pub fn spans(&self) -> &[StyleSpan] {
+        &self.spans
+    }
+
+    // Note anchors are not text: they count as neither characters nor words, and one
+    // after a space does not start a word.
+    pub fn char_count(&self) -> usize {
+        self.text_chars().count()
+    }
+
+    pub fn word_count(&self) -> usize {
+        let mut count = 0;
+        let mut in_word = false;
+        for c in self.text_chars() {
+            if c.is_whitespace() {
+                in_word = false;
+            } else if !in_word {
+                in_word = true;
+                count += 1;
+            }
+        }
+        count
+    }
+
+    fn text_chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.content
+            .iter()
+            .copied()
+            .filter(|c| notes::anchor_id(*c).is_none())
+    }
+
+    fn span_at(&self, n: usize) -> Option<&StyleSpan> {
+        if n >= self.content.len() {
+            return None;
+        }
+        let mut start = 0;
+        self.spans.iter().find(|span| {
+            start += span.len;
+            n < start
+        })
+    }
+
+    // The index of the first span starting at or after a character position.
+    fn span_boundary(&self, pos: usize) -> usize {
+        let mut start = 0;
+        for (index, span) in self.spans.iter().enumerate() {
+            if start >= pos {
+                return index;
+            }
+            start += span.len;
+        }
+        self.spans.len()
+    }
+
+    // Make sure a span starts exactly at a position and return its index.
+    fn split_spans_at(&mut self, pos: usize) -> usize {
+        let mut start = 0;
+        for index in 0..self.spans.len() {
+            let span = self.spans[index];
+            if pos == start {
+                return index;
+            }
+            if pos < start + span.len {
+                let head = pos - start;
+                self.spans[index].len = head;
+                self.spans.insert(
+                    index + 1,
+                    StyleSpan {
+                        len: span.len - head,
+                        ..span
+                    },
+                );
+                return index + 1;
+            }
+            start += span.len;
+        }
+        self.spans.len()
+    }
+
+    // Drop empty spans and join neighbours that share a style.
+    fn merge_spans(&mut self) {
+        let mut merged: Vec<StyleSpan> = Vec::with_capacity(self.spans.len());
+        for span in self.spans.drain(..) {
+            if span.len == 0 {
+                continue;
+            }
+            match merged.last_mut() {
+                Some(last) if last.same_style(&span) => last.len += span.len,
+                _ => merged.push(span),
+            }
         }
+        self.spans = merged;
+    }
+}
+
+// A position in the document. Ordering compares the line first, then the column.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TextPosition {
+    pub vpos: usize,
+    pub hpos: usize,
+}
+
+impl TextPosition {
+    pub fn new(hpos: usize, vpos: usize) -> Self {
+        Self { vpos, hpos }
     }
 }
 
@@ -132,18 +437,37 @@ pub struct TextEditorState {
 
 #[derive(Debug, Clone)]
 struct TextEditorStateInner {
-    lines: Vec<Line>,
+    lines: LineRope,
     cursor_hpos: usize,
     cursor_vpos: usize,
     cursor_visible: bool,
     default_font: Font,
     default_font_size: f32,
-    char_width: f32,
     line_height: f32,
     scroll_offset_y: f32,
     viewport_height: f32,
     viewport_width: f32,
-    last_click_position: Option<Point>,
+    // The selection spans from the anchor to the cursor.
+    selection_anchor: Option<TextPosition>,
+    dragging: bool,
+    last_click: Option<(Instant, TextPosition)>,
+    click_count: usize,
+    // Emphasis toggled without a selection applies to the next typed characters,
+    // until the cursor moves.
+    typing_style: Option<TextStyle>,
+    // Keyboard input is ignored while another widget, like the find bar, has focus.
+    focused: bool,
+    search: Option<Search>,
+    // Every match of the search in document order, recomputed lazily after edits.
+    search_matches: Vec<(TextPosition, TextPosition)>,
+    search_stale: bool,
+    // The bodies of footnotes and endnotes; their anchors live in the lines.
+    notes: Notes,
+    speller: Option<Speller>,
+    // The misspelled words of every line, checked again whenever a line changes.
+    misspellings: Vec<Vec<std::ops::Range<usize>>>,
+    // The findings of the style checker, from a copy of the document.
+    lint: Option<Lint>,
 
     // TODO: Maybe we could render those values only when needed, so that
     // the count functions only get called when text changes, instead of when something
@@ -153,28 +477,39 @@ struct TextEditorStateInner {
     // be precise.
     cached_word_count: usize,
     cached_char_count: usize,
-    max_chars_per_visual_line: usize,
+    history: EditHistory,
 }
 
 impl Default for TextEditorState {
     fn default() -> Self {
         Self {
             inner: RefCell::new(TextEditorStateInner {
-                lines: vec![Line::new()],
+                lines: LineRope::default(),
                 cursor_hpos: 0,
                 cursor_vpos: 0,
                 cursor_visible: true,
                 default_font: Font::with_name("Courier New"),
                 default_font_size: 16.0,
-                char_width: 9.6,
                 line_height: 20.0,
                 scroll_offset_y: 0.0,
                 viewport_height: 0.0,
                 viewport_width: 0.0,
-                last_click_position: None,
+                selection_anchor: None,
+                dragging: false,
+                last_click: None,
+                click_count: 0,
+                typing_style: None,
+                focused: true,
+                search: None,
+                search_matches: Vec::new(),
+                search_stale: false,
+                notes: Notes::default(),
+                speller: None,
+                misspellings: Vec::new(),
+                lint: None,
                 cached_word_count: 0,
                 cached_char_count: 0,
-                max_chars_per_visual_line: 120,
+                history: EditHistory::default(),
             }),
         }
     }
@@ -196,99 +531,141 @@ impl canvas::Program<TextEditorMessage> for TextEditorState {
         bounds: Rectangle,
         _cursor: Cursor,
     ) -> Vec<Geometry> {
+        // The canvas decides how much room there is, so keep the viewport in sync with it.
+        if self.inner.borrow().viewport_size() != bounds.size() {
+            self.inner.borrow_mut().set_viewport_size(bounds.size());
+        }
+        if self.inner.borrow().search_stale {
+            self.inner.borrow_mut().refresh_search();
+        }
+
         let inner = self.inner.borrow();
         let mut frame = Frame::new(renderer, bounds.size());
         frame.fill_rectangle(Point::ORIGIN, bounds.size(), Color::TRANSPARENT);
 
-        let max_chars = inner.max_chars_per_visual_line;
-        let line_height = inner.line_height;
-        let char_width = inner.char_width;
-
-        let mut current_visual_line = 0;
-
-        for (logical_idx, line) in inner.lines.iter().enumerate() {
-            let mut pos = 0;
-
-            while pos < line.content.len() {
-                let wrap_pos = inner.find_wrap_position(line, pos, max_chars);
-                let line_y = current_visual_line as f32 * line_height - inner.scroll_offset_y;
-
-                if line_y + line_height >= 0.0 && line_y <= bounds.height {
-                    let text = line.content[pos..wrap_pos].iter().collect::<String>();
-
-                    frame.fill_text(canvas::Text {
-                        content: text,
-                        position: Point::new(10.0, line_y + line_height),
-                        color: Color::WHITE,
-                        size: iced::Pixels(inner.default_font_size),
-                        line_height: LineHeight::Relative(1.0),
-                        font: inner.default_font,
-                        horizontal_alignment: Horizontal::Left,
-                        vertical_alignment: Vertical::Top,
-                        shaping: Shaping::Basic,
-                    });
+        // A selection that continues past the end of a line covers one space for the break.
+        let break_width = metrics::advance(' ', inner.default_font, inner.default_font_size);
+
+        // Only lay out what is on screen, starting with the paragraph at the top edge.
+        let first_line = inner
+            .lines
+            .line_at_y(inner.scroll_offset_y - inner.top_padding());
+        let mut paragraph_y =
+            inner.top_padding() + inner.lines.y_offset(first_line) - inner.scroll_offset_y;
+
+        let cursor_segment = (inner.cursor_vpos < inner.lines.len()).then(|| {
+            inner
+                .lines
+                .layout(inner.cursor_vpos)
+                .visual_line(inner.cursor_hpos)
+        });
+
+        'lines: for (offset, (line, layout)) in inner.lines.layouts_from(first_line).enumerate() {
+            let logical_idx = first_line + offset;
+            let paragraph = line.paragraph();
+            let findings = match &inner.lint {
+                Some(lint) if lint.is_current(logical_idx, line) => lint.on_line(logical_idx),
+                _ => &[],
+            };
+            if paragraph_y > bounds.height {
+                break 'lines;
+            }
 
-                    if inner.cursor_visible && logical_idx == inner.cursor_vpos {
-                        let (cursor_visual_line, cursor_visual_column) =
-                            inner.logical_to_visual_position(logical_idx, inner.cursor_hpos);
-
-                        if cursor_visual_line
-                            == current_visual_line - inner.get_visual_line_offset(logical_idx)
-                        {
-                            let cursor_x = 10.0 + cursor_visual_column as f32 * char_width;
-
-                            let cursor_path = Path::line(
-                                Point::new(cursor_x, line_y + line_height),
-                                Point::new(
-                                    cursor_x,
-                                    line_y + line_height + inner.default_font_size,
-                                ),
-                            );
-                            frame.stroke(
-                                &cursor_path,
-                                Stroke {
-                                    width: 1.0,
-                                    style: geometry::Style::Solid(Color::WHITE),
-                                    ..Stroke::default()
-                                },
-                            );
-                        }
+            for segment in 0..layout.visual_lines() {
+                let line_y = paragraph_y + layout.top(segment);
+                let line_height = layout.line_height(segment);
+                if line_y > bounds.height {
+                    break 'lines;
+                }
+                if line_y + line_height < 0.0 {
+                    continue;
+                }
+                // Runs of different sizes share a baseline, placed for the largest one.
+                let largest = line_height / LINE_SPACING;
+                let baseline = line_y + line_height - DESCENT * largest;
+
+                let range = layout.segment(segment);
+                for (start, end) in inner.matches_on_line(logical_idx) {
+                    let from = start.hpos.max(range.start);
+                    let to = end.hpos.min(range.end);
+                    if from < to {
+                        let from_x = layout.x(segment, from);
+                        frame.fill_rectangle(
+                            Point::new(10.0 + from_x, line_y),
+                            Size::new(layout.x(segment, to) - from_x, line_height),
+                            MATCH_COLOR,
+                        );
                     }
                 }
+                if let Some((from, to)) =
+                    inner.selected_columns(logical_idx, range.start, range.end)
+                {
+                    let from_x = layout.x(segment, range.start + from);
+                    let mut to_x = layout.x(segment, range.start + to);
+                    if range.start + to > line.content.len() {
+                        to_x += break_width;
+                    }
+                    frame.fill_rectangle(
+                        Point::new(10.0 + from_x, line_y),
+                        Size::new(to_x - from_x, line_height),
+                        SELECTION_COLOR,
+                    );
+                }
 
-                pos = wrap_pos;
-                current_visual_line += 1;
-            }
-
-            // Handle empty lines to ensure cursor visibility and line height
-            if line.content.is_empty() {
-                let line_y = current_visual_line as f32 * line_height - inner.scroll_offset_y;
-
-                if line_y + line_height >= 0.0 && line_y <= bounds.height {
-                    // Draw empty line placeholder to maintain line height
-                    frame.fill_text(canvas::Text {
-                        content: " ".to_string(),
-                        position: Point::new(10.0, line_y + line_height),
-                        color: Color::WHITE,
-                        size: iced::Pixels(inner.default_font_size),
-                        line_height: LineHeight::Relative(1.0),
-                        font: inner.default_font,
-                        horizontal_alignment: Horizontal::Left,
-                        vertical_alignment: Vertical::Top,
-                        shaping: Shaping::Basic,
-                    });
+                // Every run of same-styled characters is drawn as its own fragment.
+                for (run, style) in line.runs(range.clone()) {
+                    let style = paragraph.apply(style);
+                    let run_x = 10.0 + layout.x(segment, run.start);
+
+                    // Note anchors are drawn as their numbers in superscript, so the
+                    // run is split around them.
+                    let mut start = run.start;
+                    while start < run.end {
+                        let anchor = notes::anchor_id(line.content[start]);
+                        let end = match anchor {
+                            Some(_) => start + 1,
+                            None => (start..run.end)
+                                .find(|i| notes::anchor_id(line.content[*i]).is_some())
+                                .unwrap_or(run.end),
+                        };
+                        let (content, size, y) = match anchor {
+                            Some(id) => {
+                                let size = style.size * notes::SUPERSCRIPT_SCALE;
+                                let rise = notes::SUPERSCRIPT_RISE * style.size;
+                                let label = inner.lines.params().notes.label(id).to_string();
+                                (label, size, baseline - rise - ASCENT * size)
+                            }
+                            None => (
+                                line.content[start..end].iter().collect::<String>(),
+                                style.size,
+                                baseline - ASCENT * style.size,
+                            ),
+                        };
+
+                        frame.fill_text(canvas::Text {
+                            content,
+                            position: Point::new(10.0 + layout.x(segment, start), y),
+                            color: Color::WHITE,
+                            size: iced::Pixels(size),
+                            line_height: LineHeight::Relative(1.0),
+                            font: style.font,
+                            horizontal_alignment: Horizontal::Left,
+                            vertical_alignment: Vertical::Top,
+                            shaping: Shaping::Basic,
+                        });
+                        start = end;
+                    }
 
-                    // Draw cursor if on this empty line
-                    if inner.cursor_visible && logical_idx == inner.cursor_vpos {
-                        let cursor_x = 10.0;
-                        let cursor_path = Path::line(
-                            Point::new(cursor_x, line_y + line_height),
-                            Point::new(cursor_x, line_y + line_height + inner.default_font_size),
-                        );
+                    if style.underline {
+                        let underline_y = baseline + UNDERLINE_OFFSET * style.size;
+                        let run_end = 10.0 + layout.x(segment, run.end);
                         frame.stroke(
-                            &cursor_path,
+                            &Path::line(
+                                Point::new(run_x, underline_y),
+                                Point::new(run_end, underline_y),
+                            ),
                             Stroke {
-                                width: 1.0,
+                                width: (style.size / 16.0).max(1.0),
                                 style: geometry::Style::Solid(Color::WHITE),
                                 ..Stroke::default()
                             },
@@ -296,8 +673,85 @@ impl canvas::Program<TextEditorMessage> for TextEditorState {
                     }
                 }
 
-                current_visual_line += 1;
+                for finding in findings {
+                    let from = finding.range.start.max(range.start);
+                    let to = finding.range.end.min(range.end);
+                    if from >= to {
+                        continue;
+                    }
+                    let lint_y = baseline + UNDERLINE_OFFSET * largest + LINT_OFFSET;
+                    frame.stroke(
+                        &Path::line(
+                            Point::new(10.0 + layout.x(segment, from), lint_y),
+                            Point::new(10.0 + layout.x(segment, to), lint_y),
+                        ),
+                        Stroke {
+                            width: 2.0,
+                            style: geometry::Style::Solid(finding.rule.color()),
+                            ..Stroke::default()
+                        },
+                    );
+                }
+
+                // The word being typed is left alone until the cursor leaves it.
+                for word in inner.misspellings_on_line(logical_idx) {
+                    let from = word.start.max(range.start);
+                    let to = word.end.min(range.end);
+                    let typing = inner.focused
+                        && logical_idx == inner.cursor_vpos
+                        && word.end == inner.cursor_hpos;
+                    if from >= to || typing {
+                        continue;
+                    }
+                    let from_x = 10.0 + layout.x(segment, from);
+                    let to_x = 10.0 + layout.x(segment, to);
+                    let squiggle_y = baseline + UNDERLINE_OFFSET * largest + SQUIGGLE_HEIGHT;
+                    let squiggle = Path::new(|builder| {
+                        builder.move_to(Point::new(from_x, squiggle_y));
+                        let mut x = from_x;
+                        let mut up = true;
+                        while x < to_x {
+                            x = (x + SQUIGGLE_WAVELENGTH / 2.0).min(to_x);
+                            let y = match up {
+                                true => squiggle_y - SQUIGGLE_HEIGHT,
+                                false => squiggle_y,
+                            };
+                            builder.line_to(Point::new(x, y));
+                            up = !up;
+                        }
+                    });
+                    frame.stroke(
+                        &squiggle,
+                        Stroke {
+                            width: 1.0,
+                            style: geometry::Style::Solid(MISSPELLING_COLOR),
+                            ..Stroke::default()
+                        },
+                    );
+                }
+
+                if inner.cursor_visible
+                    && logical_idx == inner.cursor_vpos
+                    && cursor_segment == Some(segment)
+                {
+                    let cursor_x = 10.0 + layout.x(segment, inner.cursor_hpos);
+
+                    let cursor_path = Path::line(
+                        Point::new(cursor_x, baseline - ASCENT * largest),
+                        Point::new(cursor_x, baseline + DESCENT * largest),
+                    );
+                    frame.stroke(
+                        &cursor_path,
+                        Stroke {
+                            width: 1.0,
+                            style: geometry::Style::Solid(Color::WHITE),
+                            ..Stroke::default()
+                        },
+                    );
+                }
             }
+
+            paragraph_y += layout.height();
         }
 
         vec![frame.into_geometry()]
@@ -308,11 +762,13 @@ impl canvas::Program<TextEditorMessage> for TextEditorState {
         _state: &mut Self::State,
         event: canvas::Event,
         bounds: Rectangle,
-        _cursor: Cursor,
+        cursor: Cursor,
     ) -> (canvas::event::Status, Option<TextEditorMessage>) {
         let mut inner = self.inner.borrow_mut();
         match event {
-            canvas::Event::Mouse(mouse_event) => inner.handle_mouse_event(mouse_event, bounds),
+            canvas::Event::Mouse(mouse_event) => {
+                inner.handle_mouse_event(mouse_event, bounds, cursor)
+            }
             canvas::Event::Keyboard(keyboard_event) => inner.handle_keyboard_event(keyboard_event),
             _ => (canvas::event::Status::Ignored, None),
         }
@@ -326,40 +782,78 @@ impl TextEditorStateInner {
         &mut self,
         event: MouseEvent,
         bounds: Rectangle,
+        cursor: Cursor,
     ) -> (canvas::event::Status, Option<TextEditorMessage>) {
         match event {
             MouseEvent::ButtonPressed(iced::mouse::Button::Left) => {
-                if let Some(position) = self.last_click_position {
-                    let click_y = position.y + self.scroll_offset_y;
-                    let mut visual_line = (click_y / self.line_height) as usize;
-
-                    let mut logical_vpos = 0;
-                    for (idx, line) in self.lines.iter().enumerate() {
-                        let num_visual =
-                            line.content.len().div_ceil(self.max_chars_per_visual_line);
-
-                        if visual_line < num_visual {
-                            logical_vpos = idx;
-                            break;
-                        }
-                        visual_line -= num_visual;
+                // Clicking anywhere else hands the keyboard to whatever was clicked.
+                let Some(point) = cursor.position_in(bounds) else {
+                    self.focused = false;
+                    return (canvas::event::Status::Ignored, None);
+                };
+                let position = self.position_at(point);
+                let now = Instant::now();
+                self.focused = true;
+
+                // Repeated clicks on the same spot cycle through word and paragraph selection.
+                self.click_count = match self.last_click {
+                    Some((time, last)) if last == position && now - time < DOUBLE_CLICK_TIME => {
+                        self.click_count % 3 + 1
                     }
-
-                    let line = &self.lines[logical_vpos];
-                    let hpos = (visual_line * self.max_chars_per_visual_line)
-                        + ((position.x - 10.0) / self.char_width).floor().max(0.0) as usize;
-                    self.cursor_vpos = logical_vpos;
-                    self.cursor_hpos = hpos.min(line.content.len());
-                    self.cursor_visible = true;
-                    return (canvas::event::Status::Captured, None);
+                    _ => 1,
+                };
+                self.last_click = Some((now, position));
+
+                match self.click_count {
+                    1 => {
+                        self.set_cursor(position);
+                        self.selection_anchor = Some(position);
+                        self.dragging = true;
+                    }
+                    2 => self.select_word_at(position),
+                    _ => self.select_paragraph(position.vpos),
                 }
+                self.history.seal();
+                self.typing_style = None;
+                self.cursor_visible = true;
+                (canvas::event::Status::Captured, None)
+            }
+            MouseEvent::ButtonPressed(iced::mouse::Button::Right) => {
+                let Some(point) = cursor.position_in(bounds) else {
+                    return (canvas::event::Status::Ignored, None);
+                };
+                // Right-clicking a misspelled word selects it and asks for suggestions.
+                let position = self.position_at(point);
+                let Some(word) = self
+                    .misspellings_on_line(position.vpos)
+                    .iter()
+                    .find(|word| word.start <= position.hpos && position.hpos <= word.end)
+                    .cloned()
+                else {
+                    return (canvas::event::Status::Ignored, None);
+                };
+                self.focused = true;
+                self.history.seal();
+                self.typing_style = None;
+                self.selection_anchor = Some(TextPosition::new(word.start, position.vpos));
+                self.set_cursor(TextPosition::new(word.end, position.vpos));
+                self.cursor_visible = true;
+                (
+                    canvas::event::Status::Captured,
+                    Some(TextEditorMessage::SpellingRequested),
+                )
+            }
+            MouseEvent::ButtonReleased(iced::mouse::Button::Left) => {
+                self.dragging = false;
                 (canvas::event::Status::Captured, None)
             }
             MouseEvent::CursorMoved { position } => {
-                if bounds.contains(position) {
-                    self.last_click_position = Some(position);
-                } else {
-                    self.last_click_position = None;
+                if self.dragging {
+                    // Keep extending the selection while dragging outside the canvas.
+                    let point = Point::new(position.x - bounds.x, position.y - bounds.y);
+                    let head = self.position_at(point);
+                    self.set_cursor(head);
+                    self.ensure_cursor_visible();
                 }
                 (canvas::event::Status::Captured, None)
             }
@@ -383,6 +877,9 @@ impl TextEditorStateInner {
         &mut self,
         event: KeyEvent,
     ) -> (canvas::event::Status, Option<TextEditorMessage>) {
+        if !self.focused {
+            return (canvas::event::Status::Ignored, None);
+        }
         match event {
             KeyEvent::KeyPressed {
                 key: iced::keyboard::Key::Named(Named::Enter),
@@ -428,11 +925,97 @@ impl TextEditorStateInner {
                     )),
                 )
             }
+            KeyEvent::KeyPressed {
+                key: iced::keyboard::Key::Character(c),
+                modifiers,
+                ..
+            } if modifiers.command()
+                && (c.eq_ignore_ascii_case("z") || c.eq_ignore_ascii_case("y")) =>
+            {
+                if c.eq_ignore_ascii_case("y") || modifiers.shift() {
+                    self.handle_redo();
+                } else {
+                    self.handle_undo();
+                }
+                (
+                    canvas::event::Status::Captured,
+                    Some(TextEditorMessage::ContentChanged(
+                        self.cursor_hpos,
+                        self.cursor_vpos,
+                        0,
+                    )),
+                )
+            }
+            KeyEvent::KeyPressed {
+                key: iced::keyboard::Key::Character(c),
+                modifiers,
+                ..
+            } if modifiers.command() && c.eq_ignore_ascii_case("a") => {
+                self.select_all();
+                (
+                    canvas::event::Status::Captured,
+                    Some(TextEditorMessage::CursorChanged(
+                        self.cursor_hpos,
+                        self.cursor_vpos,
+                        0,
+                    )),
+                )
+            }
+            KeyEvent::KeyPressed {
+                key: iced::keyboard::Key::Character(c),
+                modifiers,
+                ..
+            } if modifiers.command()
+                && ["b", "i", "u"]
+                    .iter()
+                    .any(|key| c.eq_ignore_ascii_case(key)) =>
+            {
+                let emphasis = if c.eq_ignore_ascii_case("b") {
+                    Emphasis::Bold
+                } else if c.eq_ignore_ascii_case("i") {
+                    Emphasis::Italic
+                } else {
+                    Emphasis::Underline
+                };
+                let message = if self.toggle_emphasis(emphasis) {
+                    TextEditorMessage::ContentChanged
+                } else {
+                    TextEditorMessage::CursorChanged
+                };
+                (
+                    canvas::event::Status::Captured,
+                    Some(message(self.cursor_hpos, self.cursor_vpos, 0)),
+                )
+            }
+            KeyEvent::KeyPressed {
+                key: iced::keyboard::Key::Character(c),
+                modifiers,
+                ..
+            } if modifiers.command() && Self::paragraph_shortcut(&c, modifiers).is_some() => {
+                if let Some(style) = Self::paragraph_shortcut(&c, modifiers) {
+                    self.set_paragraph_style(style);
+                }
+                (
+                    canvas::event::Status::Captured,
+                    Some(TextEditorMessage::ContentChanged(
+                        self.cursor_hpos,
+                        self.cursor_vpos,
+                        0,
+                    )),
+                )
+            }
             KeyEvent::KeyPressed {
                 key: iced::keyboard::Key::Named(Named::ArrowLeft),
+                modifiers,
                 ..
             } => {
-                self.handle_arrow_left();
+                if modifiers.command() {
+                    self.prepare_movement(modifiers.shift());
+                    self.handle_word_left();
+                } else if modifiers.shift() || !self.collapse_selection(true) {
+                    self.prepare_movement(modifiers.shift());
+                    self.handle_arrow_left();
+                }
                 (
                     canvas::event::Status::Captured,
                     Some(TextEditorMessage::CursorChanged(
@@ -444,9 +1027,16 @@ impl TextEditorStateInner {
             }
             KeyEvent::KeyPressed {
                 key: iced::keyboard::Key::Named(Named::ArrowRight),
+                modifiers,
                 ..
             } => {
-                self.handle_arrow_right();
+                if modifiers.command() {
+                    self.prepare_movement(modifiers.shift());
+                    self.handle_word_right();
+                } else if modifiers.shift() || !self.collapse_selection(false) {
+                    self.prepare_movement(modifiers.shift());
+                    self.handle_arrow_right();
+                }
                 (
                     canvas::event::Status::Captured,
                     Some(TextEditorMessage::CursorChanged(
@@ -458,8 +1048,10 @@ impl TextEditorStateInner {
             }
             KeyEvent::KeyPressed {
                 key: iced::keyboard::Key::Named(Named::ArrowUp),
+                modifiers,
                 ..
             } => {
+                self.prepare_movement(modifiers.shift());
                 self.handle_arrow_up();
                 (
                     canvas::event::Status::Captured,
@@ -472,8 +1064,10 @@ impl TextEditorStateInner {
             }
             KeyEvent::KeyPressed {
                 key: iced::keyboard::Key::Named(Named::ArrowDown),
+                modifiers,
                 ..
             } => {
+                self.prepare_movement(modifiers.shift());
                 self.handle_arrow_down();
                 (
                     canvas::event::Status::Captured,
@@ -485,8 +1079,82 @@ impl TextEditorStateInner {
                 )
             }
             KeyEvent::KeyPressed {
-                text: Some(text), ..
+                key: iced::keyboard::Key::Named(Named::Home),
+                modifiers,
+                ..
+            } => {
+                self.prepare_movement(modifiers.shift());
+                if modifiers.command() {
+                    self.handle_document_start();
+                } else {
+                    self.handle_home();
+                }
+                (
+                    canvas::event::Status::Captured,
+                    Some(TextEditorMessage::CursorChanged(
+                        self.cursor_hpos,
+                        self.cursor_vpos,
+                        -1,
+                    )),
+                )
+            }
+            KeyEvent::KeyPressed {
+                key: iced::keyboard::Key::Named(Named::End),
+                modifiers,
+                ..
+            } => {
+                self.prepare_movement(modifiers.shift());
+                if modifiers.command() {
+                    self.handle_document_end();
+                } else {
+                    self.handle_end();
+                }
+                (
+                    canvas::event::Status::Captured,
+                    Some(TextEditorMessage::CursorChanged(
+                        self.cursor_hpos,
+                        self.cursor_vpos,
+                        1,
+                    )),
+                )
+            }
+            KeyEvent::KeyPressed {
+                key: iced::keyboard::Key::Named(Named::PageUp),
+                modifiers,
+                ..
             } => {
+                self.prepare_movement(modifiers.shift());
+                self.handle_page(-1);
+                (
+                    canvas::event::Status::Captured,
+                    Some(TextEditorMessage::CursorChanged(
+                        self.cursor_hpos,
+                        self.cursor_vpos,
+                        -1,
+                    )),
+                )
+            }
+            KeyEvent::KeyPressed {
+                key: iced::keyboard::Key::Named(Named::PageDown),
+                modifiers,
+                ..
+            } => {
+                self.prepare_movement(modifiers.shift());
+                self.handle_page(1);
+                (
+                    canvas::event::Status::Captured,
+                    Some(TextEditorMessage::CursorChanged(
+                        self.cursor_hpos,
+                        self.cursor_vpos,
+                        1,
+                    )),
+                )
+            }
+            KeyEvent::KeyPressed {
+                text: Some(text),
+                modifiers,
+                ..
+            } if !modifiers.command() => {
                 self.handle_text_input(text.as_str());
                 self.update_cached_counts();
                 (
@@ -502,55 +1170,46 @@ impl TextEditorStateInner {
         }
     }
 
-    fn get_visual_line_offset(&self, logical_line_idx: usize) -> usize {
-        let mut offset = 0;
-        for idx in 0..logical_line_idx {
-            offset += self.calculate_visual_lines(&self.lines[idx]);
-        }
-        offset
-    }
-
     fn handle_enter(&mut self) -> i32 {
         let mut scroll_direction = 0;
         self.ensure_line_exists(self.cursor_vpos);
         if self.cursor_vpos + 1 == self.lines.len() {
             scroll_direction = 1;
         }
-        let mut new_line = Line::new();
-        if self.cursor_hpos < self.lines[self.cursor_vpos].content.len() {
-            let content_range = self.cursor_hpos..self.lines[self.cursor_vpos].content.len();
-            let content_to_move = self.lines[self.cursor_vpos].drain_chars(content_range);
-            for c in content_to_move {
-                new_line.insert_char(
-                    new_line.content.len(),
-                    c,
-                    self.default_font,
-                    self.default_font_size,
-                );
-            }
-            self.lines[self.cursor_vpos].ensure_styles_match();
+        self.history.begin_group();
+        self.delete_selection();
+        // A new paragraph continues the current style, except after a heading or
+        // caption, where the text that follows is body text again.
+        let current = &self.lines[self.cursor_vpos];
+        let mut next = Line::new();
+        if self.cursor_hpos < current.content.len()
+            || !(current.paragraph().is_heading() || current.paragraph() == ParagraphStyle::Caption)
+        {
+            next.set_paragraph(current.paragraph());
         }
-        self.lines.insert(self.cursor_vpos + 1, new_line);
-        self.cursor_vpos += 1;
-        self.cursor_hpos = 0;
+        self.insert_at_cursor(vec![Line::new(), next], EditKind::Other);
+        self.history.end_group();
         self.ensure_cursor_visible();
         scroll_direction
     }
 
     fn handle_backspace(&mut self, modifiers: Modifiers) {
         self.ensure_line_exists(self.cursor_vpos);
-        if self.cursor_hpos > 0 {
+        let cursor = self.cursor();
+        if self.delete_selection() {
+            // The selection was all there was to remove.
+        } else if self.cursor_hpos > 0 {
             if modifiers.control() {
                 self.handle_ctrl_backspace();
             } else {
-                self.lines[self.cursor_vpos].remove_char(self.cursor_hpos - 1);
-                self.cursor_hpos -= 1;
+                let start = TextPosition::new(self.cursor_hpos - 1, self.cursor_vpos);
+                self.delete_range(start, cursor, EditKind::Deleting);
             }
         } else if self.cursor_vpos > 0 {
-            self.join_with_previous_line();
-        }
-        if self.cursor_vpos < self.lines.len() {
-            self.lines[self.cursor_vpos].ensure_styles_match();
+            // Join with the previous line.
+            let prev_len = self.lines[self.cursor_vpos - 1].content.len();
+            let start = TextPosition::new(prev_len, self.cursor_vpos - 1);
+            self.delete_range(start, cursor, EditKind::Deleting);
         }
         self.ensure_cursor_visible();
         self.update_cached_counts();
@@ -574,8 +1233,8 @@ impl TextEditorStateInner {
             0
         };
         if start_pos < self.cursor_hpos {
-            self.lines[self.cursor_vpos].drain_chars(start_pos..self.cursor_hpos);
-            self.cursor_hpos = start_pos;
+            let start = TextPosition::new(start_pos, self.cursor_vpos);
+            self.delete_range(start, self.cursor(), EditKind::Other);
         }
         self.update_cached_counts();
     }
@@ -585,8 +1244,7 @@ impl TextEditorStateInner {
             return;
         }
 
-        let line = &mut self.lines[self.cursor_vpos];
-        let content = &line.content;
+        let content = &self.lines[self.cursor_vpos].content;
         let mut end_pos = self.cursor_hpos;
 
         // Skip whitespace after cursor
@@ -600,46 +1258,310 @@ impl TextEditorStateInner {
         }
 
         if end_pos > self.cursor_hpos {
-            line.drain_chars(self.cursor_hpos..end_pos);
-        }
-    }
-
-    fn join_with_previous_line(&mut self) {
-        if self.cursor_vpos == 0 || self.cursor_vpos >= self.lines.len() {
-            return;
+            let end = TextPosition::new(end_pos, self.cursor_vpos);
+            self.delete_range(self.cursor(), end, EditKind::Other);
         }
-        let current_line = self.lines.remove(self.cursor_vpos);
-        let prev_line_idx = self.cursor_vpos - 1;
-        let new_cursor_pos = self.lines[prev_line_idx].content.len();
-        self.lines[prev_line_idx].append(&current_line);
-        self.cursor_vpos = prev_line_idx;
-        self.cursor_hpos = new_cursor_pos;
     }
 
     fn handle_delete(&mut self, modifiers: Modifiers) {
         self.ensure_line_exists(self.cursor_vpos);
 
-        if modifiers.control() {
+        if self.delete_selection() {
+            // The selection was all there was to remove.
+        } else if modifiers.control() {
             self.handle_ctrl_delete();
         } else {
             #[allow(clippy::collapsible_if)]
             if self.cursor_hpos < self.lines[self.cursor_vpos].content.len() {
-                self.lines[self.cursor_vpos].remove_char(self.cursor_hpos);
+                let end = TextPosition::new(self.cursor_hpos + 1, self.cursor_vpos);
+                self.delete_range(self.cursor(), end, EditKind::Deleting);
             } else if self.cursor_vpos < self.lines.len() - 1 {
-                self.join_with_next_line();
+                // Join with the next line.
+                let end = TextPosition::new(0, self.cursor_vpos + 1);
+                self.delete_range(self.cursor(), end, EditKind::Deleting);
             }
         }
 
         self.update_cached_counts();
     }
 
-    fn join_with_next_line(&mut self) {
-        if self.cursor_vpos >= self.lines.len() - 1 {
+    fn handle_undo(&mut self) {
+        self.selection_anchor = None;
+        self.typing_style = None;
+        let Some(transaction) = self.history.undo() else {
             return;
+        };
+        for edit in transaction.edits.iter().rev() {
+            self.apply_edit(&edit.inverse());
         }
-        let next_line = self.lines.remove(self.cursor_vpos + 1);
-        self.lines[self.cursor_vpos].append(&next_line);
-        self.lines[self.cursor_vpos].ensure_styles_match();
+        self.set_cursor(transaction.cursor_before);
+        self.ensure_cursor_visible();
+        self.update_cached_counts();
+    }
+
+    fn handle_redo(&mut self) {
+        self.selection_anchor = None;
+        self.typing_style = None;
+        let Some(transaction) = self.history.redo() else {
+            return;
+        };
+        for edit in &transaction.edits {
+            self.apply_edit(edit);
+        }
+        self.set_cursor(transaction.cursor_after);
+        self.ensure_cursor_visible();
+        self.update_cached_counts();
+    }
+
+    // Insert a fragment at the cursor, recording it in the history.
+    fn insert_at_cursor(&mut self, fragment: Vec<Line>, kind: EditKind) {
+        let at = self.cursor();
+        let end = self.insert_fragment(at, &fragment);
+        self.set_cursor(end);
+        self.history
+            .record(Edit::Insert { at, fragment }, at, end, kind);
+    }
+
+    // Delete a range, recording it in the history. The cursor ends up at its start.
+    fn delete_range(&mut self, start: TextPosition, end: TextPosition, kind: EditKind) {
+        let before = self.cursor();
+        let fragment = self.remove_range(start, end);
+        self.set_cursor(start);
+        self.history.record(
+            Edit::Delete {
+                at: start,
+                fragment,
+            },
+            before,
+            start,
+            kind,
+        );
+    }
+
+    fn apply_edit(&mut self, edit: &Edit) {
+        match edit {
+            Edit::Insert { at, fragment } => {
+                self.insert_fragment(*at, fragment);
+            }
+            Edit::Delete { at, fragment } => {
+                self.remove_range(*at, fragment_end(*at, fragment));
+            }
+            Edit::Paragraph { vpos, to, .. } => {
+                self.lines.update(*vpos, |line| line.set_paragraph(*to));
+            }
+        }
+    }
+
+    // Insert styled lines at a position and return the position after them. The
+    // first line of the fragment continues the line at the position, the last
+    // one takes over whatever followed it.
+    fn insert_fragment(&mut self, at: TextPosition, fragment: &[Line]) -> TextPosition {
+        self.ensure_line_exists(at.vpos);
+        self.search_stale = true;
+        let end = fragment_end(at, fragment);
+        let Some((first, rest)) = fragment.split_first() else {
+            return at;
+        };
+
+        let tail = self.lines.update(at.vpos, |line| {
+            let tail = line.split_off(at.hpos);
+            line.append(first);
+            tail
+        });
+        if rest.is_empty() {
+            self.lines.update(at.vpos, |line| line.append(&tail));
+        } else {
+            let mut new_lines = rest.to_vec();
+            if let Some(last) = new_lines.last_mut() {
+                last.append(&tail);
+            }
+            self.lines.insert_many(at.vpos + 1, new_lines);
+        }
+        self.respell(at.vpos, 1, fragment.len());
+        if fragment.iter().any(Line::has_anchor) {
+            self.renumber_notes();
+        }
+        end
+    }
+
+    // Remove the text between two positions and return it as styled lines.
+    fn remove_range(&mut self, start: TextPosition, end: TextPosition) -> Vec<Line> {
+        let end = self.clamp_position(end);
+        if end <= start || start.vpos >= self.lines.len() {
+            return vec![Line::new()];
+        }
+        self.search_stale = true;
+        let removed = if start.vpos == end.vpos {
+            vec![
+                self.lines
+                    .update(start.vpos, |line| line.extract(start.hpos..end.hpos)),
+            ]
+        } else {
+            let mut removed = vec![
+                self.lines
+                    .update(start.vpos, |line| line.split_off(start.hpos)),
+            ];
+            removed.extend(self.lines.remove_range(start.vpos + 1..end.vpos));
+            let mut last = self.lines.remove(start.vpos + 1);
+            let rest = last.split_off(end.hpos);
+            removed.push(last);
+            self.lines.update(start.vpos, |line| line.append(&rest));
+            removed
+        };
+        self.respell(start.vpos, end.vpos - start.vpos + 1, 1);
+        if removed.iter().any(Line::has_anchor) {
+            self.renumber_notes();
+        }
+        removed
+    }
+
+    // The selected range in document order, if anything is selected.
+    fn selection(&self) -> Option<(TextPosition, TextPosition)> {
+        let anchor = self.selection_anchor?;
+        let head = self.cursor();
+        match anchor.cmp(&head) {
+            Ordering::Less => Some((anchor, head)),
+            Ordering::Greater => Some((head, anchor)),
+            Ordering::Equal => None,
+        }
+    }
+
+    // Called before the cursor moves: holding shift extends the selection,
+    // any other movement drops it.
+    fn prepare_movement(&mut self, extend: bool) {
+        if !extend {
+            self.selection_anchor = None;
+        } else if self.selection_anchor.is_none() {
+            self.selection_anchor = Some(self.cursor());
+        }
+        self.history.seal();
+        self.typing_style = None;
+    }
+
+    // Move the cursor to one end of the selection and drop it.
+    fn collapse_selection(&mut self, to_start: bool) -> bool {
+        let Some((start, end)) = self.selection() else {
+            return false;
+        };
+        self.selection_anchor = None;
+        self.set_cursor(if to_start { start } else { end });
+        self.history.seal();
+        self.typing_style = None;
+        self.ensure_cursor_visible();
+        true
+    }
+
+    // The selected columns of one visual line, relative to its start. A selection
+    // that continues onto the next line also covers one column for the line break.
+    fn selected_columns(
+        &self,
+        logical_idx: usize,
+        start: usize,
+        end: usize,
+    ) -> Option<(usize, usize)> {
+        let (sel_start, sel_end) = self.selection()?;
+        if logical_idx < sel_start.vpos || logical_idx > sel_end.vpos {
+            return None;
+        }
+
+        let line_len = self.lines[logical_idx].content.len();
+        let from = if logical_idx == sel_start.vpos {
+            sel_start.hpos
+        } else {
+            0
+        };
+        let to = if logical_idx == sel_end.vpos {
+            sel_end.hpos
+        } else {
+            line_len + 1
+        };
+        let segment_end = if end >= line_len { end + 1 } else { end };
+
+        let from = from.max(start);
+        let to = to.min(segment_end);
+        (from < to).then(|| (from - start, to - start))
+    }
+
+    fn delete_selection(&mut self) -> bool {
+        let Some((start, end)) = self.selection() else {
+            return false;
+        };
+        self.selection_anchor = None;
+        self.delete_range(start, end, EditKind::Other);
+        true
+    }
+
+    fn select_all(&mut self) {
+        self.ensure_line_exists(0);
+        self.selection_anchor = Some(TextPosition::default());
+        let last = self.lines.len() - 1;
+        self.set_cursor(TextPosition::new(self.lines[last].content.len(), last));
+        self.history.seal();
+        self.typing_style = None;
+        self.ensure_cursor_visible();
+    }
+
+    // Select the word (or run of whitespace) around a position.
+    fn select_word_at(&mut self, position: TextPosition) {
+        let content = &self.lines[position.vpos].content;
+        let is_word = |c: &char| c.is_alphanumeric() || *c == '_' || *c == '\'';
+        let probe = if position.hpos < content.len() {
+            position.hpos
+        } else {
+            position.hpos.saturating_sub(1)
+        };
+        let Some(kind) = content.get(probe).map(is_word) else {
+            self.selection_anchor = None;
+            self.set_cursor(position);
+            return;
+        };
+
+        let mut start = probe;
+        while start > 0 && is_word(&content[start - 1]) == kind {
+            start -= 1;
+        }
+        let mut end = probe + 1;
+        while end < content.len() && is_word(&content[end]) == kind {
+            end += 1;
+        }
+
+        self.selection_anchor = Some(TextPosition::new(start, position.vpos));
+        self.set_cursor(TextPosition::new(end, position.vpos));
+    }
+
+    fn select_paragraph(&mut self, vpos: usize) {
+        self.selection_anchor = Some(TextPosition::new(0, vpos));
+        self.set_cursor(TextPosition::new(self.lines[vpos].content.len(), vpos));
+    }
+
+    // Map a point on the canvas to the closest position in the document.
+    fn position_at(&self, point: Point) -> TextPosition {
+        let y = point.y + self.scroll_offset_y - self.top_padding();
+        if y >= self.lines.height() {
+            let last = self.lines.len().saturating_sub(1);
+            let len = self.lines.get(last).map_or(0, |line| line.content.len());
+            return TextPosition::new(len, last);
+        }
+
+        let vpos = self.lines.line_at_y(y);
+        let layout = self.lines.layout(vpos);
+        let segment = layout.visual_line_at(y - self.lines.y_offset(vpos));
+        TextPosition::new(layout.hit(segment, point.x - 10.0), vpos)
+    }
+
+    fn clamp_position(&self, position: TextPosition) -> TextPosition {
+        let vpos = position.vpos.min(self.lines.len().saturating_sub(1));
+        let len = self.lines.get(vpos).map_or(0, |line| line.content.len());
+        TextPosition::new(position.hpos.min(len), vpos)
+    }
+
+    fn cursor(&self) -> TextPosition {
+        TextPosition::new(self.cursor_hpos, self.cursor_vpos)
+    }
+
+    fn set_cursor(&mut self, position: TextPosition) {
+        self.cursor_hpos = position.hpos;
+        self.cursor_vpos = position.vpos;
     }
 
     fn handle_arrow_left(&mut self) {
@@ -665,256 +1587,834 @@ impl TextEditorStateInner {
     }
 
     fn handle_arrow_up(&mut self) {
-        if self.cursor_vpos == 0 && self.cursor_hpos == 0 {
-            return; // Already at the start
+        self.move_visual_lines(-1);
+    }
+
+    fn handle_arrow_down(&mut self) {
+        self.move_visual_lines(1);
+    }
+
+    // Ctrl+0 to Ctrl+3 switch between body text and headings, Ctrl+Shift with T, Q or C
+    // picks the title, block quote or caption style.
+    fn paragraph_shortcut(key: &str, modifiers: Modifiers) -> Option<ParagraphStyle> {
+        let key = key.to_ascii_lowercase();
+        match (key.as_str(), modifiers.shift()) {
+            ("0", false) => Some(ParagraphStyle::Body),
+            ("1", false) => Some(ParagraphStyle::Heading1),
+            ("2", false) => Some(ParagraphStyle::Heading2),
+            ("3", false) => Some(ParagraphStyle::Heading3),
+            ("t", true) => Some(ParagraphStyle::Title),
+            ("q", true) => Some(ParagraphStyle::BlockQuote),
+            ("c", true) => Some(ParagraphStyle::Caption),
+            _ => None,
         }
+    }
 
-        let (visual_line, visual_column) =
-            self.logical_to_visual_position(self.cursor_vpos, self.cursor_hpos);
+    // Jump to the start of the previous word, crossing into the previous line at its start.
+    fn handle_word_left(&mut self) {
+        self.ensure_line_exists(self.cursor_vpos);
+        if self.cursor_hpos == 0 {
+            if self.cursor_vpos > 0 {
+                self.cursor_vpos -= 1;
+                self.cursor_hpos = self.lines[self.cursor_vpos].content.len();
+            }
+            self.ensure_cursor_visible();
+            return;
+        }
 
-        if visual_line > 0 {
-            // Move to previous visual line within the same logical line
-            let mut pos = 0;
-            let line = &self.lines[self.cursor_vpos];
-            let mut current_visual = 0;
+        let content = &self.lines[self.cursor_vpos].content;
+        let mut pos = self.cursor_hpos.min(content.len());
+        while pos > 0 && content[pos - 1].is_whitespace() {
+            pos -= 1;
+        }
+        while pos > 0 && !content[pos - 1].is_whitespace() {
+            pos -= 1;
+        }
+        self.cursor_hpos = pos;
+        self.ensure_cursor_visible();
+    }
 
-            while current_visual < visual_line - 1 && pos < line.content.len() {
-                pos = self.find_wrap_position(line, pos, self.max_chars_per_visual_line);
-                current_visual += 1;
+    // Jump to the end of the next word, crossing into the next line at its end.
+    fn handle_word_right(&mut self) {
+        self.ensure_line_exists(self.cursor_vpos);
+        let content = &self.lines[self.cursor_vpos].content;
+        if self.cursor_hpos >= content.len() {
+            if self.cursor_vpos + 1 < self.lines.len() {
+                self.cursor_vpos += 1;
+                self.cursor_hpos = 0;
             }
+            self.ensure_cursor_visible();
+            return;
+        }
 
-            // Try to maintain the same visual column
-            self.cursor_hpos = (pos + visual_column)
-                .min(self.find_wrap_position(line, pos, self.max_chars_per_visual_line) - 1);
-        } else if self.cursor_vpos > 0 {
-            // Move to the previous logical line
-            self.cursor_vpos -= 1;
+        let mut pos = self.cursor_hpos;
+        while pos < content.len() && content[pos].is_whitespace() {
+            pos += 1;
+        }
+        while pos < content.len() && !content[pos].is_whitespace() {
+            pos += 1;
+        }
+        self.cursor_hpos = pos;
+        self.ensure_cursor_visible();
+    }
 
-            // Find the last visual line in the previous logical line
-            let prev_line = &self.lines[self.cursor_vpos];
-            let prev_visual_lines = self.calculate_visual_lines(prev_line);
+    // Home and End stay on the visual line the cursor is on.
+    fn handle_home(&mut self) {
+        self.ensure_line_exists(self.cursor_vpos);
+        let layout = self.lines.layout(self.cursor_vpos);
+        let segment = layout.visual_line(self.cursor_hpos);
+        self.cursor_hpos = layout.segment(segment).start;
+        self.ensure_cursor_visible();
+    }
 
-            if prev_visual_lines > 0 {
-                let mut pos = 0;
-                let mut current_visual = 0;
+    fn handle_end(&mut self) {
+        self.ensure_line_exists(self.cursor_vpos);
+        let layout = self.lines.layout(self.cursor_vpos);
+        let segment = layout.visual_line(self.cursor_hpos);
+        self.cursor_hpos = layout.segment_end(segment);
+        self.ensure_cursor_visible();
+    }
 
-                // Move to the last visual line of the previous logical line
-                while current_visual < prev_visual_lines - 1 && pos < prev_line.content.len() {
-                    pos = self.find_wrap_position(prev_line, pos, self.max_chars_per_visual_line);
-                    current_visual += 1;
-                }
+    fn handle_document_start(&mut self) {
+        self.set_cursor(TextPosition::new(0, 0));
+        self.ensure_cursor_visible();
+    }
 
-                // Try to maintain the same visual column
-                self.cursor_hpos = (pos + visual_column).min(prev_line.content.len());
-            } else {
-                self.cursor_hpos = 0;
-            }
+    fn handle_document_end(&mut self) {
+        self.ensure_line_exists(0);
+        let last = self.lines.len() - 1;
+        self.set_cursor(TextPosition::new(self.lines[last].content.len(), last));
+        self.ensure_cursor_visible();
+    }
+
+    // Move a screenful up or down, keeping one line of context, and scroll along.
+    fn handle_page(&mut self, direction: isize) {
+        let page = ((self.viewport_height / self.line_height).floor() as isize - 1).max(1);
+        self.scroll_offset_y =
+            (self.scroll_offset_y + (direction * page) as f32 * self.line_height).max(0.0);
+        self.move_visual_lines(direction * page);
+    }
+
+    // Move the cursor up or down by whole visual lines, keeping its x position where
+    // possible. Moving past the first or last line goes to the start or end.
+    fn move_visual_lines(&mut self, delta: isize) {
+        self.ensure_line_exists(self.cursor_vpos);
+        let layout = self.lines.layout(self.cursor_vpos);
+        let segment = layout.visual_line(self.cursor_hpos);
+        let x = layout.x(segment, self.cursor_hpos);
+        let current = self.lines.visual_offset(self.cursor_vpos) + segment;
+        let last = self.lines.visual_line_count().saturating_sub(1);
+
+        let target = current as isize + delta;
+        if target < 0 {
+            self.set_cursor(TextPosition::new(0, 0));
+        } else if target as usize > last {
+            let vpos = self.lines.len() - 1;
+            self.set_cursor(TextPosition::new(self.lines[vpos].content.len(), vpos));
+        } else {
+            let (vpos, segment) = self.lines.line_at_visual(target as usize);
+            let hpos = self.lines.layout(vpos).hit(segment, x);
+            self.set_cursor(TextPosition::new(hpos, vpos));
         }
 
         self.ensure_cursor_visible();
     }
 
-    fn handle_arrow_down(&mut self) {
-        if self.cursor_vpos >= self.lines.len() - 1
-            && self.cursor_hpos >= self.lines[self.cursor_vpos].content.len()
-        {
-            return; // Already at the end
+    fn handle_text_input(&mut self, text: &str) {
+        self.ensure_line_exists(self.cursor_vpos);
+        let replaces_selection = self.selection().is_some();
+        if replaces_selection {
+            self.history.begin_group();
+            self.delete_selection();
         }
+        let fragment = self.fragment_from_text(text, self.typing_style());
+        // Typing merges into word-sized steps, anything multi-line stands on its own.
+        let kind = if fragment.len() == 1 {
+            EditKind::Typing
+        } else {
+            EditKind::Other
+        };
+        self.insert_at_cursor(fragment, kind);
+        if replaces_selection {
+            self.history.end_group();
+        }
+        self.ensure_cursor_visible();
+    }
+
+    // Split text on line breaks into lines sharing one style.
+    fn fragment_from_text(&self, text: &str, style: TextStyle) -> Vec<Line> {
+        text.split('\n')
+            .map(|raw| {
+                let mut line = Line::new();
+                line.push_styled(raw.strip_suffix('\r').unwrap_or(raw).chars(), style);
+                line
+            })
+            .collect()
+    }
 
-        let (visual_line, visual_column) =
-            self.logical_to_visual_position(self.cursor_vpos, self.cursor_hpos);
-        let current_line = &self.lines[self.cursor_vpos];
-        let current_line_visual_lines = self.calculate_visual_lines(current_line);
+    fn default_style(&self) -> TextStyle {
+        TextStyle {
+            font: self.default_font,
+            size: self.default_font_size,
+            underline: false,
+        }
+    }
+
+    // The style new text gets: a pending toggle wins, otherwise text continues the
+    // style of the character before the cursor.
+    fn typing_style(&self) -> TextStyle {
+        if let Some(style) = self.typing_style {
+            return style;
+        }
+        let Some(line) = self.lines.get(self.cursor_vpos) else {
+            return self.default_style();
+        };
+        line.style_at(self.cursor_hpos.saturating_sub(1))
+            .unwrap_or_else(|| self.default_style())
+    }
 
-        if visual_line < current_line_visual_lines - 1 {
-            // Move to next visual line within same logical line
-            let mut pos = 0;
-            let mut current_visual = 0;
+    // Give every paragraph touched by the selection a style, as one undo step. If they
+    // all have it already, they go back to body text.
+    fn set_paragraph_style(&mut self, style: ParagraphStyle) {
+        self.ensure_line_exists(self.cursor_vpos);
+        let (start, end) = self.selection().unwrap_or((self.cursor(), self.cursor()));
+        let end_vpos = end.vpos.min(self.lines.len() - 1);
+        let target = if (start.vpos..=end_vpos).all(|vpos| self.lines[vpos].paragraph() == style) {
+            ParagraphStyle::Body
+        } else {
+            style
+        };
 
-            while current_visual <= visual_line && pos < current_line.content.len() {
-                pos = self.find_wrap_position(current_line, pos, self.max_chars_per_visual_line);
-                current_visual += 1;
+        let cursor = self.cursor();
+        self.history.seal();
+        self.history.begin_group();
+        for vpos in start.vpos..=end_vpos {
+            let from = self.lines[vpos].paragraph();
+            if from == target {
+                continue;
             }
+            let edit = Edit::Paragraph {
+                vpos,
+                from,
+                to: target,
+            };
+            self.apply_edit(&edit);
+            self.history.record(edit, cursor, cursor, EditKind::Other);
+        }
+        self.history.end_group();
+        self.ensure_cursor_visible();
+    }
 
-            // Try to maintain same visual column
-            self.cursor_hpos = (pos + visual_column)
-                .min(self.find_wrap_position(current_line, pos, self.max_chars_per_visual_line) - 1)
-                .min(current_line.content.len());
-        } else if self.cursor_vpos < self.lines.len() - 1 {
-            // Move to the next logical line
-            self.cursor_vpos += 1;
+    // Switch a character style on or off. With a selection it is applied to the
+    // selected text as one undo step, switching it off only if all of it already has it.
+    fn toggle_emphasis(&mut self, emphasis: Emphasis) -> bool {
+        let Some((start, end)) = self.selection() else {
+            let mut style = self.typing_style();
+            let on = !emphasis.is_set(&style);
+            emphasis.apply(&mut style, on);
+            self.typing_style = Some(style);
+            return false;
+        };
+
+        let mut fragment = self.copy_range(start, end);
+        let on = !fragment.iter().all(|line| {
+            line.spans()
+                .iter()
+                .all(|span| emphasis.is_set(&span.style()))
+        });
+        for line in &mut fragment {
+            line.restyle(0..line.content.len(), |style| emphasis.apply(style, on));
+        }
+
+        let anchor = self.selection_anchor;
+        let head = self.cursor();
+        self.history.seal();
+        self.history.begin_group();
+        self.delete_range(start, end, EditKind::Other);
+        self.insert_at_cursor(fragment, EditKind::Other);
+        self.history.end_group();
+        self.selection_anchor = anchor;
+        self.set_cursor(head);
+        true
+    }
 
-            // Position cursor at the same visual column on the first visual line
-            self.cursor_hpos = visual_column.min(self.lines[self.cursor_vpos].content.len());
+    // Replace everything from the start of a line to the end of the document with new
+    // paragraphs, as one undo step. Past the last line they are appended.
+    fn replace_to_end(&mut self, vpos: usize, lines: Vec<Line>) {
+        self.ensure_line_exists(0);
+        self.history.seal();
+        self.history.begin_group();
+        let last = self.lines.len() - 1;
+        let end = TextPosition::new(self.lines[last].content.len(), last);
+        if vpos <= last {
+            // The break before the old paragraphs goes too, so none of them is left
+            // behind to pass on its paragraph style.
+            let start = match vpos.checked_sub(1) {
+                Some(previous) => TextPosition::new(self.lines[previous].content.len(), previous),
+                None => TextPosition::new(0, 0),
+            };
+            self.delete_range(start, end, EditKind::Other);
         }
 
+        self.selection_anchor = None;
+        let last = self.lines.len() - 1;
+        self.set_cursor(TextPosition::new(self.lines[last].content.len(), last));
+        let mut fragment = vec![Line::new()];
+        fragment.extend(lines);
+        self.insert_at_cursor(fragment, EditKind::Other);
+        self.history.end_group();
         self.ensure_cursor_visible();
+        self.update_cached_counts();
     }
 
-    fn handle_text_input(&mut self, text: &str) {
-        self.ensure_line_exists(self.cursor_vpos);
-        let chars: Vec<char> = text.chars().collect();
-        for (i, ch) in chars.iter().enumerate() {
-            self.lines[self.cursor_vpos].insert_char(
-                self.cursor_hpos + i,
-                *ch,
-                self.default_font,
-                self.default_font_size,
-            );
+    // Insert text from the clipboard, replacing the selection.
+    fn paste(&mut self, text: &str) {
+        self.history.seal();
+        self.history.begin_group();
+        self.delete_selection();
+        let fragment = self.fragment_from_text(text, self.typing_style());
+        self.insert_at_cursor(fragment, EditKind::Other);
+        self.history.end_group();
+        self.ensure_cursor_visible();
+        self.update_cached_counts();
+    }
+
+    // Copy the selected lines without touching the document.
+    fn copy_range(&self, start: TextPosition, end: TextPosition) -> Vec<Line> {
+        let end = self.clamp_position(end);
+        if end <= start {
+            return vec![Line::new()];
+        }
+        if start.vpos == end.vpos {
+            return vec![self.lines[start.vpos].slice(start.hpos..end.hpos)];
         }
-        self.cursor_hpos += chars.len();
-        self.lines[self.cursor_vpos].ensure_styles_match();
+
+        let first = &self.lines[start.vpos];
+        let mut copied = vec![first.slice(start.hpos..first.content.len())];
+        copied.extend(
+            self.lines
+                .iter_from(start.vpos + 1)
+                .take(end.vpos - start.vpos - 1)
+                .cloned(),
+        );
+        copied.push(self.lines[end.vpos].slice(0..end.hpos));
+        copied
+    }
+
+    fn selected_lines(&self) -> Option<Vec<Line>> {
+        let (start, end) = self.selection()?;
+        Some(self.copy_range(start, end))
+    }
+
+    fn selected_text(&self) -> Option<String> {
+        let (start, end) = self.selection()?;
+        let fragment = self.copy_range(start, end);
+        let lines: Vec<String> = fragment
+            .iter()
+            .map(|line| line.content.iter().collect())
+            .collect();
+        Some(lines.join("\n"))
+    }
+
+    fn cut_selection(&mut self) -> Option<String> {
+        let text = self.selected_text()?;
+        self.history.seal();
+        self.delete_selection();
         self.ensure_cursor_visible();
+        self.update_cached_counts();
+        Some(text)
     }
 
     fn ensure_line_exists(&mut self, index: usize) {
         if self.lines.is_empty() {
             self.lines.push(Line::new());
         }
-        if index >= self.lines.len() {
-            self.lines.resize_with(index + 1, Line::new);
+        while index >= self.lines.len() {
+            self.lines.push(Line::new());
         }
     }
 
-    fn update_max_chars(&mut self) {
+    fn update_wrap_width(&mut self) {
         let padding = 20.0; // 10px on each side
-        let available_width = (self.viewport_width - padding).max(0.0);
-        self.max_chars_per_visual_line =
-            ((available_width / self.char_width).floor() as usize).max(1);
+        let max_width = (self.viewport_width - padding).max(0.0);
+        self.lines.set_params(LayoutParams {
+            max_width,
+            base_size: self.default_font_size,
+            ..self.lines.params().clone()
+        });
     }
 
-    fn calculate_visual_lines(&self, line: &Line) -> usize {
-        let content_len = line.content.len();
-        if content_len == 0 {
-            return 1;
-        }
+    // Text starts one line below the top edge.
+    fn top_padding(&self) -> f32 {
+        self.line_height
+    }
 
-        let max_chars = self.max_chars_per_visual_line;
-        let mut visual_lines = 0;
-        let mut pos = 0;
+    fn viewport_size(&self) -> Size {
+        Size::new(self.viewport_width, self.viewport_height)
+    }
+
+    fn set_viewport_size(&mut self, size: Size) {
+        self.viewport_width = size.width;
+        self.viewport_height = size.height;
+        self.update_wrap_width();
+        self.ensure_cursor_visible();
+    }
 
-        while pos < content_len {
-            let wrap_pos = self.find_wrap_position(line, pos, max_chars);
-            pos = wrap_pos;
-            visual_lines += 1;
+    fn ensure_cursor_visible(&mut self) {
+        if self.cursor_vpos >= self.lines.len() {
+            return;
+        }
+        let layout = self.lines.layout(self.cursor_vpos);
+        let segment = layout.visual_line(self.cursor_hpos);
+        let top = self.lines.y_offset(self.cursor_vpos) + layout.top(segment);
+        let bottom = self.top_padding() + top + layout.line_height(segment);
+
+        if top < self.scroll_offset_y {
+            self.scroll_offset_y = top;
+        } else if bottom > self.scroll_offset_y + self.viewport_height {
+            self.scroll_offset_y = bottom - self.viewport_height;
         }
 
-        visual_lines
+        self.clamp_scroll();
     }
 
-    fn visual_line_count(&self) -> usize {
-        self.lines
-            .iter()
-            .map(|line| self.calculate_visual_lines(line))
-            .sum()
+    // Keep the end of the document from scrolling above the bottom of the viewport.
+    fn clamp_scroll(&mut self) {
+        let content_height = self.top_padding() + self.lines.height();
+        self.scroll_offset_y = self
+            .scroll_offset_y
+            .min(content_height - self.viewport_height)
+            .max(0.0);
+    }
+
+    // Move the cursor to the start of a line and scroll it to the top of the viewport.
+    fn jump_to_line(&mut self, vpos: usize) {
+        self.set_cursor_position(0, vpos);
+        self.focused = true;
+        self.history.seal();
+        self.scroll_offset_y = self.lines.y_offset(self.cursor_vpos);
+        self.clamp_scroll();
+    }
+
+    // Get the current cursor position.
+    fn cursor_position(&self) -> (usize, usize) {
+        (self.cursor_hpos, self.cursor_vpos)
+    }
+
+    // Move the cursor, clamping it to the document.
+    fn set_cursor_position(&mut self, hpos: usize, vpos: usize) {
+        self.selection_anchor = None;
+        self.typing_style = None;
+        self.cursor_vpos = vpos.min(self.lines.len().saturating_sub(1));
+        self.ensure_line_exists(self.cursor_vpos);
+        self.cursor_hpos = hpos.min(self.lines[self.cursor_vpos].content.len());
+        self.ensure_cursor_visible();
+    }
+
+    // Replace the whole document with plain text, using the default style.
+    fn set_text(&mut self, text: &str) {
+        let lines = self.fragment_from_text(text, self.default_style());
+        self.lines = LineRope::with_params(lines, self.lines.params().clone());
+        self.notes = Notes::default();
+        self.renumber_notes();
+        self.respell_all();
+        self.typing_style = None;
+        self.search_stale = true;
+        self.cursor_hpos = 0;
+        self.cursor_vpos = 0;
+        self.selection_anchor = None;
+        self.scroll_offset_y = 0.0;
+        self.history.clear();
+        self.update_cached_counts();
     }
 
-    fn logical_to_visual_position(&self, logical_line_idx: usize, hpos: usize) -> (usize, usize) {
-        if logical_line_idx >= self.lines.len() {
-            return (0, 0);
+    // Replace the whole document with already styled lines and their notes.
+    fn set_contents(&mut self, mut lines: Vec<Line>, notes: Notes) {
+        for line in &mut lines {
+            line.ensure_styles_match();
         }
+        self.lines = LineRope::with_params(lines, self.lines.params().clone());
+        self.ensure_line_exists(0);
+        self.notes = notes;
+        self.renumber_notes();
+        self.respell_all();
+        self.typing_style = None;
+        self.search_stale = true;
+        self.cursor_hpos = 0;
+        self.cursor_vpos = 0;
+        self.selection_anchor = None;
+        self.scroll_offset_y = 0.0;
+        self.history.clear();
+        self.update_cached_counts();
+    }
 
-        let line = &self.lines[logical_line_idx];
+    // === Notes ===
+
+    // Number the notes again after anchors were added, removed or moved. Anchors are
+    // as wide as their numbers, so a changed number lays out the lines again.
+    fn renumber_notes(&mut self) {
+        let numbering = Numbering::of(self.lines.iter(), &self.notes);
+        if *self.lines.params().notes != numbering {
+            self.lines.set_params(LayoutParams {
+                notes: Arc::new(numbering),
+                ..self.lines.params().clone()
+            });
+        }
+    }
 
-        if line.content.is_empty() {
-            return (0, 0);
+    // Anchor a new, empty note after the cursor or the selection.
+    fn insert_note(&mut self, kind: NoteKind) -> Option<u32> {
+        let id = self.notes.add(kind)?;
+        if let Some((_, end)) = self.selection() {
+            self.selection_anchor = None;
+            self.set_cursor(end);
         }
+        let fragment = self.fragment_from_text(&notes::anchor(id).to_string(), self.typing_style());
+        self.history.seal();
+        self.insert_at_cursor(fragment, EditKind::Other);
+        self.history.seal();
+        self.ensure_cursor_visible();
+        self.update_cached_counts();
+        Some(id)
+    }
 
-        let max_chars = self.max_chars_per_visual_line;
+    fn set_note_body(&mut self, id: u32, body: String) {
+        self.notes.set_body(id, body);
+    }
 
-        let mut visual_line = 0;
-        let mut pos = 0;
+    fn convert_notes(&mut self, from: NoteKind, to: NoteKind) -> usize {
+        let count = self.notes.convert(from, to);
+        self.renumber_notes();
+        count
+    }
 
-        while pos < hpos {
-            let wrap_pos = self.find_wrap_position(line, pos, max_chars);
-            if wrap_pos >= hpos || wrap_pos <= pos {
-                break;
-            }
-            pos = wrap_pos;
-            visual_line += 1;
+    // The anchored notes in the order of their anchors, with their labels.
+    fn note_entries(&self) -> Vec<(u32, String, Note)> {
+        let numbering = &self.lines.params().notes;
+        numbering
+            .order()
+            .iter()
+            .map(|id| {
+                let note = self.notes.get(*id).cloned().unwrap_or_default();
+                (*id, numbering.label(*id).to_string(), note)
+            })
+            .collect()
+    }
+
+    // The notes worth saving, which are those still anchored in the text.
+    fn notes(&self) -> Notes {
+        self.notes.anchored(&self.lines.params().notes)
+    }
+
+    // === Spelling ===
+
+    fn set_speller(&mut self, speller: Option<Speller>) {
+        self.speller = speller;
+        self.respell_all();
+    }
+
+    fn respell_all(&mut self) {
+        self.misspellings = match &self.speller {
+            Some(speller) => self
+                .lines
+                .iter()
+                .map(|line| speller.misspelled(line))
+                .collect(),
+            None => Vec::new(),
+        };
+    }
+
+    // Check lines again after an edit turned `replaced` lines from `vpos` on into
+    // `count` lines.
+    fn respell(&mut self, vpos: usize, replaced: usize, count: usize) {
+        let Some(speller) = &self.speller else {
+            return;
+        };
+        let checked: Vec<_> = (vpos..vpos + count)
+            .map(|n| speller.misspelled(&self.lines[n]))
+            .collect();
+        // Lines added to reach the edit were empty, with nothing misspelled.
+        self.misspellings
+            .resize(self.lines.len() + replaced - count, Vec::new());
+        self.misspellings.splice(vpos..vpos + replaced, checked);
+    }
+
+    fn misspellings_on_line(&self, vpos: usize) -> &[std::ops::Range<usize>] {
+        self.misspellings.get(vpos).map_or(&[], Vec::as_slice)
+    }
+
+    // The misspelled word at the cursor or under the selection, with suggestions.
+    fn misspelling(&self) -> Option<Misspelling> {
+        let speller = self.speller.as_ref()?;
+        let (start, end) = self.selection().unwrap_or((self.cursor(), self.cursor()));
+        if start.vpos != end.vpos {
+            return None;
         }
+        let range = self
+            .misspellings_on_line(start.vpos)
+            .iter()
+            .find(|word| word.start <= start.hpos && end.hpos <= word.end)?
+            .clone();
+        let word: String = self.lines[start.vpos].content[range.clone()]
+            .iter()
+            .collect();
+        Some(Misspelling {
+            vpos: start.vpos,
+            suggestions: speller.suggest(&word),
+            range,
+            word,
+        })
+    }
 
-        let visual_column = hpos - pos;
-        (visual_line, visual_column)
+    // Select the next misspelled word after the cursor, wrapping around the document.
+    fn next_misspelling(&mut self) -> Option<Misspelling> {
+        let after = self.selection().map_or(self.cursor(), |(_, end)| end);
+        let words = || {
+            self.misspellings
+                .iter()
+                .enumerate()
+                .flat_map(|(vpos, words)| {
+                    words.iter().map(move |word| {
+                        (
+                            TextPosition::new(word.start, vpos),
+                            TextPosition::new(word.end, vpos),
+                        )
+                    })
+                })
+        };
+        let (start, end) = words()
+            .find(|(start, _)| *start >= after)
+            .or_else(|| words().next())?;
+
+        self.history.seal();
+        self.typing_style = None;
+        self.selection_anchor = Some(start);
+        self.set_cursor(end);
+        self.ensure_cursor_visible();
+        self.misspelling()
     }
 
-    fn ensure_cursor_visible(&mut self) {
-        let total_visual_lines = self.visual_line_count();
-        let cursor_visual_line = self
-            .logical_to_visual_position(self.cursor_vpos, self.cursor_hpos)
-            .0;
-        let cursor_y = cursor_visual_line as f32 * self.line_height;
+    // Replace a misspelled word, unless its text changed since it was found. The
+    // replacement takes the style of the word.
+    fn correct(&mut self, misspelling: &Misspelling, replacement: &str) -> bool {
+        let Some(line) = self.lines.get(misspelling.vpos) else {
+            return false;
+        };
+        let current: Option<String> = line
+            .content
+            .get(misspelling.range.clone())
+            .map(|word| word.iter().collect());
+        if current.as_deref() != Some(misspelling.word.as_str()) {
+            return false;
+        }
+        let style = line
+            .style_at(misspelling.range.start)
+            .unwrap_or_else(|| self.default_style());
+
+        let start = TextPosition::new(misspelling.range.start, misspelling.vpos);
+        let end = TextPosition::new(misspelling.range.end, misspelling.vpos);
+        self.selection_anchor = None;
+        self.history.seal();
+        self.history.begin_group();
+        self.delete_range(start, end, EditKind::Other);
+        let fragment = self.fragment_from_text(replacement, style);
+        self.insert_at_cursor(fragment, EditKind::Other);
+        self.history.end_group();
+        self.ensure_cursor_visible();
+        self.update_cached_counts();
+        true
+    }
 
-        if cursor_y < self.scroll_offset_y {
-            self.scroll_offset_y = cursor_y;
-        } else if cursor_y + self.line_height > self.scroll_offset_y + self.viewport_height {
-            self.scroll_offset_y = cursor_y + self.line_height - self.viewport_height;
+    fn add_to_dictionary(&mut self, word: &str) {
+        if let Some(speller) = &mut self.speller {
+            speller.add(word);
         }
+        self.respell_all();
+    }
 
-        self.scroll_offset_y = self
-            .scroll_offset_y
-            .max(0.0)
-            .min((total_visual_lines as f32 * self.line_height - self.viewport_height).max(0.0));
+    fn ignore_word(&mut self, word: &str) {
+        if let Some(speller) = &mut self.speller {
+            speller.ignore(word);
+        }
+        self.respell_all();
+    }
+
+    fn dictionary_name(&self) -> Option<String> {
+        self.speller
+            .as_ref()
+            .map(|speller| speller.name().to_string())
     }
 
-    fn find_wrap_position(&self, line: &Line, start: usize, max_chars: usize) -> usize {
-        let content = &line.content;
-        let end = (start + max_chars).min(content.len());
+    // === Style Check ===
 
-        // If we can't fit at least one character or we fit the whole content, return as is
-        if start >= end || end == content.len() {
-            return end;
+    fn set_lint(&mut self, lint: Option<Lint>) {
+        self.lint = lint;
+    }
+
+    // Select a range within a line, as long as the line still reaches that far.
+    fn select_range(&mut self, vpos: usize, range: std::ops::Range<usize>) -> bool {
+        let Some(line) = self.lines.get(vpos) else {
+            return false;
+        };
+        if range.end > line.content.len() {
+            return false;
         }
+        self.history.seal();
+        self.typing_style = None;
+        self.focused = true;
+        self.selection_anchor = Some(TextPosition::new(range.start, vpos));
+        self.set_cursor(TextPosition::new(range.end, vpos));
+        self.ensure_cursor_visible();
+        true
+    }
+
+    // === Find and Replace ===
+
+    fn set_search(&mut self, search: Option<Search>) {
+        self.search = search;
+        self.refresh_search();
+    }
 
-        // Look for a space to break at
-        for i in (start..end).rev() {
-            if content[i].is_whitespace() {
-                return i + 1; // Break after the whitespace
+    fn refresh_search(&mut self) {
+        self.search_matches.clear();
+        self.search_stale = false;
+        let Some(search) = &self.search else {
+            return;
+        };
+        for (vpos, line) in self.lines.iter().enumerate() {
+            for range in search.find_in(line) {
+                self.search_matches.push((
+                    TextPosition::new(range.start, vpos),
+                    TextPosition::new(range.end, vpos),
+                ));
             }
         }
+    }
 
-        // If no space was found, we have to break in the middle of a word
-        end
+    fn matches_on_line(&self, vpos: usize) -> &[(TextPosition, TextPosition)] {
+        let start = self
+            .search_matches
+            .partition_point(|(start, _)| start.vpos < vpos);
+        let end = self
+            .search_matches
+            .partition_point(|(start, _)| start.vpos <= vpos);
+        &self.search_matches[start..end]
     }
 
-    // Get the current cursor position.
-    fn cursor_position(&self) -> (usize, usize) {
-        (self.cursor_hpos, self.cursor_vpos)
+    fn match_count(&mut self) -> usize {
+        if self.search_stale {
+            self.refresh_search();
+        }
+        self.search_matches.len()
     }
 
-    // Get text content as a string.
-    fn text(&self) -> String {
-        let mut result = String::with_capacity(self.estimate_text_capacity());
-        for (i, line) in self.lines.iter().enumerate() {
-            if i > 0 {
-                result.push('\n');
-            }
-            result.extend(line.content.iter());
+    // Select the next or previous match, wrapping around the document.
+    fn find_next(&mut self, forward: bool) -> bool {
+        if self.match_count() == 0 {
+            return false;
         }
-        result
+        let (sel_start, sel_end) = self.selection().unwrap_or((self.cursor(), self.cursor()));
+        let found = if forward {
+            self.search_matches
+                .iter()
+                .position(|(start, end)| {
+                    *start >= sel_end && (*start, *end) != (sel_start, sel_end)
+                })
+                .unwrap_or(0)
+        } else {
+            self.search_matches
+                .iter()
+                .rposition(|(start, _)| *start < sel_start)
+                .unwrap_or(self.search_matches.len() - 1)
+        };
+
+        let (start, end) = self.search_matches[found];
+        self.selection_anchor = Some(start);
+        self.set_cursor(end);
+        self.history.seal();
+        self.typing_style = None;
+        self.ensure_cursor_visible();
+        true
     }
 
-    fn estimate_text_capacity(&self) -> usize {
-        let mut capacity = 0;
-        for (i, line) in self.lines.iter().enumerate() {
-            if i > 0 {
-                capacity += 1;
+    // The replacement for a match, in the style of the text it replaces.
+    fn replacement_for(
+        &self,
+        start: TextPosition,
+        end: TextPosition,
+        replacement: &str,
+    ) -> Vec<Line> {
+        let line = &self.lines[start.vpos];
+        let text = match &self.search {
+            Some(search) => search.replacement(line, start.hpos..end.hpos, replacement),
+            None => replacement.to_string(),
+        };
+        let style = line
+            .style_at(start.hpos)
+            .unwrap_or_else(|| self.default_style());
+        self.fragment_from_text(&text, style)
+    }
+
+    // Replace the selected match, if the selection is one, and move on to the next.
+    fn replace_current(&mut self, replacement: &str) -> bool {
+        self.match_count();
+        let replaced = match self.selection() {
+            Some(selected) if self.search_matches.contains(&selected) => {
+                let (start, end) = selected;
+                let fragment = self.replacement_for(start, end, replacement);
+                self.selection_anchor = None;
+                self.history.seal();
+                self.history.begin_group();
+                self.delete_range(start, end, EditKind::Other);
+                self.insert_at_cursor(fragment, EditKind::Other);
+                self.history.end_group();
+                self.update_cached_counts();
+                true
             }
-            capacity += line.content.len();
-        }
-        capacity
+            _ => false,
+        };
+        self.find_next(true);
+        replaced
     }
 
-    fn update_cached_counts(&mut self) {
-        let mut char_count = 0;
-        for line in &self.lines {
-            if !line.content.is_empty() && line.content[0] != '\n' && line.content[0] != ' ' {
-                char_count += line.content.len();
-            };
+    // Replace every match as a single undo step. Only the matched text is touched,
+    // so the styling of everything around it survives.
+    fn replace_all(&mut self, replacement: &str) -> usize {
+        self.match_count();
+        let replacements: Vec<_> = self
+            .search_matches
+            .iter()
+            .map(|(start, end)| {
+                (
+                    *start,
+                    *end,
+                    self.replacement_for(*start, *end, replacement),
+                )
+            })
+            .collect();
+        if replacements.is_empty() {
+            return 0;
         }
 
-        self.cached_char_count = char_count;
-        self.cached_word_count = self.text().split_whitespace().count();
+        self.selection_anchor = None;
+        self.history.seal();
+        self.history.begin_group();
+        // Work backwards so earlier matches keep their positions.
+        for (start, end, fragment) in replacements.iter().rev() {
+            self.delete_range(*start, *end, EditKind::Other);
+            self.insert_at_cursor(fragment.clone(), EditKind::Other);
+        }
+        self.history.end_group();
+        self.ensure_cursor_visible();
+        self.update_cached_counts();
+        replacements.len()
+    }
+
+    // The rope keeps running totals, so this no longer walks the document.
+    fn update_cached_counts(&mut self) {
+        let summary = self.lines.summary();
+        self.cached_char_count = summary.chars;
+        self.cached_word_count = summary.words;
     }
 
     fn word_count(&self) -> usize {
@@ -928,18 +2428,21 @@ impl TextEditorStateInner {
     fn line_count(&self) -> usize {
         self.lines.len()
     }
+
+    fn outline(&self) -> Vec<Heading> {
+        outline::headings(self.lines.iter())
+    }
+
+    fn paragraph_style(&self) -> ParagraphStyle {
+        self.lines
+            .get(self.cursor_vpos)
+            .map_or_else(ParagraphStyle::default, Line::paragraph)
+    }
 }
 
 // === Public Methods on TextEditorState ===
 
 impl TextEditorState {
-    pub fn set_viewport_size(&self, size: Size) {
-        let mut inner = self.inner.borrow_mut();
-        inner.viewport_width = size.width;
-        inner.viewport_height = size.height;
-        inner.update_max_chars();
-    }
-
     pub fn toggle_cursor_visibility(&self) {
         self.inner.borrow_mut().cursor_visible ^= true;
     }
@@ -948,6 +2451,10 @@ impl TextEditorState {
         self.inner.borrow().cursor_position()
     }
 
+    pub fn set_cursor_position(&self, hpos: usize, vpos: usize) {
+        self.inner.borrow_mut().set_cursor_position(hpos, vpos);
+    }
+
     pub fn word_count(&self) -> usize {
         self.inner.borrow().word_count()
     }
@@ -960,6 +2467,114 @@ impl TextEditorState {
         self.inner.borrow().line_count()
     }
 
+    pub fn paragraph_style(&self) -> ParagraphStyle {
+        self.inner.borrow().paragraph_style()
+    }
+
+    pub fn outline(&self) -> Vec<Heading> {
+        self.inner.borrow().outline()
+    }
+
+    pub fn jump_to_line(&self, vpos: usize) {
+        self.inner.borrow_mut().jump_to_line(vpos);
+    }
+
+    pub fn set_text(&self, text: &str) {
+        self.inner.borrow_mut().set_text(text);
+    }
+
+    pub fn set_contents(&self, lines: Vec<Line>, notes: Notes) {
+        self.inner.borrow_mut().set_contents(lines, notes);
+    }
+
+    pub fn insert_note(&self, kind: NoteKind) -> Option<u32> {
+        self.inner.borrow_mut().insert_note(kind)
+    }
+
+    pub fn set_note_body(&self, id: u32, body: String) {
+        self.inner.borrow_mut().set_note_body(id, body);
+    }
+
+    pub fn convert_notes(&self, from: NoteKind, to: NoteKind) -> usize {
+        self.inner.borrow_mut().convert_notes(from, to)
+    }
+
+    pub fn note_entries(&self) -> Vec<(u32, String, Note)> {
+        self.inner.borrow().note_entries()
+    }
+
+    pub fn notes(&self) -> Notes {
+        self.inner.borrow().notes()
+    }
+
+    pub fn set_speller(&self, speller: Option<Speller>) {
+        self.inner.borrow_mut().set_speller(speller);
+    }
+
+    pub fn misspelling(&self) -> Option<Misspelling> {
+        self.inner.borrow().misspelling()
+    }
+
+    pub fn next_misspelling(&self) -> Option<Misspelling> {
+        self.inner.borrow_mut().next_misspelling()
+    }
+
+    pub fn correct(&self, misspelling: &Misspelling, replacement: &str) -> bool {
+        self.inner.borrow_mut().correct(misspelling, replacement)
+    }
+
+    pub fn add_to_dictionary(&self, word: &str) {
+        self.inner.borrow_mut().add_to_dictionary(word);
+    }
+
+    pub fn ignore_word(&self, word: &str) {
+        self.inner.borrow_mut().ignore_word(word);
+    }
+
+    pub fn dictionary_name(&self) -> Option<String> {
+        self.inner.borrow().dictionary_name()
+    }
+
+    pub fn set_lint(&self, lint: Option<Lint>) {
+        self.inner.borrow_mut().set_lint(lint);
+    }
+
+    pub fn select_range(&self, vpos: usize, range: std::ops::Range<usize>) -> bool {
+        self.inner.borrow_mut().select_range(vpos, range)
+    }
+
+    pub fn selection(&self) -> Option<(TextPosition, TextPosition)> {
+        self.inner.borrow().selection()
+    }
+
+    pub fn selected_lines(&self) -> Option<Vec<Line>> {
+        self.inner.borrow().selected_lines()
+    }
+
+    pub fn selected_text(&self) -> Option<String> {
+        self.inner.borrow().selected_text()
+    }
+
+    pub fn cut_selection(&self) -> Option<String> {
+        self.inner.borrow_mut().cut_selection()
+    }
+
+    pub fn paste(&self, text: &str) {
+        self.inner.borrow_mut().paste(text);
+    }
+
+    pub fn replace_to_end(&self, vpos: usize, lines: Vec<Line>) {
+        self.inner.borrow_mut().replace_to_end(vpos, lines);
+    }
+
+    pub fn default_style(&self) -> TextStyle {
+        self.inner.borrow().default_style()
+    }
+
+    pub fn all_lines(&self) -> Vec<Line> {
+        self.inner.borrow().lines.to_vec()
+    }
+
     #[allow(dead_code)]
     pub fn line(&self, n: usize) -> Line {
         self.inner.borrow().lines[n].clone()
@@ -968,8 +2583,31 @@ impl TextEditorState {
     // Since returning a slice from a RefCell is tricky, we return a vector of lines.
     pub fn lines(&self, n: usize) -> Vec<Line> {
         let inner = self.inner.borrow();
-        let end = n.min(inner.lines.len());
-        inner.lines[0..end].to_vec()
+        inner.lines.iter().take(n).cloned().collect()
+    }
+
+    pub fn set_focused(&self, focused: bool) {
+        self.inner.borrow_mut().focused = focused;
+    }
+
+    pub fn set_search(&self, search: Option<Search>) {
+        self.inner.borrow_mut().set_search(search);
+    }
+
+    pub fn match_count(&self) -> usize {
+        self.inner.borrow_mut().match_count()
+    }
+
+    pub fn find_next(&self, forward: bool) -> bool {
+        self.inner.borrow_mut().find_next(forward)
+    }
+
+    pub fn replace_current(&self, replacement: &str) -> bool {
+        self.inner.borrow_mut().replace_current(replacement)
+    }
+
+    pub fn replace_all(&self, replacement: &str) -> usize {
+        self.inner.borrow_mut().replace_all(replacement)
     }
 }
 
@@ -999,8 +2637,8 @@ impl TextEditorWidget {
         {
             let mut inner = self.state.inner.borrow_mut();
             inner.default_font_size = size;
-            inner.line_height = size * 1.2;
-            inner.char_width = size * 0.6;
+            inner.line_height = size * LINE_SPACING;
+            inner.update_wrap_width();
         }
         self
     }
@@ -1023,9 +2661,9 @@ impl TextEditorWidget {
 
     pub fn handle_event(&mut self, event: &Event) -> Option<TextEditorMessage> {
         match event {
-            Event::Window(iced::window::Event::Resized(size)) => {
-                self.state
-                    .set_viewport_size(Size::new(size.width, size.height));
+            Event::Window(iced::window::Event::Resized(_)) => {
+                // The canvas picks up its new bounds the next time it is drawn, which
+                // also leaves room for the rest of the window.
                 None
             }
             _ => None, // Other events are handled directly by the canvas.
@@ -1041,6 +2679,10 @@ impl TextEditorWidget {
         self.state.cursor_position()
     }
 
+    pub fn set_cursor_position(&mut self, hpos: usize, vpos: usize) {
+        self.state.set_cursor_position(hpos, vpos);
+    }
+
     pub fn word_count(&self) -> usize {
         self.state.word_count()
     }
@@ -1053,10 +2695,142 @@ impl TextEditorWidget {
         self.state.line_count()
     }
 
+    pub fn paragraph_style(&self) -> ParagraphStyle {
+        self.state.paragraph_style()
+    }
+
+    pub fn outline(&self) -> Vec<Heading> {
+        self.state.outline()
+    }
+
+    pub fn jump_to_line(&mut self, vpos: usize) {
+        self.state.jump_to_line(vpos);
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.state.set_text(text);
+    }
+
+    pub fn set_contents(&mut self, lines: Vec<Line>, notes: Notes) {
+        self.state.set_contents(lines, notes);
+    }
+
+    pub fn insert_note(&mut self, kind: NoteKind) -> Option<u32> {
+        self.state.insert_note(kind)
+    }
+
+    pub fn set_note_body(&mut self, id: u32, body: String) {
+        self.state.set_note_body(id, body);
+    }
+
+    pub fn convert_notes(&mut self, from: NoteKind, to: NoteKind) -> usize {
+        self.state.convert_notes(from, to)
+    }
+
+    pub fn note_entries(&self) -> Vec<(u32, String, Note)> {
+        self.state.note_entries()
+    }
+
+    pub fn notes(&self) -> Notes {
+        self.state.notes()
+    }
+
+    pub fn set_speller(&mut self, speller: Option<Speller>) {
+        self.state.set_speller(speller);
+    }
+
+    pub fn misspelling(&self) -> Option<Misspelling> {
+        self.state.misspelling()
+    }
+
+    pub fn next_misspelling(&mut self) -> Option<Misspelling> {
+        self.state.next_misspelling()
+    }
+
+    pub fn correct(&mut self, misspelling: &Misspelling, replacement: &str) -> bool {
+        self.state.correct(misspelling, replacement)
+    }
+
+    pub fn add_to_dictionary(&mut self, word: &str) {
+        self.state.add_to_dictionary(word);
+    }
+
+    pub fn ignore_word(&mut self, word: &str) {
+        self.state.ignore_word(word);
+    }
+
+    pub fn dictionary_name(&self) -> Option<String> {
+        self.state.dictionary_name()
+    }
+
+    pub fn set_lint(&mut self, lint: Option<Lint>) {
+        self.state.set_lint(lint);
+    }
+
+    pub fn select_range(&mut self, vpos: usize, range: std::ops::Range<usize>) -> bool {
+        self.state.select_range(vpos, range)
+    }
+
+    pub fn selection(&self) -> Option<(TextPosition, TextPosition)> {
+        self.state.selection()
+    }
+
+    pub fn selected_lines(&self) -> Option<Vec<Line>> {
+        self.state.selected_lines()
+    }
+
+    pub fn selected_text(&self) -> Option<String> {
+        self.state.selected_text()
+    }
+
+    pub fn cut_selection(&mut self) -> Option<String> {
+        self.state.cut_selection()
+    }
+
+    pub fn paste(&mut self, text: &str) {
+        self.state.paste(text);
+    }
+
+    pub fn replace_to_end(&mut self, vpos: usize, lines: Vec<Line>) {
+        self.state.replace_to_end(vpos, lines);
+    }
+
+    pub fn default_style(&self) -> TextStyle {
+        self.state.default_style()
+    }
+
+    pub fn all_lines(&self) -> Vec<Line> {
+        self.state.all_lines()
+    }
+
     #[allow(dead_code)]
     pub fn lines(&self, n: usize) -> Vec<Line> {
         self.state.lines(n)
     }
+
+    pub fn set_focused(&mut self, focused: bool) {
+        self.state.set_focused(focused);
+    }
+
+    pub fn set_search(&mut self, search: Option<Search>) {
+        self.state.set_search(search);
+    }
+
+    pub fn match_count(&self) -> usize {
+        self.state.match_count()
+    }
+
+    pub fn find_next(&mut self, forward: bool) -> bool {
+        self.state.find_next(forward)
+    }
+
+    pub fn replace_current(&mut self, replacement: &str) -> bool {
+        self.state.replace_current(replacement)
+    }
+
+    pub fn replace_all(&mut self, replacement: &str) -> usize {
+        self.state.replace_all(replacement)
+    }
 }
 
 impl Default for TextEditorWidget {
@@ -1064,3 +2838,28 @@ impl Default for TextEditorWidget {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn note_anchors_are_not_counted() {
+        let anchor = notes::anchor(0);
+        let cases = [
+            (format!("Two words{}", anchor), 2, 9),
+            (format!("Two words {}", anchor), 2, 10),
+            (format!("{} {} ", anchor, notes::anchor(1)), 0, 2),
+        ];
+        for (text, words, chars) in cases {
+            let mut line = Line::new();
+            line.push_run(text.chars(), Font::DEFAULT, 16.0);
+            assert_eq!(
+                (line.word_count(), line.char_count()),
+                (words, chars),
+                "{:?}",
+                text
+            );
+        }
+    }
+}